@@ -0,0 +1,173 @@
+//! Running top-k over a corpus that arrives in chunks, e.g. pages of a
+//! paginated API or pieces of a streaming fetch, so the whole corpus never
+//! has to be held at once.
+
+use std::collections::HashSet;
+
+use wasm_bindgen::prelude::*;
+
+use crate::calibration::normalized_scores;
+use crate::cutoff::apply_auto_k;
+use crate::metric::Scorer;
+use crate::search::{QueryOptions, SearchHit, TopK};
+use crate::to_js;
+
+/// Bounded best-`k` collector fed one chunk at a time.
+///
+/// Each chunk carries the `offset` of its first vector in the overall
+/// corpus, and results report those global indices. Options are fixed at
+/// construction: `metric` and `weights` drive scoring, `exclude` lists global
+/// indices to skip, and `autoK`, `normalize` and `scoreRounding` are applied
+/// by `finalize`.
+#[wasm_bindgen]
+pub struct TopKAccumulator {
+    k: usize,
+    top: TopK,
+    query: Option<Vec<f64>>,
+    options: QueryOptions,
+    excluded: HashSet<usize>,
+    seen: usize,
+}
+
+#[wasm_bindgen]
+impl TopKAccumulator {
+    /// Accumulator for precomputed scores, fed with `pushScores`
+    #[wasm_bindgen(constructor)]
+    pub fn new(k: usize, options: JsValue) -> Result<TopKAccumulator, JsValue> {
+        let options = QueryOptions::from_js(options)?;
+        Ok(Self::with_options(k, None, options))
+    }
+
+    /// Accumulator that also scores raw chunks against `query` in
+    /// `pushChunk`
+    #[wasm_bindgen(js_name = "forQuery")]
+    pub fn for_query(
+        query: Vec<f64>,
+        k: usize,
+        options: JsValue,
+    ) -> Result<TopKAccumulator, JsValue> {
+        if query.is_empty() {
+            panic!("Query vector must have at least one dimension");
+        }
+
+        let options = QueryOptions::from_js(options)?;
+        if let Some(weights) = &options.weights {
+            if weights.len() != query.len() {
+                panic!("Weight vector dimension mismatch");
+            }
+        }
+        Ok(Self::with_options(k, Some(query), options))
+    }
+
+    /// Offer scores for the vectors at `offset`, `offset + 1`, ...
+    #[wasm_bindgen(js_name = "pushScores")]
+    pub fn push_scores(&mut self, scores: &[f64], offset: usize) {
+        for (i, &score) in scores.iter().enumerate() {
+            self.offer(offset + i, score);
+        }
+        self.seen += scores.len();
+    }
+
+    /// Score a flattened chunk of `count` vectors against the query and
+    /// offer them, the first being the corpus vector at `offset`. If a
+    /// `customMetric` callback throws, nothing from the chunk is offered and
+    /// the exception is rethrown.
+    #[wasm_bindgen(js_name = "pushChunk")]
+    pub fn push_chunk(
+        &mut self,
+        vectors: &[f64],
+        count: usize,
+        offset: usize,
+    ) -> Result<(), JsValue> {
+        let Some(query) = &self.query else {
+            panic!("pushChunk needs a query; create the accumulator with forQuery");
+        };
+        if vectors.len() != count * query.len() {
+            panic!("Vectors array size mismatch");
+        }
+        if count == 0 {
+            return Ok(());
+        }
+
+        let scorer = Scorer::new(
+            query,
+            self.options.metric,
+            self.options.weights.as_deref(),
+            self.options.assume_normalized,
+        )
+        .truncated(self.options.search_dims)
+        .with_callback(self.options.custom_metric.as_ref());
+        let mut scores = Vec::with_capacity(count);
+        for vector in vectors.chunks_exact(query.len()) {
+            scores.push(scorer.score(vector));
+            scorer.check()?;
+        }
+
+        self.push_scores(&scores, offset);
+        Ok(())
+    }
+
+    /// Number of scores offered so far, including excluded ones
+    #[wasm_bindgen(getter)]
+    pub fn seen(&self) -> usize {
+        self.seen
+    }
+
+    /// Score of the worst retained result once `k` are held; later scores
+    /// that don't beat it cannot enter the results
+    #[wasm_bindgen(getter)]
+    pub fn threshold(&self) -> Option<f64> {
+        self.top.threshold()
+    }
+
+    /// Current results as `{ index, score }` objects ordered best-first.
+    /// The accumulator is left intact, so it can keep receiving chunks.
+    pub fn finalize(&self) -> Result<JsValue, JsValue> {
+        to_js(&self.hits())
+    }
+}
+
+impl TopKAccumulator {
+    pub(crate) fn with_options(k: usize, query: Option<Vec<f64>>, options: QueryOptions) -> Self {
+        if options.groups.is_some() || options.allowed_groups.is_some() {
+            panic!("Group labels are not supported by TopKAccumulator");
+        }
+        if options.parents.is_some() {
+            panic!("Parent IDs are not supported by TopKAccumulator");
+        }
+
+        Self {
+            k,
+            top: TopK::new(k, options.metric),
+            query,
+            excluded: options.exclude.iter().copied().collect(),
+            options,
+            seen: 0,
+        }
+    }
+
+    fn offer(&mut self, index: usize, score: f64) {
+        // NaN scores have no place in the ordering
+        if !score.is_nan() && !self.excluded.contains(&index) {
+            self.top.push(index, score);
+        }
+    }
+
+    pub(crate) fn hits(&self) -> Vec<SearchHit> {
+        let mut ranked = self.top.clone().into_sorted();
+        if let Some(auto_k) = &self.options.auto_k {
+            apply_auto_k(&mut ranked, auto_k, self.k);
+        }
+
+        let normalized = normalized_scores(&ranked, &self.options);
+        ranked
+            .into_iter()
+            .zip(normalized)
+            .map(|((index, score), normalized)| SearchHit {
+                index,
+                score: self.options.reported_score(score),
+                normalized,
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,414 @@
+use std::collections::HashMap;
+use std::hint::black_box;
+
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::events;
+use crate::trace::now_ms;
+use crate::{options_from_js, to_js, VectorBenchmark, VectorSearch};
+
+/// Options for `benchmarkReport` and `measuredBenchmarkReport`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BenchmarkOptions {
+    /// Record how much wasm memory and JS heap each operation grew
+    pub memory: bool,
+    /// Time iterations in this many batches and drop slow outlier batches,
+    /// such as ones a GC pause landed in, from the mean. 0 or 1 times the
+    /// run as one block.
+    pub batches: usize,
+}
+
+impl Default for BenchmarkOptions {
+    fn default() -> Self {
+        Self {
+            memory: false,
+            batches: 1,
+        }
+    }
+}
+
+/// Memory growth while an operation ran, in bytes
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryDelta {
+    /// Growth of wasm linear memory, which never shrinks
+    pub wasm_bytes: f64,
+    /// Change in `performance.memory.usedJSHeapSize`, where the browser
+    /// exposes it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub js_heap_bytes: Option<f64>,
+}
+
+/// Timing for a single benchmarked operation
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperationTiming {
+    pub name: String,
+    pub total_ms: f64,
+    /// Mean over the iterations kept after outlier batches are dropped
+    pub mean_ms: f64,
+    /// Batches left out of `mean_ms`, present when timing in batches
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outlier_batches: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<MemoryDelta>,
+}
+
+/// Machine-readable benchmark artifact, serialized as JSON
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BenchmarkReport {
+    pub dimensions: usize,
+    pub iterations: usize,
+    pub operations: Vec<OperationTiming>,
+    /// Change in `performance.measureUserAgentSpecificMemory()` bytes over
+    /// the whole run, from `measuredBenchmarkReport` where the API is
+    /// available
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_agent_bytes: Option<f64>,
+}
+
+/// Allowed slowdown per operation, in percent of the baseline mean
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RegressionThresholds {
+    /// Threshold applied to operations without their own entry
+    pub max_regression_pct: f64,
+    /// Per-operation overrides keyed by operation name
+    pub operations: HashMap<String, f64>,
+}
+
+impl Default for RegressionThresholds {
+    fn default() -> Self {
+        Self {
+            max_regression_pct: 10.0,
+            operations: HashMap::new(),
+        }
+    }
+}
+
+/// Change of one operation between two reports
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperationDelta {
+    pub name: String,
+    pub baseline_ms: f64,
+    pub candidate_ms: f64,
+    /// Positive when the candidate is slower
+    pub delta_pct: f64,
+    pub threshold_pct: f64,
+    pub regression: bool,
+}
+
+/// Result of comparing a candidate report against a baseline
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BenchmarkComparison {
+    /// False when the reports were taken with different dimensions or
+    /// iteration counts, in which case deltas are only indicative
+    pub comparable: bool,
+    pub deltas: Vec<OperationDelta>,
+    /// Operations present in only one of the two reports
+    pub missing: Vec<String>,
+    pub regressions: usize,
+    pub passed: bool,
+}
+
+fn time_operation(
+    name: &str,
+    iterations: usize,
+    options: &BenchmarkOptions,
+    mut op: impl FnMut(),
+) -> OperationTiming {
+    let before = options.memory.then(memory_sample);
+
+    let batches = options.batches.clamp(1, iterations.max(1));
+    let mut samples = Vec::with_capacity(batches);
+    for batch in 0..batches {
+        // Spread the remainder over the first batches
+        let size = iterations / batches + usize::from(batch < iterations % batches);
+        let start = now_ms();
+        for _ in 0..size {
+            op();
+        }
+        samples.push((now_ms() - start, size));
+    }
+
+    let memory = before.map(|before| {
+        let after = memory_sample();
+        MemoryDelta {
+            wasm_bytes: after.0 - before.0,
+            js_heap_bytes: before.1.zip(after.1).map(|(before, after)| after - before),
+        }
+    });
+
+    let total_ms = samples.iter().map(|&(ms, _)| ms).sum();
+    let (kept_ms, kept_iterations, outliers) = if batches > 1 {
+        trim_outliers(&samples)
+    } else {
+        (total_ms, iterations, 0)
+    };
+
+    OperationTiming {
+        name: name.to_string(),
+        total_ms,
+        mean_ms: kept_ms / kept_iterations.max(1) as f64,
+        outlier_batches: (batches > 1).then_some(outliers),
+        memory,
+    }
+}
+
+/// Time and iterations of the `(ms, iterations)` batches below the upper
+/// Tukey fence (Q3 + 1.5 IQR) of per-iteration time, plus how many were
+/// dropped. Pauses only ever slow a batch down, so fast batches are kept.
+pub(crate) fn trim_outliers(samples: &[(f64, usize)]) -> (f64, usize, usize) {
+    let per_iteration = |&(ms, size): &(f64, usize)| ms / size.max(1) as f64;
+    let mut sorted: Vec<f64> = samples.iter().map(per_iteration).collect();
+    sorted.sort_by(f64::total_cmp);
+
+    let quantile = |q: f64| sorted[((sorted.len() - 1) as f64 * q).round() as usize];
+    let (q1, q3) = (quantile(0.25), quantile(0.75));
+    let fence = q3 + 1.5 * (q3 - q1);
+
+    let mut kept = (0.0, 0, 0);
+    for sample in samples {
+        if per_iteration(sample) <= fence {
+            kept.0 += sample.0;
+            kept.1 += sample.1;
+        } else {
+            kept.2 += 1;
+        }
+    }
+    kept
+}
+
+/// Current wasm linear-memory size and, where exposed, JS heap usage
+fn memory_sample() -> (f64, Option<f64>) {
+    let wasm = events::memory_bytes() as f64;
+
+    // Non-standard and Chromium-only
+    let js_heap = js_sys::Reflect::get(&js_sys::global(), &"performance".into())
+        .ok()
+        .filter(|performance| performance.is_object())
+        .and_then(|performance| js_sys::Reflect::get(&performance, &"memory".into()).ok())
+        .filter(|memory| memory.is_object())
+        .and_then(|memory| js_sys::Reflect::get(&memory, &"usedJSHeapSize".into()).ok())
+        .and_then(|used| used.as_f64());
+
+    (wasm, js_heap)
+}
+
+/// Start `performance.measureUserAgentSpecificMemory()`, if the global
+/// scope has it (cross-origin isolated pages and workers)
+fn measure_user_agent_memory() -> Option<js_sys::Promise> {
+    let performance = js_sys::Reflect::get(&js_sys::global(), &"performance".into()).ok()?;
+    if !performance.is_object() {
+        return None;
+    }
+    let measure = js_sys::Reflect::get(&performance, &"measureUserAgentSpecificMemory".into())
+        .ok()?
+        .dyn_into::<js_sys::Function>()
+        .ok()?;
+    measure.call0(&performance).ok()?.dyn_into().ok()
+}
+
+/// `bytes` of a `measureUserAgentSpecificMemory()` result
+fn user_agent_bytes(result: &JsValue) -> Option<f64> {
+    js_sys::Reflect::get(result, &"bytes".into()).ok()?.as_f64()
+}
+
+/// `promise.then(on_fulfilled, on_rejected)` with one-shot callbacks, each
+/// freed once it runs
+fn then(
+    promise: &js_sys::Promise,
+    on_fulfilled: impl FnOnce(JsValue) -> Result<JsValue, JsValue> + 'static,
+    on_rejected: impl FnOnce(JsValue) -> Result<JsValue, JsValue> + 'static,
+) -> Result<JsValue, JsValue> {
+    let then: js_sys::Function = js_sys::Reflect::get(promise, &"then".into())?.dyn_into()?;
+    then.call2(
+        promise,
+        &Closure::once_into_js(on_fulfilled),
+        &Closure::once_into_js(on_rejected),
+    )
+}
+
+fn run_report(dimensions: usize, iterations: usize, options: &BenchmarkOptions) -> BenchmarkReport {
+    let search = VectorSearch::new(dimensions);
+
+    let vec1: Vec<f64> = (0..dimensions).map(|i| (i as f64).sin()).collect();
+    let vec2: Vec<f64> = (0..dimensions).map(|i| (i as f64).cos()).collect();
+    let vec1_f32: Vec<f32> = vec1.iter().map(|&x| x as f32).collect();
+    let vec2_f32: Vec<f32> = vec2.iter().map(|&x| x as f32).collect();
+
+    // Inputs and results pass through `black_box` so the optimizer can
+    // neither hoist the loop-invariant work out nor discard unused results
+    let operations = vec![
+        time_operation("cosine", iterations, options, || {
+            black_box(search.cosine_similarity(black_box(&vec1), black_box(&vec2)));
+        }),
+        time_operation("cosineSimd", iterations, options, || {
+            black_box(search.cosine_similarity_simd(black_box(&vec1_f32), black_box(&vec2_f32)));
+        }),
+        time_operation("euclidean", iterations, options, || {
+            black_box(search.euclidean_distance(black_box(&vec1), black_box(&vec2)));
+        }),
+        time_operation("dot", iterations, options, || {
+            black_box(search.dot_product(black_box(&vec1), black_box(&vec2)));
+        }),
+    ];
+
+    BenchmarkReport {
+        dimensions,
+        iterations,
+        operations,
+        user_agent_bytes: None,
+    }
+}
+
+fn report_json(report: &BenchmarkReport) -> Result<JsValue, JsValue> {
+    Ok(js_sys::JSON::stringify(&to_js(report)?)?.into())
+}
+
+fn parse_report(json: &str) -> Result<BenchmarkReport, JsValue> {
+    let value = js_sys::JSON::parse(json)?;
+    serde_wasm_bindgen::from_value(value).map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+impl VectorBenchmark {
+    /// Benchmark vector operations and return the timings as a JSON report
+    /// suitable for `compareBenchmarks`. `options` is a `BenchmarkOptions`.
+    #[wasm_bindgen(js_name = "benchmarkReport")]
+    pub fn benchmark_report(
+        dimensions: usize,
+        iterations: usize,
+        options: JsValue,
+    ) -> Result<String, JsValue> {
+        let options: BenchmarkOptions = options_from_js(options)?;
+        let json = report_json(&run_report(dimensions, iterations, &options))?;
+        Ok(json.as_string().unwrap_or_default())
+    }
+
+    /// `benchmarkReport`, resolving to the JSON report once
+    /// `performance.measureUserAgentSpecificMemory()` has been taken before
+    /// and after the run. Where the API is missing or refuses (it needs
+    /// cross-origin isolation) the report simply has no `userAgentBytes`.
+    /// The browser may delay each measurement until its next GC, so this can
+    /// take several seconds.
+    #[wasm_bindgen(js_name = "measuredBenchmarkReport")]
+    pub fn measured_benchmark_report(
+        dimensions: usize,
+        iterations: usize,
+        options: JsValue,
+    ) -> Result<js_sys::Promise, JsValue> {
+        let options: BenchmarkOptions = options_from_js(options)?;
+        let Some(before) = measure_user_agent_memory() else {
+            let json = report_json(&run_report(dimensions, iterations, &options))?;
+            return Ok(js_sys::Promise::resolve(&json));
+        };
+
+        let unmeasured = options.clone();
+        let chained = then(
+            &before,
+            move |before| {
+                let mut report = run_report(dimensions, iterations, &options);
+                let Some(after) = measure_user_agent_memory() else {
+                    return report_json(&report);
+                };
+
+                let unmeasured = report.clone();
+                then(
+                    &after,
+                    move |after| {
+                        report.user_agent_bytes = user_agent_bytes(&before)
+                            .zip(user_agent_bytes(&after))
+                            .map(|(before, after)| after - before);
+                        report_json(&report)
+                    },
+                    move |_| report_json(&unmeasured),
+                )
+            },
+            move |_| report_json(&run_report(dimensions, iterations, &unmeasured)),
+        )?;
+        Ok(js_sys::Promise::resolve(&chained))
+    }
+
+    /// Compare two JSON reports from `benchmarkReport`, flagging operations
+    /// whose mean time grew by more than the configured threshold
+    #[wasm_bindgen(js_name = "compareBenchmarks")]
+    pub fn compare_benchmarks(
+        report_a_json: &str,
+        report_b_json: &str,
+        thresholds: JsValue,
+    ) -> Result<JsValue, JsValue> {
+        let baseline = parse_report(report_a_json)?;
+        let candidate = parse_report(report_b_json)?;
+        let thresholds: RegressionThresholds = options_from_js(thresholds)?;
+
+        to_js(&compare_reports(&baseline, &candidate, &thresholds))
+    }
+}
+
+pub(crate) fn compare_reports(
+    baseline: &BenchmarkReport,
+    candidate: &BenchmarkReport,
+    thresholds: &RegressionThresholds,
+) -> BenchmarkComparison {
+    let candidate_ops: HashMap<&str, &OperationTiming> = candidate
+        .operations
+        .iter()
+        .map(|op| (op.name.as_str(), op))
+        .collect();
+
+    let mut deltas = Vec::new();
+    let mut missing = Vec::new();
+
+    for base in &baseline.operations {
+        let Some(cand) = candidate_ops.get(base.name.as_str()) else {
+            missing.push(base.name.clone());
+            continue;
+        };
+
+        let delta_pct = if base.mean_ms > 0.0 {
+            (cand.mean_ms - base.mean_ms) / base.mean_ms * 100.0
+        } else if cand.mean_ms > 0.0 {
+            f64::INFINITY
+        } else {
+            0.0
+        };
+
+        let threshold_pct = thresholds
+            .operations
+            .get(&base.name)
+            .copied()
+            .unwrap_or(thresholds.max_regression_pct);
+
+        deltas.push(OperationDelta {
+            name: base.name.clone(),
+            baseline_ms: base.mean_ms,
+            candidate_ms: cand.mean_ms,
+            delta_pct,
+            threshold_pct,
+            regression: delta_pct > threshold_pct,
+        });
+    }
+
+    for cand in &candidate.operations {
+        if !baseline.operations.iter().any(|op| op.name == cand.name) {
+            missing.push(cand.name.clone());
+        }
+    }
+
+    let regressions = deltas.iter().filter(|d| d.regression).count();
+
+    BenchmarkComparison {
+        comparable: baseline.dimensions == candidate.dimensions
+            && baseline.iterations == candidate.iterations,
+        deltas,
+        missing,
+        regressions,
+        passed: regressions == 0,
+    }
+}
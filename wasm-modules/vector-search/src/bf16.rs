@@ -0,0 +1,79 @@
+//! bfloat16 conversion and ingest.
+//!
+//! Some model runtimes emit embeddings as bf16: the top 16 bits of an f32,
+//! carried in JS as a `Uint16Array`. Widening is exact; narrowing rounds to
+//! nearest, ties to even, and keeps NaNs NaN.
+
+use wasm_bindgen::prelude::*;
+
+use crate::index::VectorIndex;
+use crate::mixed::MixedIndex;
+use crate::shared::SharedCorpus;
+
+pub(crate) fn bf16_to_f32(bits: u16) -> f32 {
+    f32::from_bits((bits as u32) << 16)
+}
+
+pub(crate) fn f32_to_bf16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    if value.is_nan() {
+        // Truncating could clear every mantissa bit left and yield infinity
+        return ((bits >> 16) as u16) | 0x0040;
+    }
+
+    let round = 0x7fff + ((bits >> 16) & 1);
+    (bits.wrapping_add(round) >> 16) as u16
+}
+
+pub(crate) fn widen(values: &[u16]) -> Vec<f32> {
+    values.iter().map(|&bits| bf16_to_f32(bits)).collect()
+}
+
+/// Widen bf16 values (a `Uint16Array`) to a `Float32Array`
+#[wasm_bindgen(js_name = "bf16ToF32")]
+pub fn bf16_to_f32_array(values: &[u16]) -> Vec<f32> {
+    widen(values)
+}
+
+/// Widen bf16 values to a `Float64Array`, the precision `VectorSearch`
+/// methods take
+#[wasm_bindgen(js_name = "bf16ToF64")]
+pub fn bf16_to_f64_array(values: &[u16]) -> Vec<f64> {
+    values
+        .iter()
+        .map(|&bits| bf16_to_f32(bits) as f64)
+        .collect()
+}
+
+/// Round f32 values to bf16, returned as a `Uint16Array`
+#[wasm_bindgen(js_name = "f32ToBf16")]
+pub fn f32_to_bf16_array(values: &[f32]) -> Vec<u16> {
+    values.iter().map(|&value| f32_to_bf16(value)).collect()
+}
+
+#[wasm_bindgen]
+impl VectorIndex {
+    /// `insert` for a bf16 vector given as a `Uint16Array`
+    #[wasm_bindgen(js_name = "insertBf16")]
+    pub fn insert_bf16(&mut self, id: u32, vector: &[u16]) -> Result<(), JsValue> {
+        self.insert(id, &widen(vector))
+    }
+}
+
+#[wasm_bindgen]
+impl MixedIndex {
+    /// `insert` for a bf16 vector given as a `Uint16Array`
+    #[wasm_bindgen(js_name = "insertBf16")]
+    pub fn insert_bf16(&mut self, id: u32, vector: &[u16]) -> Result<(), JsValue> {
+        self.insert(id, &widen(vector))
+    }
+}
+
+#[wasm_bindgen]
+impl SharedCorpus {
+    /// `pushBatch` for `count` bf16 vectors given as one `Uint16Array`
+    #[wasm_bindgen(js_name = "pushBatchBf16")]
+    pub fn push_batch_bf16(&mut self, vectors: &[u16], count: usize) -> Result<usize, JsValue> {
+        self.push_batch(&widen(vectors), count)
+    }
+}
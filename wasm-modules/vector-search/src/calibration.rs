@@ -0,0 +1,142 @@
+//! Score post-processing for display: turn raw metric scores into
+//! comparable values in `[0, 1]`, returned next to the raw scores.
+//!
+//! Each hit's raw score is first mapped to a similarity (distances are
+//! transformed so larger is better), then calibrated as
+//! `(similarity + bias) / temperature`, and finally normalized.
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::VectorError;
+use crate::metric::MetricKind;
+use crate::search::QueryOptions;
+
+/// How calibrated scores are mapped into `[0, 1]`
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum NormalizationMethod {
+    /// Best hit in the result set maps to 1, worst to 0
+    #[default]
+    MinMax,
+    /// Shares of a softmax over the result set, summing to 1
+    Softmax,
+    /// Logistic function of each hit on its own, independent of the others
+    Sigmoid,
+}
+
+/// How distances are turned into similarities before calibration
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum DistanceTransform {
+    /// `-d`
+    #[default]
+    Negate,
+    /// `1 / (1 + d)`
+    Reciprocal,
+    /// `exp(-d)`
+    Exponential,
+}
+
+/// Options for `normalize` in query options
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ScoreNormalization {
+    pub method: NormalizationMethod,
+    /// Applied to distance metrics only
+    pub distance_transform: DistanceTransform,
+    /// Divides calibrated scores; below 1 sharpens softmax and sigmoid
+    pub temperature: f64,
+    /// Added to similarities before dividing by `temperature`
+    pub bias: f64,
+}
+
+impl Default for ScoreNormalization {
+    fn default() -> Self {
+        Self {
+            method: NormalizationMethod::default(),
+            distance_transform: DistanceTransform::default(),
+            temperature: 1.0,
+            bias: 0.0,
+        }
+    }
+}
+
+impl ScoreNormalization {
+    fn similarity(&self, score: f64, metric: MetricKind) -> f64 {
+        if metric.higher_is_better() {
+            return score;
+        }
+
+        match self.distance_transform {
+            DistanceTransform::Negate => -score,
+            DistanceTransform::Reciprocal => 1.0 / (1.0 + score.max(0.0)),
+            DistanceTransform::Exponential => (-score).exp(),
+        }
+    }
+
+    /// Fail unless `temperature` is a positive number
+    pub(crate) fn check(&self) -> Result<(), VectorError> {
+        if self.temperature > 0.0 && self.temperature.is_finite() {
+            return Ok(());
+        }
+        Err(VectorError::InvalidOptions {
+            message: format!(
+                "normalize.temperature must be positive and finite, got {}",
+                self.temperature
+            ),
+        })
+    }
+
+    /// Normalized value for each score of a best-first result set
+    pub(crate) fn apply(&self, scores: &[f64], metric: MetricKind) -> Vec<f64> {
+        let calibrated: Vec<f64> = scores
+            .iter()
+            .map(|&score| (self.similarity(score, metric) + self.bias) / self.temperature)
+            .collect();
+
+        let max = calibrated.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let min = calibrated.iter().copied().fold(f64::INFINITY, f64::min);
+
+        match self.method {
+            NormalizationMethod::MinMax => calibrated
+                .iter()
+                .map(|&z| {
+                    if max > min {
+                        (z - min) / (max - min)
+                    } else {
+                        1.0
+                    }
+                })
+                .collect(),
+            NormalizationMethod::Softmax => {
+                // Shift by the max so exp() can't overflow
+                let exps: Vec<f64> = calibrated.iter().map(|&z| (z - max).exp()).collect();
+                let total: f64 = exps.iter().sum();
+                exps.iter().map(|&e| e / total).collect()
+            }
+            NormalizationMethod::Sigmoid => calibrated
+                .iter()
+                .map(|&z| 1.0 / (1.0 + (-z).exp()))
+                .collect(),
+        }
+    }
+}
+
+/// Normalized scores for a best-first `(slot, score)` result set, or `None`
+/// for every hit when the query didn't ask for normalization
+pub(crate) fn normalized_scores(
+    ranked: &[(usize, f64)],
+    options: &QueryOptions,
+) -> Vec<Option<f64>> {
+    match &options.normalize {
+        Some(normalization) => {
+            let scores: Vec<f64> = ranked.iter().map(|&(_, score)| score).collect();
+            normalization
+                .apply(&scores, options.metric)
+                .into_iter()
+                .map(|value| Some(options.reported_normalized(value)))
+                .collect()
+        }
+        None => vec![None; ranked.len()],
+    }
+}
@@ -0,0 +1,566 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::metric::{self, MetricKind, Scorer};
+use crate::rng::SplitMix64;
+use crate::rows::Rows;
+use crate::search::{compare_scores, TopK};
+
+const ROOT: usize = 0;
+
+/// Build and query parameters for a cluster tree
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ClusterTreeParams {
+    /// Number of k-means clusters per internal node
+    pub branching: usize,
+    /// Nodes with at most this many vectors become leaves
+    pub leaf_size: usize,
+    /// Maximum k-means iterations per split
+    pub iterations: usize,
+    /// Number of nodes kept per level during search
+    pub beam_width: usize,
+    pub seed: u64,
+}
+
+impl Default for ClusterTreeParams {
+    fn default() -> Self {
+        Self {
+            branching: 8,
+            leaf_size: 64,
+            iterations: 10,
+            beam_width: 4,
+            seed: 42,
+        }
+    }
+}
+
+/// Shape of a built tree, returned to JS after a build
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClusterTreeStats {
+    pub nodes: usize,
+    pub leaves: usize,
+    pub depth: usize,
+    pub max_leaf_size: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct ClusterNode {
+    centroid: Vec<f32>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    /// Record IDs, only populated on leaves
+    members: Vec<u32>,
+    /// Number of vectors below this node
+    count: usize,
+    /// Largest euclidean distance from the centroid to any vector below
+    radius: f64,
+    /// Smallest and largest L2 norm of any vector below
+    min_norm: f64,
+    max_norm: f64,
+}
+
+impl ClusterNode {
+    fn is_leaf(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    fn absorb(&mut self, vector: &[f32]) {
+        let norm = metric::dot(vector, vector).sqrt();
+        self.count += 1;
+        self.radius = self.radius.max(metric::euclidean(vector, &self.centroid));
+        self.min_norm = self.min_norm.min(norm);
+        self.max_norm = self.max_norm.max(norm);
+    }
+}
+
+/// Frontier node keyed so the best optimistic score is taken first
+#[derive(Clone, Copy, Debug)]
+struct FrontierEntry {
+    /// Larger means worse, regardless of metric direction
+    badness: f64,
+    bound: f64,
+    node: usize,
+}
+
+impl PartialEq for FrontierEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for FrontierEntry {}
+
+impl PartialOrd for FrontierEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for FrontierEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.badness.total_cmp(&other.badness)
+    }
+}
+
+/// Unvisited subtrees of a best-first walk over a `ClusterTree`, taken in
+/// order of the best score any vector below them could reach. Every vector
+/// not yet handed out by `next_leaf` scores no better than `bound`.
+pub(crate) struct Frontier<'a> {
+    tree: &'a ClusterTree,
+    query: &'a [f32],
+    query_norm: f64,
+    metric: MetricKind,
+    heap: BinaryHeap<Reverse<FrontierEntry>>,
+    /// Centroids bounded so far
+    pub(crate) centroids_scored: usize,
+}
+
+impl<'a> Frontier<'a> {
+    fn push(&mut self, node: usize) {
+        let bound = self
+            .tree
+            .optimistic_score(node, self.query, self.query_norm, self.metric);
+        // An unbounded (NaN) node is visited first rather than never
+        let badness = if bound.is_nan() {
+            f64::NEG_INFINITY
+        } else if self.metric.higher_is_better() {
+            -bound
+        } else {
+            bound
+        };
+        self.centroids_scored += 1;
+        self.heap.push(Reverse(FrontierEntry {
+            badness,
+            bound,
+            node,
+        }));
+    }
+
+    /// Best score any unvisited vector could reach, or `None` once the
+    /// whole tree has been handed out
+    pub(crate) fn bound(&self) -> Option<f64> {
+        self.heap.peek().map(|Reverse(entry)| entry.bound)
+    }
+
+    /// Members of the most promising unvisited leaf, expanding internal
+    /// nodes on the way
+    pub(crate) fn next_leaf(&mut self) -> Option<&'a [u32]> {
+        while let Some(Reverse(entry)) = self.heap.pop() {
+            let tree = self.tree;
+            let node = &tree.nodes[entry.node];
+            if node.is_leaf() {
+                return Some(&node.members);
+            }
+            for &child in &node.children {
+                if tree.nodes[child].count > 0 {
+                    self.push(child);
+                }
+            }
+        }
+        None
+    }
+
+    /// Hand out everything left, so `bound` reports `None`
+    pub(crate) fn clear(&mut self) {
+        self.heap.clear();
+    }
+}
+
+/// Recursive k-means tree searched coarse-to-fine with a beam.
+///
+/// Each node keeps its centroid plus radius and norm range statistics, which
+/// give an optimistic score for everything below it; subtrees that cannot
+/// beat the current k-th result are pruned. The tree is plain data, so it
+/// serializes as-is.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct ClusterTree {
+    pub(crate) params: ClusterTreeParams,
+    dimensions: usize,
+    nodes: Vec<ClusterNode>,
+    leaf_of: HashMap<u32, usize>,
+}
+
+impl ClusterTree {
+    /// Build a tree over `rows`, where slot `i` holds the vector for `ids[i]`
+    pub(crate) fn build(params: ClusterTreeParams, rows: &impl Rows, ids: &[u32]) -> Self {
+        let mut tree = Self {
+            params,
+            dimensions: rows.dimensions(),
+            nodes: Vec::new(),
+            leaf_of: HashMap::new(),
+        };
+
+        let mut rng = SplitMix64::new(tree.params.seed);
+        let members: Vec<usize> = (0..ids.len()).collect();
+        tree.build_node(rows, ids, members, None, &mut rng);
+
+        tree
+    }
+
+    fn build_node(
+        &mut self,
+        rows: &impl Rows,
+        ids: &[u32],
+        members: Vec<usize>,
+        parent: Option<usize>,
+        rng: &mut SplitMix64,
+    ) -> usize {
+        let mut centroid = vec![0.0f64; self.dimensions];
+        for &slot in &members {
+            for (c, &x) in centroid.iter_mut().zip(rows.row(slot).iter()) {
+                *c += x as f64;
+            }
+        }
+        let scale = 1.0 / members.len().max(1) as f64;
+
+        let mut node = ClusterNode {
+            centroid: centroid.iter().map(|c| (c * scale) as f32).collect(),
+            parent,
+            children: Vec::new(),
+            members: Vec::new(),
+            count: 0,
+            radius: 0.0,
+            min_norm: f64::INFINITY,
+            max_norm: 0.0,
+        };
+        for &slot in &members {
+            node.absorb(&rows.row(slot));
+        }
+
+        let node_idx = self.nodes.len();
+        self.nodes.push(node);
+
+        if members.len() > self.params.leaf_size.max(1) && self.params.branching > 1 {
+            let k = self.params.branching.min(members.len());
+            let assignment = kmeans(rows, &members, k, self.params.iterations, rng);
+
+            let mut partitions = vec![Vec::new(); k];
+            for (&slot, &cluster) in members.iter().zip(&assignment) {
+                partitions[cluster].push(slot);
+            }
+            partitions.retain(|p| !p.is_empty());
+
+            // Identical vectors cannot be split further; keep them in a leaf
+            if partitions.len() > 1 {
+                for partition in partitions {
+                    let child = self.build_node(rows, ids, partition, Some(node_idx), rng);
+                    self.nodes[node_idx].children.push(child);
+                }
+                return node_idx;
+            }
+        }
+
+        for &slot in &members {
+            self.leaf_of.insert(ids[slot], node_idx);
+        }
+        self.nodes[node_idx].members = members.iter().map(|&slot| ids[slot]).collect();
+        node_idx
+    }
+
+    /// Route a vector to its nearest leaf, widening node statistics on the way
+    pub(crate) fn insert(&mut self, id: u32, vector: &[f32]) {
+        self.remove(id);
+
+        let mut node = ROOT;
+        loop {
+            self.nodes[node].absorb(vector);
+
+            if self.nodes[node].is_leaf() {
+                self.nodes[node].members.push(id);
+                self.leaf_of.insert(id, node);
+                return;
+            }
+
+            node = self.nodes[node]
+                .children
+                .iter()
+                .copied()
+                .min_by(|&a, &b| {
+                    let da = metric::euclidean(vector, &self.nodes[a].centroid);
+                    let db = metric::euclidean(vector, &self.nodes[b].centroid);
+                    da.total_cmp(&db)
+                })
+                .unwrap();
+        }
+    }
+
+    /// Detach an ID from its leaf. Radius and norm bounds are left as they
+    /// are; they stay valid (if looser) after removals.
+    pub(crate) fn remove(&mut self, id: u32) {
+        let Some(leaf) = self.leaf_of.remove(&id) else {
+            return;
+        };
+
+        self.nodes[leaf].members.retain(|&member| member != id);
+
+        let mut node = Some(leaf);
+        while let Some(n) = node {
+            self.nodes[n].count -= 1;
+            node = self.nodes[n].parent;
+        }
+    }
+
+    pub(crate) fn leaf_count(&self) -> usize {
+        self.nodes.iter().filter(|n| n.is_leaf()).count()
+    }
+
+    pub(crate) fn stats(&self) -> ClusterTreeStats {
+        let mut depth = 0;
+        for node in self.nodes.iter().filter(|n| n.is_leaf()) {
+            let mut level = 1;
+            let mut parent = node.parent;
+            while let Some(p) = parent {
+                level += 1;
+                parent = self.nodes[p].parent;
+            }
+            depth = usize::max(depth, level);
+        }
+
+        ClusterTreeStats {
+            nodes: self.nodes.len(),
+            leaves: self.leaf_count(),
+            depth,
+            max_leaf_size: self
+                .nodes
+                .iter()
+                .map(|n| n.members.len())
+                .max()
+                .unwrap_or(0),
+        }
+    }
+
+    /// Best score any vector below `node` could reach.
+    ///
+    /// Uses the triangle inequality for distances and Cauchy–Schwarz for
+    /// `q·x = q·c + q·(x - c) <= q·c + |q| r`, divided by the node's norm
+    /// range for cosine.
+    fn optimistic_score(
+        &self,
+        node: usize,
+        query: &[f32],
+        query_norm: f64,
+        metric: MetricKind,
+    ) -> f64 {
+        let n = &self.nodes[node];
+        match metric {
+            MetricKind::Euclidean => (metric::euclidean(query, &n.centroid) - n.radius).max(0.0),
+            MetricKind::Dot => metric::dot(query, &n.centroid) + query_norm * n.radius,
+            // Arbitrary callbacks admit no bound, so never prune on them
+            MetricKind::Custom => f64::INFINITY,
+            MetricKind::Cosine => {
+                if query_norm == 0.0 {
+                    return 0.0;
+                }
+                let numerator = metric::dot(query, &n.centroid) + query_norm * n.radius;
+                let norm = if numerator >= 0.0 {
+                    n.min_norm
+                } else {
+                    n.max_norm
+                };
+                if norm == 0.0 {
+                    return 1.0;
+                }
+                (numerator / (query_norm * norm)).min(1.0)
+            }
+        }
+    }
+
+    /// Best-first walk from the root for `bound_metric`, the metric scores
+    /// effectively follow (dot for cosine over unit vectors)
+    pub(crate) fn frontier<'a>(
+        &'a self,
+        query: &'a [f32],
+        bound_metric: MetricKind,
+    ) -> Frontier<'a> {
+        let mut frontier = Frontier {
+            tree: self,
+            query,
+            query_norm: metric::dot(query, query).sqrt(),
+            metric: bound_metric,
+            heap: BinaryHeap::new(),
+            centroids_scored: 0,
+        };
+        if self.nodes[ROOT].count > 0 {
+            frontier.push(ROOT);
+        }
+        frontier
+    }
+
+    /// Beam search from the root, pushing every accepted leaf member into
+    /// `top`.
+    ///
+    /// `visit` maps a record ID to its storage slot and score, or `None` to
+    /// skip it. `bound_metric` is the metric scores effectively follow (dot
+    /// for cosine over unit vectors), or `None` to disable pruning when no
+    /// valid bound exists (e.g. weighted queries). Returns how many
+    /// centroids were scored.
+    pub(crate) fn search(
+        &self,
+        scorer: &Scorer<f32>,
+        bound_metric: Option<MetricKind>,
+        beam_width: usize,
+        top: &mut TopK,
+        mut visit: impl FnMut(u32) -> Option<(usize, f64)>,
+    ) -> usize {
+        let query = scorer.query();
+        let query_norm = metric::dot(query, query).sqrt();
+        let mut frontier = vec![ROOT];
+        let mut centroids_scored = 0;
+
+        while !frontier.is_empty() {
+            let mut next = Vec::new();
+
+            for &node in &frontier {
+                let n = &self.nodes[node];
+                if n.is_leaf() {
+                    for &id in &n.members {
+                        if let Some((slot, score)) = visit(id) {
+                            top.push(slot, score);
+                        }
+                    }
+                } else {
+                    next.extend(
+                        n.children
+                            .iter()
+                            .copied()
+                            .filter(|&c| self.nodes[c].count > 0),
+                    );
+                }
+            }
+
+            if let (Some(bound_metric), Some(threshold)) = (bound_metric, top.threshold()) {
+                next.retain(|&child| {
+                    let bound = self.optimistic_score(child, query, query_norm, bound_metric);
+                    if bound_metric.higher_is_better() {
+                        bound > threshold
+                    } else {
+                        bound < threshold
+                    }
+                });
+            }
+
+            let mut ranked: Vec<(usize, f64)> = next
+                .into_iter()
+                .map(|child| (child, scorer.score(&self.nodes[child].centroid)))
+                .collect();
+            centroids_scored += ranked.len();
+            ranked.sort_by(|a, b| compare_scores(scorer.metric(), a, b));
+            ranked.truncate(beam_width.max(1));
+
+            frontier = ranked.into_iter().map(|(node, _)| node).collect();
+        }
+
+        centroids_scored
+    }
+}
+
+/// Lloyd's k-means with k-means++ seeding, returning a cluster per member
+pub(crate) fn kmeans(
+    rows: &impl Rows,
+    members: &[usize],
+    k: usize,
+    iterations: usize,
+    rng: &mut SplitMix64,
+) -> Vec<usize> {
+    let dims = rows.dimensions();
+    let row = |slot: usize| rows.row(slot);
+    let sq_dist = |a: &[f32], b: &[f32]| -> f64 {
+        a.iter()
+            .zip(b)
+            .map(|(&x, &y)| {
+                let d = x as f64 - y as f64;
+                d * d
+            })
+            .sum()
+    };
+
+    // k-means++: each new centroid is drawn proportionally to its
+    // squared distance from the nearest centroid chosen so far
+    let mut centroids: Vec<f32> = Vec::with_capacity(k * dims);
+    let first = members[rng.next_below(members.len())];
+    centroids.extend_from_slice(&row(first));
+
+    let mut nearest: Vec<f64> = members
+        .iter()
+        .map(|&slot| sq_dist(&row(slot), &centroids[..dims]))
+        .collect();
+
+    while centroids.len() < k * dims {
+        let total: f64 = nearest.iter().sum();
+        let pick = if total > 0.0 {
+            let mut target = rng.next_f64() * total;
+            let mut chosen = members.len() - 1;
+            for (i, &d) in nearest.iter().enumerate() {
+                if target < d {
+                    chosen = i;
+                    break;
+                }
+                target -= d;
+            }
+            chosen
+        } else {
+            rng.next_below(members.len())
+        };
+
+        let start = centroids.len();
+        centroids.extend_from_slice(&row(members[pick]));
+        for (d, &slot) in nearest.iter_mut().zip(members) {
+            *d = d.min(sq_dist(&row(slot), &centroids[start..]));
+        }
+    }
+
+    let mut assignment = vec![0usize; members.len()];
+    for iteration in 0..iterations.max(1) {
+        let mut changed = iteration == 0;
+        for (a, &slot) in assignment.iter_mut().zip(members) {
+            let candidate = row(slot);
+            let best = centroids
+                .chunks_exact(dims)
+                .map(|c| sq_dist(&candidate, c))
+                .enumerate()
+                .min_by(|x, y| x.1.total_cmp(&y.1))
+                .map(|(i, _)| i)
+                .unwrap_or(0);
+            if *a != best {
+                *a = best;
+                changed = true;
+            }
+        }
+
+        if !changed {
+            break;
+        }
+
+        let mut sums = vec![0.0f64; k * dims];
+        let mut counts = vec![0usize; k];
+        for (&cluster, &slot) in assignment.iter().zip(members) {
+            counts[cluster] += 1;
+            for (s, &x) in sums[cluster * dims..(cluster + 1) * dims]
+                .iter_mut()
+                .zip(row(slot).iter())
+            {
+                *s += x as f64;
+            }
+        }
+
+        // Empty clusters keep their previous centroid
+        for cluster in 0..k {
+            if counts[cluster] == 0 {
+                continue;
+            }
+            let scale = 1.0 / counts[cluster] as f64;
+            for (c, s) in centroids[cluster * dims..(cluster + 1) * dims]
+                .iter_mut()
+                .zip(&sums[cluster * dims..(cluster + 1) * dims])
+            {
+                *c = (s * scale) as f32;
+            }
+        }
+    }
+
+    assignment
+}
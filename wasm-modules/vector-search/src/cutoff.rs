@@ -0,0 +1,79 @@
+//! Score-gap cutoffs: trim a ranked list at the point where relevance drops
+//! off sharply, so callers don't have to guess a fixed `k`.
+
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::VectorSearch;
+
+/// Bounds for automatic result counts, set as `autoK` in query options.
+///
+/// The query's `k` still decides how many candidates are ranked; the list is
+/// then cut at the largest score gap that leaves between `min_k` and `max_k`
+/// results.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AutoK {
+    /// Never return fewer results than this (if that many exist)
+    pub min_k: usize,
+    /// Never return more results than this (defaults to `k`)
+    pub max_k: Option<usize>,
+}
+
+impl Default for AutoK {
+    fn default() -> Self {
+        Self {
+            min_k: 1,
+            max_k: None,
+        }
+    }
+}
+
+/// Length to keep from a best-first `scores` list: the cut falls on the
+/// largest gap between neighbours such that between `min_k` and `max_k`
+/// entries survive. Ties keep the shorter list.
+pub(crate) fn gap_cutoff(scores: &[f64], min_k: usize, max_k: usize) -> usize {
+    let max_k = max_k.min(scores.len());
+    let min_k = min_k.max(1);
+    if max_k <= min_k {
+        return max_k;
+    }
+
+    // Cutting after position `len` separates scores[len - 1] from scores[len]
+    let mut best_len = max_k;
+    let mut best_gap = f64::NEG_INFINITY;
+    for len in min_k..max_k {
+        let gap = (scores[len - 1] - scores[len]).abs();
+        if gap > best_gap {
+            best_gap = gap;
+            best_len = len;
+        }
+    }
+
+    // Keeping everything up to `max_k` is only better when the list runs on
+    // past it and the drop there is the biggest one
+    if max_k < scores.len() && (scores[max_k - 1] - scores[max_k]).abs() > best_gap {
+        best_len = max_k;
+    }
+
+    best_len
+}
+
+/// Truncate best-first `(slot, score)` pairs according to `auto_k`, with
+/// `k` as the default upper bound
+pub(crate) fn apply_auto_k(ranked: &mut Vec<(usize, f64)>, auto_k: &AutoK, k: usize) {
+    let scores: Vec<f64> = ranked.iter().map(|&(_, score)| score).collect();
+    let keep = gap_cutoff(&scores, auto_k.min_k, auto_k.max_k.unwrap_or(k).min(k));
+    ranked.truncate(keep);
+}
+
+#[wasm_bindgen]
+impl VectorSearch {
+    /// Number of leading results to keep from a best-first score list, cut
+    /// at the largest gap between neighbouring scores while keeping between
+    /// `min_k` and `max_k` results
+    #[wasm_bindgen(js_name = "scoreGapCutoff")]
+    pub fn score_gap_cutoff(scores: &[f64], min_k: usize, max_k: usize) -> usize {
+        gap_cutoff(scores, min_k, max_k)
+    }
+}
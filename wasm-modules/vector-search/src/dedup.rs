@@ -0,0 +1,98 @@
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::{to_js, VectorSearch};
+
+/// A set of near-duplicate vectors collapsed onto a single representative
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateGroup {
+    /// Index of the vector kept as the representative of the group
+    pub canonical: usize,
+    /// Indices of every vector in the group, canonical first
+    pub members: Vec<usize>,
+}
+
+#[wasm_bindgen]
+impl VectorSearch {
+    /// Group near-duplicate vectors by cosine similarity.
+    ///
+    /// Vectors are visited in input order; the first unassigned vector becomes
+    /// the canonical member of a new group and absorbs every later unassigned
+    /// vector whose similarity to it is at least `threshold`. Every input
+    /// index appears in exactly one group, so singletons are returned as
+    /// groups of one.
+    #[wasm_bindgen(js_name = "groupNearDuplicates")]
+    pub fn group_near_duplicates(
+        &self,
+        vectors: &[f64],
+        count: usize,
+        threshold: f64,
+    ) -> Result<JsValue, JsValue> {
+        to_js(&self.near_duplicate_groups(vectors, count, threshold))
+    }
+}
+
+impl VectorSearch {
+    pub(crate) fn near_duplicate_groups(
+        &self,
+        vectors: &[f64],
+        count: usize,
+        threshold: f64,
+    ) -> Vec<DuplicateGroup> {
+        if vectors.len() != count * self.dimensions {
+            panic!("Vectors array size mismatch");
+        }
+
+        // Norms are computed once up front instead of per pair
+        let norms: Vec<f64> = vectors
+            .chunks_exact(self.dimensions)
+            .map(|vec| vec.iter().map(|x| x * x).sum::<f64>().sqrt())
+            .collect();
+
+        let mut assigned = vec![false; count];
+        let mut groups = Vec::new();
+
+        for i in 0..count {
+            if assigned[i] {
+                continue;
+            }
+            assigned[i] = true;
+
+            let canonical = &vectors[i * self.dimensions..(i + 1) * self.dimensions];
+            let mut members = vec![i];
+
+            for j in (i + 1)..count {
+                if assigned[j] {
+                    continue;
+                }
+
+                let magnitude = norms[i] * norms[j];
+                if magnitude == 0.0 {
+                    continue;
+                }
+
+                let candidate = &vectors[j * self.dimensions..(j + 1) * self.dimensions];
+                let dot: f64 = canonical.iter().zip(candidate).map(|(a, b)| a * b).sum();
+
+                if dot / magnitude >= threshold {
+                    assigned[j] = true;
+                    members.push(j);
+                }
+            }
+
+            groups.push(DuplicateGroup {
+                canonical: i,
+                members,
+            });
+        }
+
+        log!(
+            "Grouped {} vectors into {} near-duplicate groups",
+            count,
+            groups.len()
+        );
+
+        groups
+    }
+}
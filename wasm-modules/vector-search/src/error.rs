@@ -0,0 +1,153 @@
+use std::fmt;
+
+use wasm_bindgen::prelude::*;
+
+use crate::validation::VectorProblem;
+
+/// Recoverable errors surfaced to JS as `Error` objects whose `name` is the
+/// variant name, so callers can branch on `err.name`
+#[derive(Debug, Clone, PartialEq)]
+pub enum VectorError {
+    /// The collection changed since the result set the cursor belongs to
+    StaleCursor { token: u64, current: u64 },
+    /// An options object was well-formed but asked for an unsupported
+    /// combination
+    InvalidOptions { message: String },
+    /// No savepoint with this name is active
+    UnknownSavepoint { name: String },
+    /// A write was attempted through a read-only view
+    ReadOnly,
+    /// Storage is full or the requested size cannot be allocated
+    CapacityExceeded { capacity: usize },
+    /// Every read attempt overlapped a write; retry later
+    ConcurrentWrite { attempts: u32 },
+    /// A shared buffer does not have the expected layout
+    InvalidBuffer { message: String },
+    /// A mixed collection has no vectors of this dimensionality
+    UnknownGroup { dimensions: usize },
+    /// A vector failed validation; `index` is the record ID or batch
+    /// position, and is absent for queries
+    InvalidVector {
+        index: Option<usize>,
+        dimension: Option<usize>,
+        problem: VectorProblem,
+    },
+    /// Serializing an export failed
+    ExportFailed { message: String },
+    /// Snapshot bytes are malformed or could not be fetched
+    InvalidSnapshot { message: String },
+    /// The operation would change results under reads opened with
+    /// `beginRead`
+    ReadInProgress { readers: usize },
+    /// Int8 dot products over this many dimensions could overflow their
+    /// accumulator, and the overflow mode is `checked`
+    AccumulatorOverflow { dimensions: usize, limit: usize },
+    /// No open result set has this handle; it was closed, fully paged out,
+    /// or never opened
+    UnknownResultSet { handle: u32 },
+    /// A covariance or precision matrix could not be Cholesky-factored;
+    /// `dimension` is the first pivot that was not positive
+    NotPositiveDefinite { dimension: usize },
+    /// The `customMetric` callback threw; `error` is what it threw, and is
+    /// rethrown as-is rather than wrapped
+    CallbackFailed { error: JsValue },
+}
+
+impl VectorError {
+    pub fn name(&self) -> &'static str {
+        match self {
+            VectorError::StaleCursor { .. } => "StaleCursor",
+            VectorError::InvalidOptions { .. } => "InvalidOptions",
+            VectorError::UnknownSavepoint { .. } => "UnknownSavepoint",
+            VectorError::ReadOnly => "ReadOnly",
+            VectorError::CapacityExceeded { .. } => "CapacityExceeded",
+            VectorError::ConcurrentWrite { .. } => "ConcurrentWrite",
+            VectorError::InvalidBuffer { .. } => "InvalidBuffer",
+            VectorError::UnknownGroup { .. } => "UnknownGroup",
+            VectorError::InvalidVector { .. } => "InvalidVector",
+            VectorError::ExportFailed { .. } => "ExportFailed",
+            VectorError::InvalidSnapshot { .. } => "InvalidSnapshot",
+            VectorError::ReadInProgress { .. } => "ReadInProgress",
+            VectorError::AccumulatorOverflow { .. } => "AccumulatorOverflow",
+            VectorError::UnknownResultSet { .. } => "UnknownResultSet",
+            VectorError::NotPositiveDefinite { .. } => "NotPositiveDefinite",
+            VectorError::CallbackFailed { .. } => "CallbackFailed",
+        }
+    }
+}
+
+impl fmt::Display for VectorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VectorError::StaleCursor { token, current } => write!(
+                f,
+                "Collection changed since sequence {} (now at {}); re-run the query",
+                token, current
+            ),
+            VectorError::InvalidOptions { message } => write!(f, "Invalid options: {}", message),
+            VectorError::UnknownSavepoint { name } => write!(f, "No savepoint named {:?}", name),
+            VectorError::ReadOnly => write!(f, "This view is read-only"),
+            VectorError::CapacityExceeded { capacity } => {
+                write!(f, "Capacity of {} vectors exceeded", capacity)
+            }
+            VectorError::ConcurrentWrite { attempts } => write!(
+                f,
+                "Data changed during each of {} read attempts; retry later",
+                attempts
+            ),
+            VectorError::InvalidBuffer { message } => {
+                write!(f, "Invalid shared buffer: {}", message)
+            }
+            VectorError::UnknownGroup { dimensions } => {
+                write!(f, "No vectors with {} dimensions are stored", dimensions)
+            }
+            VectorError::InvalidVector {
+                index,
+                dimension,
+                problem,
+            } => {
+                match index {
+                    Some(index) => write!(f, "Vector {} has {}", index, problem)?,
+                    None => write!(f, "Query vector has {}", problem)?,
+                }
+                match dimension {
+                    Some(dimension) => write!(f, " at dimension {}", dimension),
+                    None => Ok(()),
+                }
+            }
+            VectorError::ExportFailed { message } => write!(f, "Export failed: {}", message),
+            VectorError::InvalidSnapshot { message } => {
+                write!(f, "Invalid snapshot: {}", message)
+            }
+            VectorError::ReadInProgress { readers } => {
+                write!(f, "{} open read(s) must end before this operation", readers)
+            }
+            VectorError::AccumulatorOverflow { dimensions, limit } => write!(
+                f,
+                "Int8 scoring over {} dimensions can overflow (limit {}); use overflow: \"widening\"",
+                dimensions, limit
+            ),
+            VectorError::UnknownResultSet { handle } => {
+                write!(f, "No open result set with handle {}", handle)
+            }
+            VectorError::NotPositiveDefinite { dimension } => write!(
+                f,
+                "Matrix is not positive definite at dimension {}; add regularization",
+                dimension
+            ),
+            VectorError::CallbackFailed { .. } => write!(f, "customMetric callback threw"),
+        }
+    }
+}
+
+impl From<VectorError> for JsValue {
+    fn from(err: VectorError) -> Self {
+        if let VectorError::CallbackFailed { error } = err {
+            return error;
+        }
+
+        let js_err = js_sys::Error::new(&err.to_string());
+        js_err.set_name(err.name());
+        js_err.into()
+    }
+}
@@ -0,0 +1,162 @@
+//! Structured telemetry events for observability pipelines.
+//!
+//! A single listener registered with `setEventListener` receives every event
+//! as a plain object with a `type` field. Nothing is measured or allocated
+//! for events while no listener is set, and a listener that throws is
+//! ignored so telemetry can never fail a vector operation.
+
+use std::cell::{Cell, RefCell};
+
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::trace::{now_ms, ScanCounts, ScanStrategy};
+
+thread_local! {
+    static LISTENER: RefCell<Option<js_sys::Function>> = const { RefCell::new(None) };
+    /// Linear-memory size when last checked, for `memory_grown`
+    static MEMORY_BYTES: Cell<usize> = const { Cell::new(0) };
+}
+
+/// What a structure build produced
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum BuildKind {
+    ClusterTree,
+    Quantization,
+}
+
+/// One telemetry event, serialized with a snake_case `type` tag
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum VectorEvent {
+    #[serde(rename_all = "camelCase")]
+    SearchStarted {
+        /// Class the query ran on, e.g. `VectorIndex`
+        source: &'static str,
+        k: usize,
+        dimensions: usize,
+    },
+    #[serde(rename_all = "camelCase")]
+    SearchCompleted {
+        source: &'static str,
+        latency_ms: f64,
+        strategy: ScanStrategy,
+        /// Vectors and compressed codes scored; zero for cache hits
+        candidates_scanned: usize,
+        results: usize,
+        /// Answered from the result cache
+        cached: bool,
+    },
+    #[serde(rename_all = "camelCase")]
+    IndexBuilt {
+        kind: BuildKind,
+        vectors: usize,
+        duration_ms: f64,
+    },
+    #[serde(rename_all = "camelCase")]
+    MemoryGrown {
+        previous_bytes: usize,
+        current_bytes: usize,
+    },
+}
+
+/// Register `callback(event)` to receive telemetry events, replacing any
+/// previous listener; pass `null` or `undefined` to stop
+#[wasm_bindgen(js_name = "setEventListener")]
+pub fn set_event_listener(callback: Option<js_sys::Function>) {
+    if callback.is_some() {
+        MEMORY_BYTES.with(|bytes| bytes.set(memory_bytes()));
+    }
+    LISTENER.with(|listener| *listener.borrow_mut() = callback);
+}
+
+/// Whether a listener is registered, so callers can skip measuring
+pub(crate) fn enabled() -> bool {
+    LISTENER.with(|listener| listener.borrow().is_some())
+}
+
+/// Timestamp to measure an operation from, taken only while listening
+pub(crate) fn start() -> Option<f64> {
+    enabled().then(now_ms)
+}
+
+/// Deliver the event built by `event` to the listener, if any
+pub(crate) fn emit(event: impl FnOnce() -> VectorEvent) {
+    // Cloned out so a listener may re-register without a borrow conflict
+    let Some(listener) = LISTENER.with(|listener| listener.borrow().clone()) else {
+        return;
+    };
+    if let Ok(value) = crate::to_js(&event()) {
+        let _ = listener.call1(&JsValue::NULL, &value);
+    }
+}
+
+/// Emit the `search_started` event for a query
+pub(crate) fn search_started(source: &'static str, k: usize, dimensions: usize) {
+    emit(|| VectorEvent::SearchStarted {
+        source,
+        k,
+        dimensions,
+    });
+}
+
+/// Emit `search_completed` for a query timed from `started`
+pub(crate) fn search_completed(
+    source: &'static str,
+    started: Option<f64>,
+    scan: &ScanCounts,
+    results: usize,
+    cached: bool,
+) {
+    let Some(started) = started else {
+        return;
+    };
+    emit(|| VectorEvent::SearchCompleted {
+        source,
+        latency_ms: now_ms() - started,
+        strategy: scan.strategy,
+        candidates_scanned: scan.vectors + scan.codes,
+        results,
+        cached,
+    });
+    check_memory();
+}
+
+/// Emit `index_built` for a build timed from `started`
+pub(crate) fn index_built(kind: BuildKind, vectors: usize, started: Option<f64>) {
+    let Some(started) = started else {
+        return;
+    };
+    emit(|| VectorEvent::IndexBuilt {
+        kind,
+        vectors,
+        duration_ms: now_ms() - started,
+    });
+    check_memory();
+}
+
+/// Emit `memory_grown` if linear memory grew since the last check
+pub(crate) fn check_memory() {
+    if !enabled() {
+        return;
+    }
+
+    let current = memory_bytes();
+    let previous = MEMORY_BYTES.with(|bytes| bytes.replace(current));
+    if current > previous {
+        emit(|| VectorEvent::MemoryGrown {
+            previous_bytes: previous,
+            current_bytes: current,
+        });
+    }
+}
+
+/// Current size of wasm linear memory
+pub(crate) fn memory_bytes() -> usize {
+    wasm_bindgen::memory()
+        .unchecked_into::<js_sys::WebAssembly::Memory>()
+        .buffer()
+        .unchecked_into::<js_sys::ArrayBuffer>()
+        .byte_length() as usize
+}
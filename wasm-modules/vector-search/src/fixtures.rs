@@ -0,0 +1,172 @@
+//! Canonical known-answer vectors for cross-binding parity tests.
+//!
+//! Every expected value is produced by the exported Rust functions
+//! themselves, so the suite is the reference: other implementations (the
+//! TypeScript fallbacks, future language bindings) load the JSON and check
+//! their own outputs against it within each case's relative tolerances:
+//! `|actual - expected| <= tolerance * |expected|`, so an expected 0 must
+//! be matched exactly. The tolerances grow with the dimensions, leaving room
+//! for bindings that sum in a different order than the SIMD kernels here.
+
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::rng::SplitMix64;
+use crate::{to_js, VectorSearch};
+
+/// Bumped whenever cases are added or expected values change meaning
+const SUITE_VERSION: u32 = 2;
+
+/// Rounding steps allowed per dimension, relative to the expected value
+const ULPS_PER_DIMENSION: f64 = 16.0;
+
+/// Seed for the generated cases; changing it changes the suite
+const SUITE_SEED: u64 = 0x5EED_CAFE;
+
+/// Expected outputs for one pair of vectors
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KnownAnswerCase {
+    pub name: String,
+    /// Maximum relative difference allowed for f64 outputs
+    pub tolerance: f64,
+    /// Maximum relative difference allowed for `cosine_f32`
+    pub f32_tolerance: f64,
+    pub a: Vec<f64>,
+    pub b: Vec<f64>,
+    pub weights: Vec<f64>,
+    pub cosine: f64,
+    pub euclidean: f64,
+    pub dot: f64,
+    pub weighted_cosine: f64,
+    pub weighted_euclidean: f64,
+    pub weighted_dot: f64,
+    /// `a` after `normalizeVector` (unchanged when `a` has zero norm)
+    pub normalized_a: Vec<f64>,
+    /// `cosineSimilaritySIMD` on `a` and `b` rounded to f32
+    pub cosine_f32: f64,
+}
+
+/// The full known-answer suite, serialized as JSON
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KnownAnswerSuite {
+    pub version: u32,
+    pub cases: Vec<KnownAnswerCase>,
+}
+
+fn answer(name: &str, a: Vec<f64>, b: Vec<f64>, weights: Vec<f64>) -> KnownAnswerCase {
+    let search = VectorSearch::new(a.len());
+
+    let mut normalized_a = a.clone();
+    search.normalize_vector(&mut normalized_a);
+
+    let a_f32: Vec<f32> = a.iter().map(|&x| x as f32).collect();
+    let b_f32: Vec<f32> = b.iter().map(|&x| x as f32).collect();
+
+    let steps = ULPS_PER_DIMENSION * a.len() as f64;
+
+    KnownAnswerCase {
+        name: name.to_string(),
+        tolerance: steps * f64::EPSILON,
+        f32_tolerance: steps * f32::EPSILON as f64,
+        cosine: search.cosine_similarity(&a, &b),
+        euclidean: search.euclidean_distance(&a, &b),
+        dot: search.dot_product(&a, &b),
+        weighted_cosine: search.weighted_cosine_similarity(&a, &b, &weights),
+        weighted_euclidean: search.weighted_euclidean_distance(&a, &b, &weights),
+        weighted_dot: search.weighted_dot_product(&a, &b, &weights),
+        normalized_a,
+        cosine_f32: search.cosine_similarity_simd(&a_f32, &b_f32) as f64,
+        a,
+        b,
+        weights,
+    }
+}
+
+/// Hand-picked edge cases followed by seeded random cases whose dimensions
+/// exercise both full SIMD lanes and remainders
+pub(crate) fn known_answer_suite() -> KnownAnswerSuite {
+    let ones = |n: usize| vec![1.0; n];
+
+    let mut cases = vec![
+        answer(
+            "identical",
+            vec![1.0, 2.0, 3.0],
+            vec![1.0, 2.0, 3.0],
+            ones(3),
+        ),
+        answer(
+            "opposite",
+            vec![1.0, -2.0, 3.0],
+            vec![-1.0, 2.0, -3.0],
+            ones(3),
+        ),
+        answer(
+            "orthogonal",
+            vec![1.0, 0.0, 0.0, 0.0],
+            vec![0.0, 1.0, 0.0, 0.0],
+            ones(4),
+        ),
+        answer(
+            "zero-vector",
+            vec![0.0; 4],
+            vec![1.0, 2.0, 3.0, 4.0],
+            ones(4),
+        ),
+        answer(
+            "zero-weights",
+            vec![1.0, 2.0],
+            vec![3.0, 4.0],
+            vec![0.0, 0.0],
+        ),
+        answer("single-dimension", vec![-2.5], vec![4.0], vec![2.0]),
+        answer(
+            "weighted-axis",
+            vec![1.0, 1.0, 0.0],
+            vec![1.0, 0.0, 1.0],
+            vec![4.0, 0.25, 1.0],
+        ),
+        answer(
+            "large-magnitude",
+            vec![1e15, -3e15, 2e15],
+            vec![2e15, 1e15, -1e15],
+            ones(3),
+        ),
+        answer(
+            "small-magnitude",
+            vec![1e-15, 3e-15, -2e-15],
+            vec![2e-15, -1e-15, 1e-15],
+            ones(3),
+        ),
+    ];
+
+    let mut rng = SplitMix64::new(SUITE_SEED);
+    for dimensions in [3, 4, 7, 16, 37, 128] {
+        let mut draw = |scale: f64, shift: f64| -> Vec<f64> {
+            (0..dimensions)
+                .map(|_| rng.next_f64() * scale + shift)
+                .collect()
+        };
+        let a = draw(2.0, -1.0);
+        let b = draw(2.0, -1.0);
+        let weights = draw(1.0, 0.0);
+        cases.push(answer(&format!("random-{}", dimensions), a, b, weights));
+    }
+
+    KnownAnswerSuite {
+        version: SUITE_VERSION,
+        cases,
+    }
+}
+
+#[wasm_bindgen]
+impl VectorSearch {
+    /// Canonical input vectors with the expected output of every metric, as
+    /// JSON, so other implementations can verify parity with this one
+    #[wasm_bindgen(js_name = "knownAnswerSuite")]
+    pub fn known_answer_suite() -> Result<String, JsValue> {
+        let json = js_sys::JSON::stringify(&to_js(&known_answer_suite())?)?;
+        Ok(json.into())
+    }
+}
@@ -0,0 +1,480 @@
+//! WebGPU batch scoring, compiled with the `webgpu` feature.
+//!
+//! The corpus is uploaded to the GPU once when the scorer is created; each
+//! query then costs one small upload, one compute dispatch per shard (a
+//! thread per corpus row) and one read-back of the scores. Corpora larger
+//! than the device's storage binding limit are split by row into shards that
+//! each fit one binding. When WebGPU is unavailable, or a single row exceeds
+//! the limits, the scorer keeps the corpus in WASM memory and scores on the
+//! CPU instead, so callers don't need a separate code path.
+
+use std::rc::Rc;
+
+use serde::Deserialize;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+use wgpu::util::DeviceExt;
+
+use crate::error::VectorError;
+use crate::kernels;
+use crate::metric::{MetricKind, Scorer};
+use crate::options_from_js;
+
+/// Threads per workgroup; must match `@workgroup_size` in the shader
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Largest workgroup count allowed along one dispatch dimension
+const MAX_GROUPS_PER_DIMENSION: u32 = 65535;
+
+/// Floats converted per `write_buffer` call while uploading, so the upload
+/// never holds a second full copy of the corpus
+const UPLOAD_CHUNK: usize = 1 << 16;
+
+const SHADER: &str = r#"
+struct Params {
+    dims: u32,
+    count: u32,
+    metric: u32,
+    query_norm: f32,
+}
+
+@group(0) @binding(0) var<storage, read> corpus: array<f32>;
+@group(0) @binding(1) var<storage, read> norms: array<f32>;
+@group(0) @binding(2) var<storage, read> query: array<f32>;
+@group(0) @binding(3) var<uniform> params: Params;
+@group(0) @binding(4) var<storage, read_write> scores: array<f32>;
+
+@compute @workgroup_size(64)
+fn main(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>,
+) {
+    let row = id.y * groups.x * 64u + id.x;
+    if (row >= params.count) {
+        return;
+    }
+
+    let base = row * params.dims;
+    var acc = 0.0;
+    for (var j = 0u; j < params.dims; j = j + 1u) {
+        let a = corpus[base + j];
+        let q = query[j];
+        if (params.metric == 2u) {
+            let d = q - a;
+            acc = acc + d * d;
+        } else {
+            acc = acc + q * a;
+        }
+    }
+
+    if (params.metric == 0u) {
+        let magnitude = params.query_norm * norms[row];
+        scores[row] = select(acc / magnitude, 0.0, magnitude == 0.0);
+    } else if (params.metric == 2u) {
+        scores[row] = sqrt(acc);
+    } else {
+        scores[row] = acc;
+    }
+}
+"#;
+
+/// Options for `GpuScorer.create`
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GpuScorerOptions {
+    /// Built-in metric to score with; custom callbacks are not supported
+    pub metric: MetricKind,
+    /// Skip WebGPU entirely and score on the CPU
+    pub force_cpu: bool,
+}
+
+/// A contiguous run of corpus rows small enough for one storage binding
+struct Shard {
+    /// Index of the shard's first row in the corpus
+    start: usize,
+    count: usize,
+    corpus: wgpu::Buffer,
+    norms: wgpu::Buffer,
+}
+
+/// Corpus and pipeline resident on the GPU
+struct GpuCorpus {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+    shards: Vec<Shard>,
+}
+
+enum Backend {
+    Gpu(GpuCorpus),
+    Cpu(Vec<f32>),
+}
+
+struct ScorerState {
+    dimensions: usize,
+    count: usize,
+    metric: MetricKind,
+    backend: Backend,
+}
+
+/// Batch scorer for large corpora, running on WebGPU when available and on
+/// the CPU otherwise.
+///
+/// Create it with `await GpuScorer.create(dimensions, vectors, count,
+/// options)`, then call `await scorer.score(query)` for a `Float32Array` of
+/// scores in corpus order. Check `backend` to see which path was chosen.
+#[wasm_bindgen]
+pub struct GpuScorer {
+    state: Rc<ScorerState>,
+}
+
+fn to_bytes(values: &[f32]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+/// Create a storage buffer holding `values`, written in `UPLOAD_CHUNK`
+/// pieces
+fn upload_storage(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    label: &str,
+    values: &[f32],
+) -> wgpu::Buffer {
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size: std::mem::size_of_val(values) as u64,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let mut bytes = Vec::with_capacity(UPLOAD_CHUNK.min(values.len()) * 4);
+    for (i, chunk) in values.chunks(UPLOAD_CHUNK).enumerate() {
+        bytes.clear();
+        bytes.extend(chunk.iter().flat_map(|v| v.to_le_bytes()));
+        queue.write_buffer(&buffer, (i * UPLOAD_CHUNK * 4) as u64, &bytes);
+    }
+    buffer
+}
+
+fn metric_code(metric: MetricKind) -> u32 {
+    match metric {
+        MetricKind::Cosine => 0,
+        MetricKind::Dot => 1,
+        MetricKind::Euclidean => 2,
+        MetricKind::Custom => unreachable!("rejected in GpuScorer::create"),
+    }
+}
+
+/// Wait for a buffer mapping by bridging its callback to a JS promise
+async fn map_read(device: &wgpu::Device, slice: wgpu::BufferSlice<'_>) -> Result<(), JsValue> {
+    let mut resolve = None;
+    let promise = js_sys::Promise::new(&mut |res, _| resolve = Some(res));
+    let resolve = resolve.expect("promise executor runs synchronously");
+
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = resolve.call1(&JsValue::NULL, &JsValue::from_bool(result.is_ok()));
+    });
+    device.poll(wgpu::Maintain::Wait);
+
+    if JsFuture::from(promise).await?.as_bool() == Some(true) {
+        Ok(())
+    } else {
+        Err(JsValue::from_str("Failed to read GPU scores"))
+    }
+}
+
+impl GpuCorpus {
+    /// Upload the corpus in shards, or `None` if WebGPU is unavailable or a
+    /// single row or the score read-back doesn't fit the device's limits
+    async fn upload(
+        vectors: &[f32],
+        norms: &[f32],
+        dimensions: usize,
+        count: usize,
+    ) -> Option<Self> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::BROWSER_WEBGPU,
+            ..Default::default()
+        });
+
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                force_fallback_adapter: false,
+                compatible_surface: None,
+            })
+            .await?;
+
+        let limits = adapter.limits();
+        let binding_bytes =
+            (limits.max_storage_buffer_binding_size as u64).min(limits.max_buffer_size);
+        let row_bytes = (dimensions * std::mem::size_of::<f32>()) as u64;
+        let rows_per_shard = (binding_bytes / row_bytes) as usize;
+        let scores_bytes = (count * std::mem::size_of::<f32>()) as u64;
+        if rows_per_shard == 0 || scores_bytes > limits.max_buffer_size {
+            log!(
+                "Rows of {} bytes or {} bytes of scores exceed WebGPU storage limits, scoring on CPU",
+                row_bytes,
+                scores_bytes
+            );
+            return None;
+        }
+
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: Some("vector-search"),
+                    required_features: wgpu::Features::empty(),
+                    required_limits: limits,
+                },
+                None,
+            )
+            .await
+            .ok()?;
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("vector-search scoring"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("vector-search scoring"),
+            layout: None,
+            module: &module,
+            entry_point: "main",
+            compilation_options: Default::default(),
+        });
+
+        let shards = (0..count)
+            .step_by(rows_per_shard)
+            .map(|start| {
+                let end = (start + rows_per_shard).min(count);
+                Shard {
+                    start,
+                    count: end - start,
+                    corpus: upload_storage(
+                        &device,
+                        &queue,
+                        "corpus",
+                        &vectors[start * dimensions..end * dimensions],
+                    ),
+                    norms: upload_storage(&device, &queue, "norms", &norms[start..end]),
+                }
+            })
+            .collect();
+
+        Some(Self {
+            device,
+            queue,
+            pipeline,
+            shards,
+        })
+    }
+
+    async fn score(
+        &self,
+        query: &[f32],
+        count: usize,
+        metric: MetricKind,
+    ) -> Result<Vec<f32>, JsValue> {
+        let device = &self.device;
+        let dims = query.len() as u32;
+        let query_norm = query.iter().map(|x| x * x).sum::<f32>().sqrt();
+
+        // Per-call buffers keep concurrent `score` calls independent
+        let query = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("query"),
+            contents: &to_bytes(query),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        let size = (count * std::mem::size_of::<f32>()) as u64;
+        let staging = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("scores read-back"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("scoring"),
+        });
+        for shard in &self.shards {
+            let mut params = Vec::with_capacity(16);
+            params.extend_from_slice(&dims.to_le_bytes());
+            params.extend_from_slice(&(shard.count as u32).to_le_bytes());
+            params.extend_from_slice(&metric_code(metric).to_le_bytes());
+            params.extend_from_slice(&query_norm.to_le_bytes());
+            let params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("params"),
+                contents: &params,
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+
+            let shard_size = (shard.count * std::mem::size_of::<f32>()) as u64;
+            let output = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("scores"),
+                size: shard_size,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            });
+
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("scoring"),
+                layout: &self.pipeline.get_bind_group_layout(0),
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: shard.corpus.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: shard.norms.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: query.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: params.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: output.as_entire_binding(),
+                    },
+                ],
+            });
+
+            // Rows beyond 65535 workgroups spill into the second dimension
+            let groups = (shard.count as u32).div_ceil(WORKGROUP_SIZE);
+            let groups_x = groups.min(MAX_GROUPS_PER_DIMENSION);
+            let groups_y = groups.div_ceil(groups_x);
+
+            {
+                let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("scoring"),
+                    timestamp_writes: None,
+                });
+                pass.set_pipeline(&self.pipeline);
+                pass.set_bind_group(0, &bind_group, &[]);
+                pass.dispatch_workgroups(groups_x, groups_y, 1);
+            }
+            let offset = (shard.start * std::mem::size_of::<f32>()) as u64;
+            encoder.copy_buffer_to_buffer(&output, 0, &staging, offset, shard_size);
+        }
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = staging.slice(..);
+        map_read(device, slice).await?;
+
+        let scores = slice
+            .get_mapped_range()
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        staging.unmap();
+
+        Ok(scores)
+    }
+}
+
+impl ScorerState {
+    fn score_cpu(&self, vectors: &[f32], query: &[f32]) -> Vec<f32> {
+        if self.metric == MetricKind::Cosine {
+            let mut out = Vec::with_capacity(self.count);
+            kernels::cosine_rows_f32(query, vectors, &mut out);
+            return out;
+        }
+
+        let scorer = Scorer::new(query, self.metric, None, false);
+        vectors
+            .chunks_exact(self.dimensions)
+            .map(|row| scorer.score(row) as f32)
+            .collect()
+    }
+
+    async fn score(&self, query: Vec<f32>) -> Result<Vec<f32>, JsValue> {
+        if query.len() != self.dimensions {
+            panic!("Query vector dimension mismatch");
+        }
+
+        match &self.backend {
+            Backend::Gpu(gpu) => gpu.score(&query, self.count, self.metric).await,
+            Backend::Cpu(vectors) => Ok(self.score_cpu(vectors, &query)),
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl GpuScorer {
+    /// Upload `count` vectors for repeated scoring, preferring WebGPU and
+    /// falling back to the CPU
+    pub async fn create(
+        dimensions: usize,
+        vectors: Vec<f32>,
+        count: usize,
+        options: JsValue,
+    ) -> Result<GpuScorer, JsValue> {
+        let options: GpuScorerOptions = options_from_js(options)?;
+        if options.metric == MetricKind::Custom {
+            return Err(VectorError::InvalidOptions {
+                message: "GpuScorer supports only built-in metrics".to_string(),
+            }
+            .into());
+        }
+
+        if vectors.len() != count * dimensions {
+            panic!("Vectors array size mismatch");
+        }
+
+        let gpu = if options.force_cpu || count == 0 || dimensions == 0 {
+            None
+        } else {
+            let norms: Vec<f32> = vectors
+                .chunks_exact(dimensions)
+                .map(|row| row.iter().map(|x| x * x).sum::<f32>().sqrt())
+                .collect();
+            GpuCorpus::upload(&vectors, &norms, dimensions, count).await
+        };
+
+        let backend = match gpu {
+            Some(gpu) => Backend::Gpu(gpu),
+            None => Backend::Cpu(vectors),
+        };
+
+        Ok(GpuScorer {
+            state: Rc::new(ScorerState {
+                dimensions,
+                count,
+                metric: options.metric,
+                backend,
+            }),
+        })
+    }
+
+    /// `"webgpu"` when scoring runs on the GPU, `"cpu"` after a fallback
+    #[wasm_bindgen(getter)]
+    pub fn backend(&self) -> String {
+        match self.state.backend {
+            Backend::Gpu(_) => "webgpu".to_string(),
+            Backend::Cpu(_) => "cpu".to_string(),
+        }
+    }
+
+    /// Number of vectors in the uploaded corpus
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> usize {
+        self.state.count
+    }
+
+    /// Score `query` against every uploaded vector, resolving to a
+    /// `Float32Array` in corpus order. Euclidean scores are distances.
+    pub fn score(&self, query: Vec<f32>) -> js_sys::Promise {
+        let state = Rc::clone(&self.state);
+        future_to_promise(async move {
+            let scores = state.score(query).await?;
+            Ok(js_sys::Float32Array::from(&scores[..]).into())
+        })
+    }
+}
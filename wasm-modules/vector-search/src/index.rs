@@ -0,0 +1,968 @@
+mod batch;
+mod collapse;
+#[cfg(feature = "parquet")]
+mod export;
+mod negatives;
+mod reads;
+mod rebuild;
+mod result_cache;
+mod result_sets;
+mod savepoint;
+mod segments;
+mod streaming;
+mod triplets;
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::calibration::normalized_scores;
+use crate::cluster_tree::{ClusterTree, ClusterTreeParams, ClusterTreeStats};
+use crate::cutoff::apply_auto_k;
+use crate::error::VectorError;
+use crate::events::{self, BuildKind};
+use crate::metric::{self, MetricKind, Scorer};
+use crate::prune::PruneBounds;
+use crate::quantization::{Codes, QuantizationOptions, QuantizationStats};
+use crate::query_defaults::QueryDefaults;
+use crate::rows::Rows;
+use crate::search::{select_expanding, select_filtered, QueryOptions, TopK};
+use crate::snapshot::{encode_snapshot, SnapshotOptions};
+use crate::trace::{now_ms, QueryTrace, ScanCosts, ScanCounts, ScanStrategy};
+use crate::validation::ValidationOptions;
+use crate::{options_from_js, to_js};
+use reads::DeferredWrite;
+use rebuild::Rebuild;
+use result_cache::ResultCache;
+use result_sets::ResultSets;
+use savepoint::Undo;
+use segments::Segments;
+
+pub use batch::{ConflictPolicy, InsertBatchReport, RejectReason, RejectedRow};
+#[cfg(feature = "parquet")]
+pub use export::{ColumnSchema, ColumnType, MetadataValue, ParquetExportOptions};
+pub use negatives::NegativeStrategy;
+pub use rebuild::{
+    IndexShape, MaintenanceProgress, RebuildPlan, RebuildReport, RebuildStep, RebuildStepKind,
+    RebuildTarget,
+};
+pub use result_cache::ResultCacheStats;
+pub use triplets::TripletReport;
+
+/// A ranked result from a `VectorIndex` query
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexHit {
+    pub id: u32,
+    pub score: f64,
+    /// Display-ready score, present when `normalize` is set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub normalized: Option<f64>,
+    /// Copies of this vector dropped from the ranking, present when
+    /// `collapseDuplicates` is set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collapsed: Option<usize>,
+}
+
+/// One page of a ranked result set.
+///
+/// `sequence` identifies the collection state the page was computed against;
+/// pass it back to `searchPage` to fetch the following pages.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResultPage {
+    pub hits: Vec<IndexHit>,
+    pub sequence: u64,
+    pub offset: usize,
+    /// Offset of the next page, or `None` when this is the last one
+    pub next_offset: Option<usize>,
+    /// Read-path statistics, present when the query set `trace`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace: Option<QueryTrace>,
+}
+
+/// Number of pending updates that triggers an automatic `flush`
+pub(crate) const DEFAULT_BATCH_SIZE: usize = 256;
+
+/// Mutable collection of f32 vectors addressed by numeric ID.
+///
+/// Inserts land in dense, segmented storage immediately, so brute-force
+/// scans see them right away. Derived per-record structures (the norm table, and any
+/// acceleration structure built over the storage) are only brought up to
+/// date in micro-batches: updated IDs are queued and applied together by
+/// `flush`, which runs automatically once `batchSize` updates are pending.
+#[wasm_bindgen]
+pub struct VectorIndex {
+    dimensions: usize,
+    ids: Vec<u32>,
+    vectors: Segments,
+    positions: HashMap<u32, usize>,
+    /// Cached L2 norm per slot; NaN while the slot's update is pending
+    norms: Vec<f64>,
+    /// IDs inserted or overwritten since the last flush
+    pending: HashSet<u32>,
+    batch_size: usize,
+    /// Change sequence, bumped on every mutation
+    sequence: u64,
+    assume_normalized: bool,
+    tree: Option<ClusterTree>,
+    /// Compressed codes, slot-aligned with `vectors`
+    codes: Option<Codes>,
+    /// False once the f32 vectors were dropped in favour of decodable codes;
+    /// `vectors` is then empty and slots are read back from `codes`
+    full_precision: bool,
+    /// Reverse of each mutation since the oldest savepoint
+    undo_log: Vec<Undo>,
+    /// Savepoint names with their position in `undo_log`, oldest first
+    savepoints: Vec<(String, usize)>,
+    /// Checks applied to inserted vectors and queries
+    validation: ValidationOptions,
+    /// Options every query starts from before its own overrides
+    query_defaults: QueryDefaults,
+    /// Recent result pages, disabled until `enableResultCache`
+    result_cache: RefCell<ResultCache>,
+    /// Ranked results kept for `nextPage`, by handle
+    result_sets: ResultSets,
+    /// Migration queued by `planRebuild`, advanced by `maintenanceTick`
+    rebuild: Option<Rebuild>,
+    /// Reads opened with `beginRead` and not yet ended
+    readers: usize,
+    /// Writes queued while reads are open, applied in order by `endRead`
+    deferred: Vec<DeferredWrite>,
+}
+
+#[wasm_bindgen]
+impl VectorIndex {
+    #[wasm_bindgen(constructor)]
+    pub fn new(dimensions: usize) -> Self {
+        log!("VectorIndex initialized with {} dimensions", dimensions);
+        Self {
+            dimensions,
+            ids: Vec::new(),
+            vectors: Segments::new(dimensions),
+            positions: HashMap::new(),
+            norms: Vec::new(),
+            pending: HashSet::new(),
+            batch_size: DEFAULT_BATCH_SIZE,
+            sequence: 0,
+            assume_normalized: false,
+            tree: None,
+            codes: None,
+            full_precision: true,
+            undo_log: Vec::new(),
+            savepoints: Vec::new(),
+            validation: ValidationOptions::default(),
+            query_defaults: QueryDefaults::default(),
+            result_cache: RefCell::new(ResultCache::default()),
+            result_sets: ResultSets::default(),
+            rebuild: None,
+            readers: 0,
+            deferred: Vec::new(),
+        }
+    }
+
+    /// Number of pending updates that triggers an automatic flush
+    #[wasm_bindgen(getter, js_name = "batchSize")]
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    #[wasm_bindgen(setter, js_name = "batchSize")]
+    pub fn set_batch_size(&mut self, value: usize) {
+        self.batch_size = value.max(1);
+        if self.pending.len() >= self.batch_size {
+            self.flush();
+        }
+    }
+
+    /// Number of inserts whose derived structures have not been updated yet
+    #[wasm_bindgen(getter, js_name = "pendingCount")]
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Apply all pending updates to derived structures in one batch,
+    /// returning how many records were updated. Does nothing while reads
+    /// are open, since routing updates into the cluster tree can change
+    /// results.
+    pub fn flush(&mut self) -> usize {
+        if self.readers > 0 {
+            return 0;
+        }
+
+        let mut applied = 0;
+        // Detached while updating so it can be fed slots read from `self`
+        let mut tree = self.tree.take();
+
+        for id in std::mem::take(&mut self.pending) {
+            // IDs removed after being queued have nothing left to update
+            let Some(&slot) = self.positions.get(&id) else {
+                continue;
+            };
+
+            let vector = self.slot(slot);
+            let norm = metric::dot(&vector, &vector).sqrt();
+            if let Some(tree) = tree.as_mut() {
+                tree.insert(id, &vector);
+            }
+            self.norms[slot] = norm;
+            applied += 1;
+        }
+
+        self.tree = tree;
+        if applied > 0 {
+            self.invalidate_results();
+        }
+
+        applied
+    }
+
+    /// Whether stored vectors and queries are unit vectors, letting cosine
+    /// queries skip norm computation entirely
+    #[wasm_bindgen(getter, js_name = "assumeNormalized")]
+    pub fn assume_normalized(&self) -> bool {
+        self.assume_normalized
+    }
+
+    #[wasm_bindgen(setter, js_name = "assumeNormalized")]
+    pub fn set_assume_normalized(&mut self, value: bool) {
+        self.assume_normalized = value;
+        self.invalidate_results();
+    }
+
+    /// Number of stored vectors
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Number of storage segments (of up to 16MB each) holding the
+    /// full-precision vectors
+    #[wasm_bindgen(getter, js_name = "segmentCount")]
+    pub fn segment_count(&self) -> usize {
+        self.vectors.segment_count()
+    }
+
+    /// Bytes allocated for full-precision vectors
+    #[wasm_bindgen(getter, js_name = "storageBytes")]
+    pub fn storage_bytes(&self) -> usize {
+        self.vectors.allocated_bytes()
+    }
+
+    /// Check inserted vectors and queries for NaN, infinite values and zero
+    /// norms, rejecting or sanitizing them as `options.mode` says
+    #[wasm_bindgen(js_name = "setValidation")]
+    pub fn set_validation(&mut self, options: JsValue) -> Result<(), JsValue> {
+        self.validation = options_from_js(options)?;
+        Ok(())
+    }
+
+    /// Set query options applied to every query on this index; keys passed
+    /// with a query override them. Pass `null` to clear.
+    #[wasm_bindgen(js_name = "setQueryDefaults")]
+    pub fn set_query_defaults(&mut self, options: JsValue) -> Result<(), JsValue> {
+        self.query_defaults = QueryDefaults::from_js(options)?;
+        Ok(())
+    }
+
+    /// Query options as this index resolves them before per-query
+    /// overrides, suitable for storing alongside the index's data
+    #[wasm_bindgen(js_name = "queryDefaults")]
+    pub fn query_defaults(&self) -> Result<JsValue, JsValue> {
+        self.query_defaults.to_js()
+    }
+
+    /// Insert a vector, replacing any existing vector with the same ID.
+    ///
+    /// Fails with an `InvalidVector` error if validation is enabled and
+    /// rejects the vector. While reads are open the insert is queued and
+    /// becomes visible when the last read ends.
+    pub fn insert(&mut self, id: u32, vector: &[f32]) -> Result<(), JsValue> {
+        if vector.len() != self.dimensions {
+            panic!("Vector dimension mismatch");
+        }
+
+        let vector = self.validation.check(vector, Some(id as usize))?;
+        if self.readers > 0 {
+            self.defer_insert(id, &vector);
+            return Ok(());
+        }
+        self.store(id, &vector);
+        events::check_memory();
+        Ok(())
+    }
+
+    /// Remove a vector by ID, returning whether it existed. While reads are
+    /// open the remove is queued, and the result says whether the ID will
+    /// exist when it applies.
+    pub fn remove(&mut self, id: u32) -> bool {
+        if self.readers > 0 {
+            return self.defer_remove(id);
+        }
+
+        let Some(slot) = self.positions.remove(&id) else {
+            return false;
+        };
+        self.record_remove(id, slot);
+
+        // Move the last vector into the freed slot to keep storage dense
+        let last = self.ids.len() - 1;
+        if let Some(codes) = self.codes.as_mut() {
+            codes.swap_remove(slot);
+        }
+        if slot != last {
+            let moved_id = self.ids[last];
+            if self.full_precision {
+                self.vectors.copy_row(last, slot);
+            }
+            self.ids[slot] = moved_id;
+            self.norms[slot] = self.norms[last];
+            self.positions.insert(moved_id, slot);
+        }
+
+        self.ids.pop();
+        self.norms.pop();
+        // Frees the last segment once it empties
+        self.vectors.pop();
+        self.pending.remove(&id);
+        if let Some(tree) = self.tree.as_mut() {
+            tree.remove(id);
+        }
+        self.sequence += 1;
+        true
+    }
+
+    /// Build a cluster tree (recursive k-means) over the stored vectors.
+    ///
+    /// Once built, queries descend the tree with a beam instead of scanning
+    /// every vector; pass `exact: true` in the query options to bypass it.
+    /// Later inserts are routed into the tree on `flush`, and until then are
+    /// scanned exhaustively so they are never missed.
+    #[wasm_bindgen(js_name = "buildClusterTree")]
+    pub fn build_cluster_tree(&mut self, params: JsValue) -> Result<JsValue, JsValue> {
+        self.check_no_readers()?;
+        let params: ClusterTreeParams = options_from_js(params)?;
+        to_js(&self.build_tree(params))
+    }
+
+    /// Serialize the collection in the range-readable snapshot format,
+    /// partitioned into k-means lists so `RemoteSnapshot` can fetch only the
+    /// lists a query probes. Query defaults are stored with it; a
+    /// `customMetric` callback can't be serialized and is left out.
+    pub fn snapshot(&self, options: JsValue) -> Result<Vec<u8>, JsValue> {
+        let options: SnapshotOptions = options_from_js(options)?;
+        let defaults = self.query_defaults.to_json()?;
+        let bytes = encode_snapshot(self, &self.ids, &defaults, &options)?;
+        log!(
+            "Wrote snapshot of {} vectors as {} bytes",
+            self.ids.len(),
+            bytes.len()
+        );
+        Ok(bytes)
+    }
+
+    /// Discard the cluster tree, returning to exhaustive scans
+    #[wasm_bindgen(js_name = "dropClusterTree")]
+    pub fn drop_cluster_tree(&mut self) -> Result<(), JsValue> {
+        self.check_no_readers()?;
+        self.tree = None;
+        self.invalidate_results();
+        Ok(())
+    }
+
+    /// Train compressed codes over the stored vectors and keep them in sync
+    /// with later inserts.
+    ///
+    /// Queries then run in two stages: the codes are scanned to pick
+    /// `rerankK` candidates, which are re-scored with the full-precision
+    /// vectors. Raising `rerankK` trades speed for recall.
+    ///
+    /// With the `int8` codec and `keepOriginals: false` the f32 vectors are
+    /// dropped and re-ranking uses dequantized vectors instead, storing
+    /// roughly a quarter of the bytes.
+    #[wasm_bindgen(js_name = "enableQuantization")]
+    pub fn enable_quantization(&mut self, options: JsValue) -> Result<JsValue, JsValue> {
+        self.check_no_readers()?;
+        let options: QuantizationOptions = options_from_js(options)?;
+        to_js(&self.quantize(&options)?)
+    }
+
+    /// Drop compressed codes, returning to full-precision scans. Vectors
+    /// stored only as codes are decoded back into f32 storage first.
+    #[wasm_bindgen(js_name = "disableQuantization")]
+    pub fn disable_quantization(&mut self) -> Result<(), JsValue> {
+        self.check_no_readers()?;
+        self.materialize();
+        self.codes = None;
+        self.invalidate_results();
+        Ok(())
+    }
+
+    /// Whether a vector with this ID is stored
+    pub fn contains(&self, id: u32) -> bool {
+        self.positions.contains_key(&id)
+    }
+
+    /// Return the first page of results for `query`.
+    ///
+    /// Uses the same options as `VectorSearch.search`, except that `exclude`
+    /// lists IDs rather than buffer indices.
+    pub fn search(&self, query: &[f32], k: usize, options: JsValue) -> Result<JsValue, JsValue> {
+        let query = self.validation.check(query, None)?;
+        let options = self.query_defaults.resolve(options)?;
+        to_js(&self.cached_page(&query, 0, k, &options)?)
+    }
+
+    /// Return a later page of a result set started by `search`.
+    ///
+    /// Fails with a `StaleCursor` error if the collection changed since the
+    /// result set's `sequence`, so pages from different generations are
+    /// never mixed.
+    #[wasm_bindgen(js_name = "searchPage")]
+    pub fn search_page(
+        &self,
+        query: &[f32],
+        offset: usize,
+        page_size: usize,
+        sequence: f64,
+        options: JsValue,
+    ) -> Result<JsValue, JsValue> {
+        // Sequences cross the boundary as plain numbers rather than BigInts
+        self.check_sequence(sequence as u64)?;
+        let query = self.validation.check(query, None)?;
+        let options = self.query_defaults.resolve(options)?;
+        to_js(&self.cached_page(&query, offset, page_size, &options)?)
+    }
+}
+
+impl VectorIndex {
+    pub(crate) fn set_validation_options(&mut self, options: ValidationOptions) {
+        self.validation = options;
+    }
+
+    /// Upsert a vector that already passed validation
+    pub(crate) fn store(&mut self, id: u32, vector: &[f32]) {
+        self.record_insert(id);
+        match self.positions.get(&id) {
+            Some(&slot) => {
+                if self.full_precision {
+                    self.vectors.row_mut(slot).copy_from_slice(vector);
+                }
+                self.norms[slot] = f64::NAN;
+                if let Some(codes) = self.codes.as_mut() {
+                    codes.set(slot, vector);
+                }
+            }
+            None => {
+                self.positions.insert(id, self.ids.len());
+                self.ids.push(id);
+                if self.full_precision {
+                    self.vectors.push(vector);
+                }
+                self.norms.push(f64::NAN);
+                if let Some(codes) = self.codes.as_mut() {
+                    codes.push(vector);
+                }
+            }
+        }
+
+        self.sequence += 1;
+
+        // Repeated upserts of the same ID coalesce into one pending update
+        self.pending.insert(id);
+        if self.pending.len() >= self.batch_size {
+            self.flush();
+        }
+    }
+
+    fn slot(&self, slot: usize) -> Cow<'_, [f32]> {
+        if self.full_precision {
+            return Cow::Borrowed(self.vectors.row(slot));
+        }
+
+        let codes = self
+            .codes
+            .as_ref()
+            .expect("codes are kept when originals are dropped");
+        Cow::Owned(codes.decode(slot).expect("codec supports decoding"))
+    }
+
+    /// Restore full-precision storage from the codes
+    fn materialize(&mut self) {
+        if !self.full_precision {
+            let mut vectors = Segments::new(self.dimensions);
+            for slot in 0..self.ids.len() {
+                vectors.push(&self.slot(slot));
+            }
+            self.vectors = vectors;
+            self.full_precision = true;
+        }
+    }
+
+    pub(crate) fn check_sequence(&self, token: u64) -> Result<(), VectorError> {
+        if token != self.sequence {
+            return Err(VectorError::StaleCursor {
+                token,
+                current: self.sequence,
+            });
+        }
+        Ok(())
+    }
+
+    pub(crate) fn quantize(
+        &mut self,
+        options: &QuantizationOptions,
+    ) -> Result<QuantizationStats, VectorError> {
+        options.check(self.dimensions)?;
+
+        let timer = events::start();
+        let codes = Codes::train(options, self);
+        Ok(self.install_codes(codes, options, timer))
+    }
+
+    /// Replace the codes with freshly trained ones covering every slot
+    pub(crate) fn install_codes(
+        &mut self,
+        codes: Codes,
+        options: &QuantizationOptions,
+        timer: Option<f64>,
+    ) -> QuantizationStats {
+        // Training may have used decoded vectors from a previous codec
+        self.materialize();
+        self.codes = Some(codes);
+        self.invalidate_results();
+
+        if !options.keep_originals {
+            self.vectors.clear();
+            self.full_precision = false;
+
+            // Re-ranking now scores dequantized vectors, so cached norms must
+            // describe those rather than the originals
+            for slot in 0..self.ids.len() {
+                if !self.norms[slot].is_nan() {
+                    let vector = self.slot(slot);
+                    self.norms[slot] = metric::dot(&vector, &vector).sqrt();
+                }
+            }
+        }
+
+        events::index_built(BuildKind::Quantization, self.ids.len(), timer);
+        let codes = self.codes.as_ref().expect("codes were just installed");
+        codes.stats(self.ids.len(), self.full_precision)
+    }
+
+    pub(crate) fn build_tree(&mut self, params: ClusterTreeParams) -> ClusterTreeStats {
+        let timer = events::start();
+        // Everything stored is indexed by the build itself
+        self.flush();
+        let tree = ClusterTree::build(params, self, &self.ids);
+        self.install_tree(tree, timer)
+    }
+
+    /// Replace the cluster tree with one built over every stored vector
+    pub(crate) fn install_tree(
+        &mut self,
+        tree: ClusterTree,
+        timer: Option<f64>,
+    ) -> ClusterTreeStats {
+        let stats = tree.stats();
+        self.tree = Some(tree);
+        self.invalidate_results();
+
+        log!(
+            "Built cluster tree with {} nodes over {} vectors",
+            stats.nodes,
+            self.ids.len()
+        );
+        events::index_built(BuildKind::ClusterTree, self.ids.len(), timer);
+        stats
+    }
+
+    fn scorer<'a>(&self, query: &'a [f32], options: &'a QueryOptions) -> Scorer<'a, f32> {
+        if query.len() != self.dimensions {
+            panic!("Query vector dimension mismatch");
+        }
+
+        let weights = options.weights.as_deref();
+        if let Some(weights) = weights {
+            if weights.len() != self.dimensions {
+                panic!("Weight vector dimension mismatch");
+            }
+        }
+
+        let assume_normalized = self.assume_normalized || options.assume_normalized;
+        Scorer::new(query, options.metric, weights, assume_normalized)
+            .truncated(options.search_dims)
+            .with_callback(options.custom_metric.as_ref())
+    }
+
+    /// Whether the query scores a prefix of the stored vectors
+    fn truncates(&self, options: &QueryOptions) -> bool {
+        options
+            .search_dims
+            .is_some_and(|dims| dims < self.dimensions)
+    }
+
+    /// Whether plain cosine can reuse the cached norm table
+    fn uses_cached_norms(&self, options: &QueryOptions) -> bool {
+        options.metric == MetricKind::Cosine
+            && options.weights.is_none()
+            && !self.truncates(options)
+            && !(self.assume_normalized || options.assume_normalized)
+    }
+
+    fn score_slot(&self, scorer: &Scorer<f32>, use_norms: bool, slot: usize) -> f64 {
+        let norm = self.norms[slot];
+        if use_norms && !norm.is_nan() {
+            scorer.score_with_norm(&self.slot(slot), norm)
+        } else {
+            scorer.score(&self.slot(slot))
+        }
+    }
+
+    /// Score every stored vector against the query, walking full-precision
+    /// storage one segment at a time and stopping at the first exception
+    /// from a `customMetric` callback
+    pub(crate) fn score_all(
+        &self,
+        query: &[f32],
+        options: &QueryOptions,
+    ) -> Result<Vec<f64>, VectorError> {
+        let scorer = self.scorer(query, options);
+        let use_norms = self.uses_cached_norms(options);
+        let mut scores = Vec::with_capacity(self.ids.len());
+        if !self.full_precision {
+            for slot in 0..self.ids.len() {
+                scores.push(self.score_slot(&scorer, use_norms, slot));
+                scorer.check()?;
+            }
+            return Ok(scores);
+        }
+
+        for (first, chunk) in self.vectors.chunks() {
+            for (row, vector) in chunk.chunks_exact(self.dimensions).enumerate() {
+                let norm = self.norms[first + row];
+                scores.push(if use_norms && !norm.is_nan() {
+                    scorer.score_with_norm(vector, norm)
+                } else {
+                    scorer.score(vector)
+                });
+                scorer.check()?;
+            }
+        }
+        Ok(scores)
+    }
+
+    /// Best `want` accepted `(slot, score)` pairs, best-first, counting the
+    /// work done into `scan`
+    pub(crate) fn ranked(
+        &self,
+        query: &[f32],
+        want: usize,
+        options: &QueryOptions,
+        accepts: impl Fn(usize) -> bool,
+        scan: &mut ScanCounts,
+    ) -> Result<Vec<(usize, f64)>, VectorError> {
+        let flat = |scan: &mut ScanCounts| {
+            scan.strategy = ScanStrategy::Flat;
+            if let Some(bounds) = self.prune_bounds(query, options) {
+                return self.pruned_ranked(&bounds, query, want, options, &accepts, scan);
+            }
+            scan.vectors += self.ids.len();
+            let scores = self.score_all(query, options)?;
+            Ok(select_filtered(&scores, want, options.metric, &accepts))
+        };
+
+        // Tree bounds and codes describe whole vectors
+        if options.exact || self.truncates(options) {
+            return flat(scan);
+        }
+
+        match (&self.tree, &self.codes) {
+            (Some(tree), _) => self.tree_ranked(tree, query, want, options, &accepts, scan),
+            // Codes can't estimate a custom metric, so those scan exactly
+            (None, Some(codes)) if options.metric != MetricKind::Custom => {
+                self.reranked(codes, query, want, options, &accepts, scan)
+            }
+            (None, _) => flat(scan),
+        }
+    }
+
+    /// Bounds for `prune`, when the query asked for it and it applies
+    fn prune_bounds<'a>(
+        &self,
+        query: &'a [f32],
+        options: &QueryOptions,
+    ) -> Option<PruneBounds<'a>> {
+        if !options.prune
+            || options.weights.is_some()
+            || !self.full_precision
+            || self.truncates(options)
+        {
+            return None;
+        }
+
+        let metric = match options.metric {
+            MetricKind::Cosine if self.assume_normalized || options.assume_normalized => {
+                MetricKind::Dot
+            }
+            metric => metric,
+        };
+        PruneBounds::new(query, metric)
+    }
+
+    /// Exhaustive scan that skips candidates `bounds` rules out against the
+    /// worst result held so far
+    fn pruned_ranked(
+        &self,
+        bounds: &PruneBounds,
+        query: &[f32],
+        want: usize,
+        options: &QueryOptions,
+        accepts: impl Fn(usize) -> bool,
+        scan: &mut ScanCounts,
+    ) -> Result<Vec<(usize, f64)>, VectorError> {
+        let scorer = self.scorer(query, options);
+        let use_norms = self.uses_cached_norms(options);
+        let mut top = TopK::new(want, options.metric);
+
+        for slot in 0..self.ids.len() {
+            if !accepts(slot) {
+                continue;
+            }
+
+            if let Some(threshold) = top.threshold() {
+                let vector = self.vectors.row(slot);
+                if bounds.can_skip(vector, self.norms[slot], threshold) {
+                    scan.pruned += 1;
+                    continue;
+                }
+            }
+
+            scan.vectors += 1;
+            top.push(slot, self.score_slot(&scorer, use_norms, slot));
+            scorer.check()?;
+        }
+
+        Ok(top.into_sorted())
+    }
+
+    /// Bytes read per scored item, for turning `ScanCounts` into bandwidth
+    fn scan_costs(&self, options: &QueryOptions) -> ScanCosts {
+        let code_bytes = self.codes.as_ref().map_or(0, Codes::bytes_per_vector);
+        let vector_bytes = if self.full_precision {
+            self.dimensions * std::mem::size_of::<f32>()
+        } else {
+            code_bytes
+        };
+        let norm_bytes = if self.uses_cached_norms(options) {
+            std::mem::size_of::<f64>()
+        } else {
+            0
+        };
+
+        ScanCosts {
+            vector_bytes: vector_bytes + norm_bytes,
+            code_bytes,
+            centroid_bytes: self.dimensions * std::mem::size_of::<f32>(),
+        }
+    }
+
+    /// Two-stage search: pick `rerank_k` accepted candidates from the
+    /// compressed codes, then re-score them exactly (against dequantized
+    /// vectors if the originals were dropped)
+    fn reranked(
+        &self,
+        codes: &Codes,
+        query: &[f32],
+        want: usize,
+        options: &QueryOptions,
+        accepts: impl Fn(usize) -> bool,
+        scan: &mut ScanCounts,
+    ) -> Result<Vec<(usize, f64)>, VectorError> {
+        let scorer = self.scorer(query, options);
+        let use_norms = self.uses_cached_norms(options);
+        scan.strategy = ScanStrategy::Quantized;
+        scan.codes += self.ids.len();
+
+        let rerank_k = options.rerank_k.unwrap_or(want.saturating_mul(4)).max(want);
+        let approximate = codes.score_all(query, options.metric);
+        let candidates =
+            select_expanding(&approximate, rerank_k, MetricKind::Dot, options, accepts);
+
+        let mut top = TopK::new(want, options.metric);
+        scan.vectors += candidates.len();
+        for (slot, _) in candidates {
+            top.push(slot, self.score_slot(&scorer, use_norms, slot));
+            scorer.check()?;
+        }
+        Ok(top.into_sorted())
+    }
+
+    /// Beam search over the cluster tree; the beam doubles (up to
+    /// `max_expansions` times) when filtering leaves fewer than `want` results
+    fn tree_ranked(
+        &self,
+        tree: &ClusterTree,
+        query: &[f32],
+        want: usize,
+        options: &QueryOptions,
+        accepts: impl Fn(usize) -> bool,
+        scan: &mut ScanCounts,
+    ) -> Result<Vec<(usize, f64)>, VectorError> {
+        let scorer = self.scorer(query, options);
+        let use_norms = self.uses_cached_norms(options);
+        scan.strategy = ScanStrategy::ClusterTree;
+
+        let bound_metric = match options.metric {
+            _ if options.weights.is_some() => None,
+            MetricKind::Custom => None,
+            MetricKind::Cosine if self.assume_normalized || options.assume_normalized => {
+                Some(MetricKind::Dot)
+            }
+            metric => Some(metric),
+        };
+
+        let leaves = tree.leaf_count();
+        let mut beam_width = options.beam_width.unwrap_or(tree.params.beam_width).max(1);
+        let mut expansions = 0;
+
+        loop {
+            let mut top = TopK::new(want, options.metric);
+
+            // Pending records are not in the tree yet, so scan them directly
+            for id in &self.pending {
+                let slot = self.positions[id];
+                if accepts(slot) {
+                    scan.vectors += 1;
+                    top.push(slot, self.score_slot(&scorer, use_norms, slot));
+                    scorer.check()?;
+                }
+            }
+
+            let mut visited = 0;
+            scan.centroids += tree.search(&scorer, bound_metric, beam_width, &mut top, |id| {
+                if self.pending.contains(&id) {
+                    return None;
+                }
+                let slot = *self.positions.get(&id)?;
+                // Once a callback has thrown, the rest of the tree is skipped
+                if !accepts(slot) || scorer.check().is_err() {
+                    return None;
+                }
+                visited += 1;
+                Some((slot, self.score_slot(&scorer, use_norms, slot)))
+            });
+            scan.vectors += visited;
+            scorer.check()?;
+
+            if top.len() == want || beam_width >= leaves || expansions == options.max_expansions {
+                return Ok(top.into_sorted());
+            }
+
+            beam_width = beam_width.saturating_mul(2);
+            expansions += 1;
+        }
+    }
+
+    pub(crate) fn page(
+        &self,
+        query: &[f32],
+        offset: usize,
+        page_size: usize,
+        options: &QueryOptions,
+    ) -> Result<ResultPage, VectorError> {
+        if options.groups.is_some() || options.allowed_groups.is_some() {
+            return Err(VectorError::InvalidOptions {
+                message: "groups and allowedGroups are not supported by VectorIndex".to_string(),
+            });
+        }
+        if options.parents.is_some() {
+            return Err(VectorError::InvalidOptions {
+                message: "parents are not supported by VectorIndex".to_string(),
+            });
+        }
+        if options.auto_k.is_some() && offset > 0 {
+            return Err(VectorError::InvalidOptions {
+                message: "autoK cannot be combined with paging".to_string(),
+            });
+        }
+
+        let excluded: HashSet<usize> = options
+            .exclude
+            .iter()
+            .filter_map(|id| self.positions.get(&(*id as u32)).copied())
+            .collect();
+
+        let timer = events::start();
+        events::search_started("VectorIndex", page_size, self.dimensions);
+
+        let started = options.trace.then(now_ms);
+        let mut scan = ScanCounts::default();
+
+        // One extra candidate tells us whether another page follows
+        let want = offset.saturating_add(page_size).saturating_add(1);
+        let accepts = |slot| !excluded.contains(&slot);
+        let (mut ranked, collapsed) = if options.collapse_duplicates {
+            self.collapsed_ranked(query, want, options, accepts, &mut scan)?
+        } else {
+            let ranked = self.ranked(query, want, options, accepts, &mut scan)?;
+            (ranked, HashMap::new())
+        };
+
+        let trace = started
+            .map(|started| QueryTrace::new(scan, self.scan_costs(options), now_ms() - started));
+
+        let mut has_more = ranked.len() == want;
+        ranked.truncate(want - 1);
+
+        // An automatic cutoff decides the whole result set, so nothing follows
+        if let Some(auto_k) = &options.auto_k {
+            apply_auto_k(&mut ranked, auto_k, page_size);
+            has_more = false;
+        }
+
+        let page = &ranked[offset.min(ranked.len())..];
+        let hits: Vec<IndexHit> = page
+            .iter()
+            .zip(normalized_scores(page, options))
+            .map(|(&(slot, score), normalized)| IndexHit {
+                id: self.ids[slot],
+                score: options.reported_score(score),
+                normalized,
+                collapsed: options
+                    .collapse_duplicates
+                    .then(|| collapsed.get(&slot).copied().unwrap_or(0)),
+            })
+            .collect();
+
+        events::search_completed("VectorIndex", timer, &scan, hits.len(), false);
+
+        Ok(ResultPage {
+            next_offset: has_more.then_some(offset + hits.len()),
+            hits,
+            sequence: self.sequence,
+            offset,
+            trace,
+        })
+    }
+}
+
+/// Stored vectors by slot, decoded from the codes once the originals are
+/// dropped, for builds that read them many times over
+impl Rows for VectorIndex {
+    fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    fn count(&self) -> usize {
+        self.ids.len()
+    }
+
+    fn row(&self, slot: usize) -> Cow<'_, [f32]> {
+        self.slot(slot)
+    }
+}
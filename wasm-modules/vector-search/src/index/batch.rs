@@ -0,0 +1,205 @@
+//! Batch inserts that report per-row outcomes instead of failing outright.
+//!
+//! Rows with the wrong length or non-finite values are rejected and the
+//! rest of the batch still lands. IDs already stored, or repeated within the
+//! batch, are resolved by the batch's `ConflictPolicy`.
+
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use super::VectorIndex;
+use crate::error::VectorError;
+use crate::events;
+use crate::validation::{ValidationMode, ValidationOptions, VectorProblem};
+use crate::{options_from_js, to_js};
+
+/// What `insertBatch` does with an ID that is already stored
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ConflictPolicy {
+    /// Replace the stored vector, like `insert`
+    #[default]
+    Overwrite,
+    /// Keep the stored vector and count the row as skipped
+    Skip,
+    /// Keep the stored vector and reject the row as `duplicateId`
+    Error,
+}
+
+/// Why `insertBatch` rejected a row
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum RejectReason {
+    /// The row's length differs from the index's dimensions
+    DimensionMismatch,
+    Nan,
+    Infinite,
+    ZeroNorm,
+    /// The ID exists and the policy is `error`
+    DuplicateId,
+}
+
+impl From<VectorProblem> for RejectReason {
+    fn from(problem: VectorProblem) -> Self {
+        match problem {
+            VectorProblem::Nan => RejectReason::Nan,
+            VectorProblem::Infinite => RejectReason::Infinite,
+            VectorProblem::ZeroNorm => RejectReason::ZeroNorm,
+        }
+    }
+}
+
+/// A row `insertBatch` did not store
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RejectedRow {
+    /// Position of the row in the batch
+    pub index: usize,
+    pub id: u32,
+    pub reason: RejectReason,
+    /// Offending component for non-finite values
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dimension: Option<usize>,
+}
+
+/// Outcome of `insertBatch`
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InsertBatchReport {
+    /// Rows stored under a new ID
+    pub inserted: usize,
+    /// Rows that replaced a stored vector
+    pub overwritten: usize,
+    /// Rows left out under the `skip` policy
+    pub skipped: usize,
+    pub rejected: Vec<RejectedRow>,
+}
+
+#[wasm_bindgen]
+impl VectorIndex {
+    /// Insert `count` vectors under `ids`, returning an
+    /// `InsertBatchReport`. `vectors` is either one flat `Float32Array` of
+    /// `count` rows or an array of `count` rows, in which case rows of the
+    /// wrong length are rejected individually.
+    ///
+    /// Non-finite values are always rejected, or repaired when validation is
+    /// set to `sanitize`; zero-norm rows only fail when validation is
+    /// enabled. `onConflict` (`"overwrite"` by default, `"skip"` or
+    /// `"error"`) also applies to IDs repeated within the batch. While reads
+    /// are open, stored rows are queued as with `insert`.
+    #[wasm_bindgen(js_name = "insertBatch")]
+    pub fn insert_batch(
+        &mut self,
+        ids: &[u32],
+        vectors: JsValue,
+        count: usize,
+        on_conflict: JsValue,
+    ) -> Result<JsValue, JsValue> {
+        if ids.len() != count {
+            panic!("IDs array size mismatch");
+        }
+        let policy: ConflictPolicy = options_from_js(on_conflict)?;
+        let rows = self.batch_rows(vectors, count);
+
+        to_js(&self.store_batch(ids, &rows, policy))
+    }
+}
+
+impl VectorIndex {
+    /// Split `vectors` into `count` rows, keeping their given lengths
+    fn batch_rows(&self, vectors: JsValue, count: usize) -> Vec<Vec<f32>> {
+        if let Some(flat) = vectors.dyn_ref::<js_sys::Float32Array>() {
+            if flat.length() as usize != count * self.dimensions {
+                panic!("Vectors array size mismatch");
+            }
+            return flat
+                .to_vec()
+                .chunks_exact(self.dimensions.max(1))
+                .map(<[f32]>::to_vec)
+                .collect();
+        }
+
+        let Some(rows) = vectors.dyn_ref::<js_sys::Array>() else {
+            panic!("Vectors must be a Float32Array or an array of rows");
+        };
+        if rows.length() as usize != count {
+            panic!("Vectors array size mismatch");
+        }
+        rows.iter()
+            .map(|row| js_sys::Float32Array::new(&row).to_vec())
+            .collect()
+    }
+
+    pub(crate) fn store_batch(
+        &mut self,
+        ids: &[u32],
+        rows: &[Vec<f32>],
+        policy: ConflictPolicy,
+    ) -> InsertBatchReport {
+        let validation = match self.validation.mode {
+            ValidationMode::Off => ValidationOptions {
+                mode: ValidationMode::Reject,
+                reject_zero_norm: false,
+                ..self.validation.clone()
+            },
+            _ => self.validation.clone(),
+        };
+
+        let mut report = InsertBatchReport::default();
+        let mut seen = HashSet::new();
+        for (index, (&id, row)) in ids.iter().zip(rows).enumerate() {
+            let reject = |reason, dimension| RejectedRow {
+                index,
+                id,
+                reason,
+                dimension,
+            };
+
+            if row.len() != self.dimensions {
+                report
+                    .rejected
+                    .push(reject(RejectReason::DimensionMismatch, None));
+                continue;
+            }
+
+            let vector = match validation.check(row, Some(id as usize)) {
+                Ok(vector) => vector,
+                Err(VectorError::InvalidVector {
+                    dimension, problem, ..
+                }) => {
+                    report.rejected.push(reject(problem.into(), dimension));
+                    continue;
+                }
+                Err(err) => unreachable!("validation failed with {:?}", err),
+            };
+
+            let exists = seen.contains(&id) || self.will_exist(id);
+            match policy {
+                ConflictPolicy::Skip if exists => {
+                    report.skipped += 1;
+                    continue;
+                }
+                ConflictPolicy::Error if exists => {
+                    report
+                        .rejected
+                        .push(reject(RejectReason::DuplicateId, None));
+                    continue;
+                }
+                _ if exists => report.overwritten += 1,
+                _ => report.inserted += 1,
+            }
+
+            seen.insert(id);
+            if self.readers > 0 {
+                self.defer_insert(id, &vector);
+            } else {
+                self.store(id, &vector);
+            }
+        }
+
+        events::check_memory();
+        report
+    }
+}
@@ -0,0 +1,70 @@
+//! Collapsing copies of the same content in ranked results.
+//!
+//! A document ingested twice under different IDs stores two bit-identical
+//! vectors, which then take two places in every top k they reach. With
+//! `collapseDuplicates`, candidates are grouped by a fingerprint of their
+//! stored vector; only the best-ranked member of each group is returned and
+//! the others are counted in its `collapsed` field.
+
+use std::collections::hash_map::{DefaultHasher, Entry};
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use super::VectorIndex;
+use crate::error::VectorError;
+use crate::search::QueryOptions;
+use crate::trace::ScanCounts;
+
+/// Best-first `(slot, score)` pairs with the number of copies collapsed
+/// into each, keyed by slot
+pub(crate) type Collapsed = (Vec<(usize, f64)>, HashMap<usize, usize>);
+
+impl VectorIndex {
+    /// Fingerprint of the vector stored at `slot`
+    fn fingerprint(&self, slot: usize) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for x in self.slot(slot).iter() {
+            x.to_bits().hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    /// `ranked` with duplicates collapsed onto their best-ranked copy.
+    ///
+    /// Collapsing shrinks the list, so the candidate count doubles until
+    /// `want` distinct results remain or the search runs dry. Copies the
+    /// search never reached, e.g. in an unvisited cluster, aren't counted.
+    pub(crate) fn collapsed_ranked(
+        &self,
+        query: &[f32],
+        want: usize,
+        options: &QueryOptions,
+        accepts: impl Fn(usize) -> bool,
+        scan: &mut ScanCounts,
+    ) -> Result<Collapsed, VectorError> {
+        let mut fetch = want;
+        loop {
+            let ranked = self.ranked(query, fetch, options, &accepts, scan)?;
+            let exhausted = ranked.len() < fetch;
+
+            let mut kept = Vec::with_capacity(want);
+            let mut counts = HashMap::new();
+            let mut owners: HashMap<u64, usize> = HashMap::new();
+            for (slot, score) in ranked {
+                match owners.entry(self.fingerprint(slot)) {
+                    Entry::Occupied(owner) => *counts.entry(*owner.get()).or_insert(0) += 1,
+                    Entry::Vacant(owner) => {
+                        owner.insert(slot);
+                        kept.push((slot, score));
+                    }
+                }
+            }
+
+            if kept.len() >= want || exhausted || fetch >= self.ids.len() {
+                kept.truncate(want);
+                return Ok((kept, counts));
+            }
+            fetch = fetch.saturating_mul(2);
+        }
+    }
+}
@@ -0,0 +1,240 @@
+//! Parquet export of a collection with its metadata, for loading into
+//! DuckDB, pandas or any other Arrow-aware tool.
+//!
+//! The file holds one row per stored vector, sorted by ID: a `UInt32` ID
+//! column, the vectors as a `FixedSizeList<Float32>` column, then one
+//! nullable column per entry of `columns`, typed as declared there.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use arrow_array::{
+    ArrayRef, BooleanArray, FixedSizeListArray, Float32Array, Float64Array, Int64Array,
+    RecordBatch, StringArray, UInt32Array,
+};
+use arrow_schema::{DataType, Field, Schema};
+use parquet::arrow::ArrowWriter;
+use serde::Deserialize;
+use wasm_bindgen::prelude::*;
+
+use super::VectorIndex;
+use crate::error::VectorError;
+use crate::options_from_js;
+
+/// Arrow type of an exported metadata column
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ColumnType {
+    #[default]
+    Utf8,
+    Int64,
+    Float64,
+    Boolean,
+}
+
+/// One metadata column of the exported file
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColumnSchema {
+    pub name: String,
+    #[serde(default, rename = "type")]
+    pub column_type: ColumnType,
+}
+
+/// A metadata value as it arrives from JS
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum MetadataValue {
+    Null,
+    Boolean(bool),
+    Number(f64),
+    Text(String),
+}
+
+/// Options for `exportParquet`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ParquetExportOptions {
+    pub id_column: String,
+    pub vector_column: String,
+    pub columns: Vec<ColumnSchema>,
+    /// Metadata per ID, keyed by the ID's decimal string (a plain JS object
+    /// keyed by ID); IDs or fields that are absent become nulls
+    pub metadata: HashMap<String, HashMap<String, MetadataValue>>,
+}
+
+impl Default for ParquetExportOptions {
+    fn default() -> Self {
+        Self {
+            id_column: "id".to_string(),
+            vector_column: "vector".to_string(),
+            columns: Vec::new(),
+            metadata: HashMap::new(),
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl VectorIndex {
+    /// Serialize every stored vector and its metadata as a Parquet file.
+    ///
+    /// Fails with `InvalidOptions` when a metadata value does not match its
+    /// column's type, and with `ExportFailed` if the writer itself fails.
+    #[wasm_bindgen(js_name = "exportParquet")]
+    pub fn export_parquet(&self, options: JsValue) -> Result<Vec<u8>, JsValue> {
+        let options: ParquetExportOptions = options_from_js(options)?;
+        Ok(self.parquet_bytes(&options)?)
+    }
+}
+
+fn invalid(message: String) -> VectorError {
+    VectorError::InvalidOptions { message }
+}
+
+fn export_failed(err: impl std::fmt::Display) -> VectorError {
+    VectorError::ExportFailed {
+        message: err.to_string(),
+    }
+}
+
+/// Build one metadata column from the rows' values
+fn metadata_column(
+    column: &ColumnSchema,
+    ids: &[u32],
+    metadata: &HashMap<String, HashMap<String, MetadataValue>>,
+) -> Result<ArrayRef, VectorError> {
+    let values = ids.iter().map(|id| {
+        let value = metadata
+            .get(&id.to_string())
+            .and_then(|row| row.get(&column.name));
+        (id, value)
+    });
+    let mismatch = |id: &u32| {
+        invalid(format!(
+            "column {:?} expects {:?} values, but ID {} has another type",
+            column.name, column.column_type, id
+        ))
+    };
+
+    let array: ArrayRef = match column.column_type {
+        ColumnType::Utf8 => Arc::new(StringArray::from(
+            values
+                .map(|(id, value)| match value {
+                    None | Some(MetadataValue::Null) => Ok(None),
+                    Some(MetadataValue::Text(text)) => Ok(Some(text.as_str())),
+                    Some(_) => Err(mismatch(id)),
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+        )),
+        ColumnType::Int64 => Arc::new(Int64Array::from(
+            values
+                .map(|(id, value)| match value {
+                    None | Some(MetadataValue::Null) => Ok(None),
+                    Some(MetadataValue::Number(n)) if n.fract() == 0.0 => Ok(Some(*n as i64)),
+                    Some(_) => Err(mismatch(id)),
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+        )),
+        ColumnType::Float64 => Arc::new(Float64Array::from(
+            values
+                .map(|(id, value)| match value {
+                    None | Some(MetadataValue::Null) => Ok(None),
+                    Some(MetadataValue::Number(n)) => Ok(Some(*n)),
+                    Some(_) => Err(mismatch(id)),
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+        )),
+        ColumnType::Boolean => Arc::new(BooleanArray::from(
+            values
+                .map(|(id, value)| match value {
+                    None | Some(MetadataValue::Null) => Ok(None),
+                    Some(MetadataValue::Boolean(b)) => Ok(Some(*b)),
+                    Some(_) => Err(mismatch(id)),
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+        )),
+    };
+    Ok(array)
+}
+
+fn data_type(column_type: ColumnType) -> DataType {
+    match column_type {
+        ColumnType::Utf8 => DataType::Utf8,
+        ColumnType::Int64 => DataType::Int64,
+        ColumnType::Float64 => DataType::Float64,
+        ColumnType::Boolean => DataType::Boolean,
+    }
+}
+
+impl VectorIndex {
+    pub(crate) fn parquet_bytes(
+        &self,
+        options: &ParquetExportOptions,
+    ) -> Result<Vec<u8>, VectorError> {
+        let mut names = HashSet::new();
+        let metadata_names = options.columns.iter().map(|column| &column.name);
+        for name in [&options.id_column, &options.vector_column]
+            .into_iter()
+            .chain(metadata_names)
+        {
+            if !names.insert(name) {
+                return Err(invalid(format!("duplicate column {:?}", name)));
+            }
+        }
+
+        let mut order: Vec<usize> = (0..self.ids.len()).collect();
+        order.sort_unstable_by_key(|&slot| self.ids[slot]);
+        let ids: Vec<u32> = order.iter().map(|&slot| self.ids[slot]).collect();
+
+        let mut values = Vec::with_capacity(order.len() * self.dimensions);
+        for &slot in &order {
+            values.extend_from_slice(&self.slot(slot));
+        }
+
+        let dimensions = i32::try_from(self.dimensions).map_err(export_failed)?;
+        let item = Arc::new(Field::new("item", DataType::Float32, false));
+        let vectors = FixedSizeListArray::try_new(
+            item.clone(),
+            dimensions,
+            Arc::new(Float32Array::from(values)),
+            None,
+        )
+        .map_err(export_failed)?;
+
+        let mut fields = vec![
+            Field::new(&options.id_column, DataType::UInt32, false),
+            Field::new(
+                &options.vector_column,
+                DataType::FixedSizeList(item, dimensions),
+                false,
+            ),
+        ];
+        let mut columns: Vec<ArrayRef> =
+            vec![Arc::new(UInt32Array::from(ids.clone())), Arc::new(vectors)];
+        for column in &options.columns {
+            fields.push(Field::new(
+                &column.name,
+                data_type(column.column_type),
+                true,
+            ));
+            columns.push(metadata_column(column, &ids, &options.metadata)?);
+        }
+
+        let batch =
+            RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).map_err(export_failed)?;
+
+        let mut bytes = Vec::new();
+        let mut writer =
+            ArrowWriter::try_new(&mut bytes, batch.schema(), None).map_err(export_failed)?;
+        writer.write(&batch).map_err(export_failed)?;
+        writer.close().map_err(export_failed)?;
+
+        log!(
+            "Exported {} vectors and {} metadata columns as {} bytes of Parquet",
+            ids.len(),
+            options.columns.len(),
+            bytes.len()
+        );
+        Ok(bytes)
+    }
+}
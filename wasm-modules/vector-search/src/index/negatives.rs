@@ -0,0 +1,150 @@
+//! Negative sampling for exporting contrastive training data.
+//!
+//! Every strategy samples without replacement and never returns the
+//! positive itself, and the same seed always yields the same IDs for the
+//! same index contents.
+
+use std::collections::HashSet;
+
+use serde::Deserialize;
+use wasm_bindgen::prelude::*;
+
+use super::VectorIndex;
+use crate::metric::MetricKind;
+use crate::options_from_js;
+use crate::rng::SplitMix64;
+use crate::search::QueryOptions;
+use crate::trace::ScanCounts;
+
+/// How negatives are chosen: `"random"`, `{ hard: { skipTop, poolSize } }`
+/// or `{ inBatch: [ids] }`. Defaults to random.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum NegativeStrategy {
+    /// Any stored ID
+    #[default]
+    Random,
+    /// IDs ranked just below the positive's nearest neighbours: the closest
+    /// `skip_top` are passed over, since they are often unlabeled
+    /// positives, and negatives are drawn from the next `pool_size`
+    #[serde(rename_all = "camelCase")]
+    Hard {
+        #[serde(default = "default_skip_top")]
+        skip_top: usize,
+        #[serde(default = "default_pool_size")]
+        pool_size: usize,
+        #[serde(default)]
+        metric: MetricKind,
+    },
+    /// Other IDs from the same training batch; IDs that aren't stored are
+    /// ignored
+    InBatch(Vec<u32>),
+}
+
+fn default_skip_top() -> usize {
+    5
+}
+
+fn default_pool_size() -> usize {
+    50
+}
+
+#[wasm_bindgen]
+impl VectorIndex {
+    /// Sample up to `n` negative IDs for `positiveId`.
+    ///
+    /// Returns fewer than `n` when the strategy has fewer candidates.
+    /// Seeds cross the boundary as plain numbers and are truncated to
+    /// integers.
+    #[wasm_bindgen(js_name = "sampleNegatives")]
+    pub fn sample_negatives(
+        &self,
+        positive_id: u32,
+        strategy: JsValue,
+        n: usize,
+        seed: f64,
+    ) -> Result<Vec<u32>, JsValue> {
+        let strategy: NegativeStrategy = options_from_js(strategy)?;
+        Ok(self.negatives(positive_id, &strategy, n, seed as u64))
+    }
+}
+
+impl VectorIndex {
+    pub(crate) fn negatives(
+        &self,
+        positive_id: u32,
+        strategy: &NegativeStrategy,
+        n: usize,
+        seed: u64,
+    ) -> Vec<u32> {
+        let mut candidates: Vec<u32> = match strategy {
+            NegativeStrategy::Random => self
+                .ids
+                .iter()
+                .copied()
+                .filter(|&id| id != positive_id)
+                .collect(),
+            NegativeStrategy::Hard {
+                skip_top,
+                pool_size,
+                metric,
+            } => self.hard_candidates(positive_id, *skip_top, *pool_size, *metric),
+            NegativeStrategy::InBatch(batch) => {
+                let mut seen = HashSet::new();
+                batch
+                    .iter()
+                    .copied()
+                    .filter(|&id| id != positive_id && self.contains(id) && seen.insert(id))
+                    .collect()
+            }
+        };
+
+        // Partial Fisher-Yates: the first `n` entries become the sample
+        let n = n.min(candidates.len());
+        let mut rng = SplitMix64::new(seed);
+        for i in 0..n {
+            let j = i + rng.next_below(candidates.len() - i);
+            candidates.swap(i, j);
+        }
+        candidates.truncate(n);
+        candidates
+    }
+
+    /// IDs ranked `skip_top..skip_top + pool_size` by similarity to the
+    /// positive, best first
+    fn hard_candidates(
+        &self,
+        positive_id: u32,
+        skip_top: usize,
+        pool_size: usize,
+        metric: MetricKind,
+    ) -> Vec<u32> {
+        let Some(&positive) = self.positions.get(&positive_id) else {
+            panic!("Positive ID not found");
+        };
+        if metric == MetricKind::Custom {
+            panic!("Hard negatives need a built-in metric");
+        }
+
+        let options = QueryOptions {
+            metric,
+            exact: true,
+            ..QueryOptions::default()
+        };
+        let query = self.slot(positive).into_owned();
+        let ranked = self.ranked(
+            &query,
+            skip_top.saturating_add(pool_size),
+            &options,
+            |slot| slot != positive,
+            &mut ScanCounts::default(),
+        );
+        let ranked = ranked.expect("built-in metrics never call back");
+
+        ranked
+            .into_iter()
+            .skip(skip_top)
+            .map(|(slot, _)| self.ids[slot])
+            .collect()
+    }
+}
@@ -0,0 +1,117 @@
+//! Read sessions that give async JS code a consistent view of an index.
+//!
+//! A handle shared between async tasks can have inserts from one task land
+//! between another task's page fetches or related queries. Between
+//! `beginRead()` and the matching `endRead()`, the stored vectors stay
+//! frozen: inserts and removes are validated and queued, then applied in
+//! order when the last open read ends. Rebuilding acceleration structures
+//! or rolling back would change results mid-read, so those fail with
+//! `ReadInProgress` instead.
+//!
+//! Reads nest and may overlap; the view is released only when every
+//! `beginRead()` has been matched.
+
+use wasm_bindgen::prelude::*;
+
+use super::VectorIndex;
+use crate::error::VectorError;
+
+/// A write queued while reads were open
+#[derive(Clone, Debug)]
+pub(crate) enum DeferredWrite {
+    Insert(u32, Vec<f32>),
+    Remove(u32),
+}
+
+#[wasm_bindgen]
+impl VectorIndex {
+    /// Open a read, freezing the stored vectors until the matching
+    /// `endRead()`. Returns the collection sequence the read observes, which
+    /// stays valid for `searchPage` for the whole read.
+    #[wasm_bindgen(js_name = "beginRead")]
+    pub fn begin_read(&mut self) -> f64 {
+        self.readers += 1;
+        // Sequences cross the boundary as plain numbers rather than BigInts
+        self.sequence as f64
+    }
+
+    /// Close a read opened with `beginRead()`. When it was the last one, the
+    /// writes queued meanwhile are applied; returns how many were.
+    #[wasm_bindgen(js_name = "endRead")]
+    pub fn end_read(&mut self) -> usize {
+        if self.readers == 0 {
+            panic!("endRead called without a matching beginRead");
+        }
+
+        self.readers -= 1;
+        if self.readers > 0 {
+            return 0;
+        }
+
+        let deferred = std::mem::take(&mut self.deferred);
+        let applied = deferred.len();
+        for write in deferred {
+            match write {
+                DeferredWrite::Insert(id, vector) => self.store(id, &vector),
+                DeferredWrite::Remove(id) => {
+                    self.remove(id);
+                }
+            }
+        }
+
+        if applied > 0 {
+            log!("Applied {} writes deferred by open reads", applied);
+        }
+        applied
+    }
+
+    /// Number of reads currently open
+    #[wasm_bindgen(getter, js_name = "openReads")]
+    pub fn open_reads(&self) -> usize {
+        self.readers
+    }
+
+    /// Number of inserts and removes waiting for open reads to end
+    #[wasm_bindgen(getter, js_name = "deferredWrites")]
+    pub fn deferred_writes(&self) -> usize {
+        self.deferred.len()
+    }
+}
+
+impl VectorIndex {
+    /// Fail when an operation would change results under an open read
+    pub(crate) fn check_no_readers(&self) -> Result<(), VectorError> {
+        match self.readers {
+            0 => Ok(()),
+            readers => Err(VectorError::ReadInProgress { readers }),
+        }
+    }
+
+    /// Queue an insert of an already validated vector
+    pub(super) fn defer_insert(&mut self, id: u32, vector: &[f32]) {
+        self.deferred
+            .push(DeferredWrite::Insert(id, vector.to_vec()));
+    }
+
+    /// Whether `id` will exist once the queued writes apply
+    pub(super) fn will_exist(&self, id: u32) -> bool {
+        self.deferred
+            .iter()
+            .rev()
+            .find_map(|write| match write {
+                DeferredWrite::Insert(queued, _) if *queued == id => Some(true),
+                DeferredWrite::Remove(queued) if *queued == id => Some(false),
+                _ => None,
+            })
+            .unwrap_or_else(|| self.positions.contains_key(&id))
+    }
+
+    /// Queue a remove, returning whether `id` will exist when it applies
+    pub(super) fn defer_remove(&mut self, id: u32) -> bool {
+        let exists = self.will_exist(id);
+        if exists {
+            self.deferred.push(DeferredWrite::Remove(id));
+        }
+        exists
+    }
+}
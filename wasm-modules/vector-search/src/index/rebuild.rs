@@ -0,0 +1,430 @@
+//! Planned migrations between index configurations.
+//!
+//! `planRebuild` compares the target configuration with the collection,
+//! estimates what moving to it costs, and queues the work as steps:
+//!
+//! - `train`: learn the codec's parameters from the stored vectors
+//! - `encode`: encode every stored vector, a slice at a time
+//! - `build`: build the cluster tree
+//! - `swap`: install the new structures and drop the ones the target
+//!   doesn't have
+//!
+//! `maintenanceTick` then advances the plan within a time budget, so a
+//! migration can run between frames or during idle callbacks. Queries keep
+//! using the current structures until `swap`. A write between ticks makes
+//! the staged work stale, so the plan restarts from its first step.
+//!
+//! `rebuild` runs a whole plan in one call instead, reporting progress to a
+//! callback and comparing the index before and after, for trying out
+//! parameters without re-ingesting the vectors from JS.
+
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use super::VectorIndex;
+use crate::cluster_tree::{ClusterTree, ClusterTreeParams, ClusterTreeStats};
+use crate::error::VectorError;
+use crate::events;
+use crate::quantization::{Codes, QuantizationOptions, QuantizationStats};
+use crate::trace::now_ms;
+use crate::{options_from_js, to_js};
+
+/// Vectors encoded between checks of the tick's time budget
+const ENCODE_SLICE: usize = 1024;
+
+/// Assumed throughput used to turn bytes of work into time estimates, in
+/// bytes per millisecond. A rough figure for scalar WASM; actual speed
+/// depends on the device.
+const ESTIMATED_BYTES_PER_MS: f64 = 1e6;
+
+/// Index configuration a rebuild migrates to. Structures left unset are
+/// dropped.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RebuildTarget {
+    /// Cluster tree to build, or none for exhaustive scans
+    pub cluster_tree: Option<ClusterTreeParams>,
+    /// Codec to train, or none to search full-precision vectors only
+    pub quantization: Option<QuantizationOptions>,
+}
+
+/// One stage of a rebuild
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum RebuildStepKind {
+    Train,
+    Encode,
+    Build,
+    Swap,
+}
+
+/// A step of a `RebuildPlan`
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RebuildStep {
+    pub kind: RebuildStepKind,
+    pub estimated_ms: f64,
+}
+
+/// Steps and estimated cost of migrating to a `RebuildTarget`
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RebuildPlan {
+    pub steps: Vec<RebuildStep>,
+    pub vectors: usize,
+    /// Sum of the steps' estimates
+    pub estimated_ms: f64,
+    /// Most memory held at once by staged structures and training copies,
+    /// on top of the current index
+    pub peak_extra_bytes: usize,
+    /// Estimated size of the vectors, codes and tree once swapped in
+    pub final_bytes: usize,
+}
+
+/// Where a rebuild stands after `maintenanceTick`
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MaintenanceProgress {
+    /// Step to run next, absent once the plan is done or when none is queued
+    pub step: Option<RebuildStepKind>,
+    pub steps_done: usize,
+    pub steps_total: usize,
+    /// Vectors encoded so far by the `encode` step
+    pub encoded: usize,
+    pub vectors: usize,
+    /// Whether a write since the last tick discarded the staged work
+    pub restarted: bool,
+    pub done: bool,
+}
+
+/// Search structures and memory of an index at one point in time
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexShape {
+    pub vectors: usize,
+    /// Bytes allocated for full-precision vectors
+    pub storage_bytes: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cluster_tree: Option<ClusterTreeStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quantization: Option<QuantizationStats>,
+}
+
+/// Outcome of `rebuild`
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RebuildReport {
+    pub before: IndexShape,
+    pub after: IndexShape,
+    pub elapsed_ms: f64,
+}
+
+/// A queued rebuild and its staged structures
+#[derive(Debug)]
+pub(crate) struct Rebuild {
+    target: RebuildTarget,
+    steps: Vec<RebuildStepKind>,
+    next: usize,
+    /// Collection sequence the staged work was computed against
+    sequence: u64,
+    codes: Option<Codes>,
+    encoded: usize,
+    tree: Option<ClusterTree>,
+}
+
+impl Rebuild {
+    fn new(target: RebuildTarget, sequence: u64) -> Self {
+        let mut steps = Vec::new();
+        if target.quantization.is_some() {
+            steps.extend([RebuildStepKind::Train, RebuildStepKind::Encode]);
+        }
+        if target.cluster_tree.is_some() {
+            steps.push(RebuildStepKind::Build);
+        }
+        steps.push(RebuildStepKind::Swap);
+
+        Self {
+            target,
+            steps,
+            next: 0,
+            sequence,
+            codes: None,
+            encoded: 0,
+            tree: None,
+        }
+    }
+
+    fn restart(&mut self, sequence: u64) {
+        self.next = 0;
+        self.sequence = sequence;
+        self.codes = None;
+        self.encoded = 0;
+        self.tree = None;
+    }
+}
+
+#[wasm_bindgen]
+impl VectorIndex {
+    /// Plan a migration to `target` (`{ clusterTree, quantization }`, each
+    /// taking the options of `buildClusterTree` / `enableQuantization`, or
+    /// left out to drop that structure), returning a `RebuildPlan`. The plan
+    /// replaces any queued one and runs through `maintenanceTick`.
+    #[wasm_bindgen(js_name = "planRebuild")]
+    pub fn plan_rebuild(&mut self, target: JsValue) -> Result<JsValue, JsValue> {
+        let target: RebuildTarget = options_from_js(target)?;
+        if let Some(options) = &target.quantization {
+            options.check(self.dimensions)?;
+        }
+
+        let rebuild = Rebuild::new(target, self.sequence);
+        let plan = self.rebuild_plan(&rebuild);
+        self.rebuild = Some(rebuild);
+        to_js(&plan)
+    }
+
+    /// Advance the queued rebuild for up to `budgetMs` milliseconds,
+    /// returning `MaintenanceProgress`. Each tick makes some progress even
+    /// on a tiny budget; `build` and `swap` run whole. Fails with
+    /// `ReadInProgress` when the swap is due while reads are open.
+    #[wasm_bindgen(js_name = "maintenanceTick")]
+    pub fn maintenance_tick(&mut self, budget_ms: f64) -> Result<JsValue, JsValue> {
+        to_js(&self.advance_rebuild(budget_ms)?)
+    }
+
+    /// Rebuild the cluster tree and codes for `target` (as for
+    /// `planRebuild`) from the vectors already stored, running every step
+    /// now. `onProgress`, if given, receives a `MaintenanceProgress` after
+    /// each step and each slice of encoding. Returns a `RebuildReport`
+    /// comparing the index before and after. Replaces any queued plan, and
+    /// fails with `ReadInProgress` while reads are open.
+    pub fn rebuild(
+        &mut self,
+        target: JsValue,
+        on_progress: Option<js_sys::Function>,
+    ) -> Result<JsValue, JsValue> {
+        self.check_no_readers()?;
+        let target: RebuildTarget = options_from_js(target)?;
+        if let Some(options) = &target.quantization {
+            options.check(self.dimensions)?;
+        }
+
+        let started = now_ms();
+        let before = self.shape();
+        self.rebuild = Some(Rebuild::new(target, self.sequence));
+        loop {
+            let progress = self.advance_rebuild(0.0)?;
+            if let Some(callback) = &on_progress {
+                if let Err(err) = callback.call1(&JsValue::NULL, &to_js(&progress)?) {
+                    self.rebuild = None;
+                    return Err(err);
+                }
+            }
+            if progress.done {
+                break;
+            }
+        }
+
+        let report = RebuildReport {
+            before,
+            after: self.shape(),
+            elapsed_ms: now_ms() - started,
+        };
+        log!(
+            "Rebuilt index over {} vectors in {:.1}ms",
+            self.ids.len(),
+            report.elapsed_ms
+        );
+        to_js(&report)
+    }
+
+    /// Drop the queued rebuild and its staged work, returning whether one
+    /// was queued
+    #[wasm_bindgen(js_name = "cancelRebuild")]
+    pub fn cancel_rebuild(&mut self) -> bool {
+        self.rebuild.take().is_some()
+    }
+
+    /// Whether a planned rebuild is waiting for `maintenanceTick`
+    #[wasm_bindgen(getter, js_name = "rebuildPending")]
+    pub fn rebuild_pending(&self) -> bool {
+        self.rebuild.is_some()
+    }
+}
+
+impl VectorIndex {
+    pub(crate) fn shape(&self) -> IndexShape {
+        IndexShape {
+            vectors: self.ids.len(),
+            storage_bytes: self.vectors.allocated_bytes(),
+            cluster_tree: self.tree.as_ref().map(ClusterTree::stats),
+            quantization: self
+                .codes
+                .as_ref()
+                .map(|codes| codes.stats(self.ids.len(), self.full_precision)),
+        }
+    }
+
+    fn rebuild_plan(&self, rebuild: &Rebuild) -> RebuildPlan {
+        let vectors = self.ids.len();
+        let vector_bytes = vectors * self.dimensions * std::mem::size_of::<f32>();
+        let code_bytes = rebuild.target.quantization.as_ref().map_or(0, |options| {
+            vectors * options.bytes_per_vector(self.dimensions)
+        });
+        let keep_originals = rebuild
+            .target
+            .quantization
+            .as_ref()
+            .is_none_or(|options| options.keep_originals);
+
+        // Every node stores a centroid; leaves of roughly `leaf_size`
+        // members and their ancestors come to about twice the leaf count
+        let (tree_bytes, tree_work) = rebuild.target.cluster_tree.as_ref().map_or((0, 0), |p| {
+            let leaves = vectors.div_ceil(p.leaf_size.max(1));
+            let nodes = 2 * leaves.max(1);
+            let depth = (leaves.max(1) as f64).log(p.branching.max(2) as f64).ceil() as usize;
+            let bytes = nodes * self.dimensions * std::mem::size_of::<f32>()
+                + vectors * std::mem::size_of::<u32>();
+            (
+                bytes,
+                vector_bytes * p.iterations * p.branching * depth.max(1),
+            )
+        });
+
+        // Builds read rows where they are stored, but swapping codes in over
+        // code-only storage restores the originals first
+        let copy_bytes = if self.full_precision { 0 } else { vector_bytes };
+        let estimate = |bytes: usize| bytes as f64 / ESTIMATED_BYTES_PER_MS;
+        let steps: Vec<RebuildStep> = rebuild
+            .steps
+            .iter()
+            .map(|&kind| RebuildStep {
+                kind,
+                estimated_ms: match kind {
+                    RebuildStepKind::Train => estimate(vector_bytes),
+                    RebuildStepKind::Encode => estimate(vector_bytes + code_bytes),
+                    RebuildStepKind::Build => estimate(tree_work),
+                    // Dropping or restoring full-precision storage copies it
+                    RebuildStepKind::Swap if keep_originals != self.full_precision => {
+                        estimate(vector_bytes)
+                    }
+                    RebuildStepKind::Swap => 0.0,
+                },
+            })
+            .collect();
+
+        RebuildPlan {
+            estimated_ms: steps.iter().map(|step| step.estimated_ms).sum(),
+            steps,
+            vectors,
+            peak_extra_bytes: copy_bytes + code_bytes + tree_bytes,
+            final_bytes: if keep_originals { vector_bytes } else { 0 } + code_bytes + tree_bytes,
+        }
+    }
+
+    pub(crate) fn advance_rebuild(
+        &mut self,
+        budget_ms: f64,
+    ) -> Result<MaintenanceProgress, VectorError> {
+        let vectors = self.ids.len();
+        let Some(mut rebuild) = self.rebuild.take() else {
+            return Ok(MaintenanceProgress {
+                step: None,
+                steps_done: 0,
+                steps_total: 0,
+                encoded: 0,
+                vectors,
+                restarted: false,
+                done: true,
+            });
+        };
+
+        let restarted = rebuild.sequence != self.sequence;
+        if restarted {
+            rebuild.restart(self.sequence);
+        }
+
+        let deadline = now_ms() + budget_ms.max(0.0);
+        let result = self.run_rebuild_steps(&mut rebuild, deadline);
+        let progress = MaintenanceProgress {
+            step: rebuild.steps.get(rebuild.next).copied(),
+            steps_done: rebuild.next,
+            steps_total: rebuild.steps.len(),
+            encoded: rebuild.encoded,
+            vectors,
+            restarted,
+            done: rebuild.next == rebuild.steps.len(),
+        };
+
+        if !progress.done {
+            self.rebuild = Some(rebuild);
+        }
+        result.map(|()| progress)
+    }
+
+    /// Run steps until the plan is done or `deadline` passes, always
+    /// finishing at least one unit of work
+    fn run_rebuild_steps(
+        &mut self,
+        rebuild: &mut Rebuild,
+        deadline: f64,
+    ) -> Result<(), VectorError> {
+        let mut first = true;
+        while let Some(&kind) = rebuild.steps.get(rebuild.next) {
+            if !first && now_ms() >= deadline {
+                break;
+            }
+            first = false;
+
+            match kind {
+                RebuildStepKind::Train => {
+                    let options = rebuild.target.quantization.as_ref().expect("planned codec");
+                    rebuild.codes = Some(Codes::fit(options, self));
+                }
+                RebuildStepKind::Encode => {
+                    let codes = rebuild.codes.as_mut().expect("codec trained");
+                    let end = (rebuild.encoded + ENCODE_SLICE).min(self.ids.len());
+                    for slot in rebuild.encoded..end {
+                        codes.push(&self.slot(slot));
+                    }
+                    rebuild.encoded = end;
+                    if end < self.ids.len() {
+                        continue;
+                    }
+                }
+                RebuildStepKind::Build => {
+                    let params = rebuild.target.cluster_tree.clone().expect("planned tree");
+                    self.flush();
+                    rebuild.tree = Some(ClusterTree::build(params, self, &self.ids));
+                }
+                RebuildStepKind::Swap => {
+                    self.check_no_readers()?;
+                    self.swap_rebuilt(rebuild);
+                }
+            }
+            rebuild.next += 1;
+        }
+        Ok(())
+    }
+
+    fn swap_rebuilt(&mut self, rebuild: &mut Rebuild) {
+        match (rebuild.codes.take(), &rebuild.target.quantization) {
+            (Some(codes), Some(options)) => {
+                self.install_codes(codes, options, events::start());
+            }
+            _ => {
+                self.materialize();
+                self.codes = None;
+            }
+        }
+
+        match rebuild.tree.take() {
+            Some(tree) => {
+                self.install_tree(tree, events::start());
+            }
+            None => self.tree = None,
+        }
+
+        self.invalidate_results();
+        log!("Swapped in rebuilt index over {} vectors", self.ids.len());
+    }
+}
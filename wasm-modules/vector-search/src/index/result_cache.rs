@@ -0,0 +1,221 @@
+//! Result-page cache for repeated identical queries, e.g. dashboard widgets
+//! re-issuing the same filtered search.
+//!
+//! Entries are keyed on a digest of the query vector, a digest of the
+//! filter (`exclude`), a digest of the remaining result-shaping options, the
+//! page bounds and the collection sequence. Any mutation bumps the sequence
+//! and empties the cache on the next lookup, and rebuilding acceleration
+//! structures empties it directly, so a hit is always what a fresh scan
+//! would return.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use super::{ResultPage, VectorIndex};
+use crate::error::VectorError;
+use crate::events;
+use crate::search::QueryOptions;
+use crate::to_js;
+use crate::trace::ScanCounts;
+
+/// Hit and occupancy counters for the result cache
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResultCacheStats {
+    pub capacity: usize,
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+    /// `hits / (hits + misses)`, or 0 before any cacheable query
+    pub hit_rate: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct CacheKey {
+    query: u64,
+    filter: u64,
+    options: u64,
+    offset: usize,
+    page_size: usize,
+}
+
+impl CacheKey {
+    fn new(query: &[f32], offset: usize, page_size: usize, options: &QueryOptions) -> Self {
+        let mut hasher = DefaultHasher::new();
+        for x in query {
+            x.to_bits().hash(&mut hasher);
+        }
+        let query = hasher.finish();
+
+        // Exclusion order doesn't change the results
+        let mut exclude = options.exclude.clone();
+        exclude.sort_unstable();
+        exclude.dedup();
+        let mut hasher = DefaultHasher::new();
+        exclude.hash(&mut hasher);
+        let filter = hasher.finish();
+
+        // The option types hold floats and so aren't `Hash`; their `Debug`
+        // output is exact, which makes it a faithful fingerprint. `prune`
+        // and `trace` never change the hits.
+        let shaping = QueryOptions {
+            exclude: Vec::new(),
+            prune: false,
+            trace: false,
+            ..options.clone()
+        };
+        let mut hasher = DefaultHasher::new();
+        format!("{:?}", shaping).hash(&mut hasher);
+        let options = hasher.finish();
+
+        Self {
+            query,
+            filter,
+            options,
+            offset,
+            page_size,
+        }
+    }
+}
+
+/// Least-recently-used cache of result pages for one collection sequence
+#[derive(Debug, Default)]
+pub(crate) struct ResultCache {
+    capacity: usize,
+    /// Sequence the cached pages were computed against
+    sequence: u64,
+    /// Pages with the tick of their last use
+    entries: HashMap<CacheKey, (ResultPage, u64)>,
+    tick: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl ResultCache {
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    fn get(&mut self, key: &CacheKey, sequence: u64) -> Option<ResultPage> {
+        if sequence != self.sequence {
+            self.entries.clear();
+            self.sequence = sequence;
+        }
+
+        self.tick += 1;
+        match self.entries.get_mut(key) {
+            Some((page, used)) => {
+                *used = self.tick;
+                self.hits += 1;
+                Some(page.clone())
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    fn put(&mut self, key: CacheKey, page: ResultPage) {
+        self.entries.insert(key, (page, self.tick));
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        while self.entries.len() > self.capacity {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(key, _)| *key)
+                .expect("cache over capacity is not empty");
+            self.entries.remove(&oldest);
+        }
+    }
+
+    fn stats(&self) -> ResultCacheStats {
+        let lookups = self.hits + self.misses;
+        ResultCacheStats {
+            capacity: self.capacity,
+            entries: self.entries.len(),
+            hits: self.hits,
+            misses: self.misses,
+            hit_rate: if lookups == 0 {
+                0.0
+            } else {
+                self.hits as f64 / lookups as f64
+            },
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl VectorIndex {
+    /// Cache up to `capacity` result pages from `search` and `searchPage`,
+    /// evicting the least recently used.
+    ///
+    /// Queries with `trace` or a `customMetric` callback always scan.
+    #[wasm_bindgen(js_name = "enableResultCache")]
+    pub fn enable_result_cache(&mut self, capacity: usize) {
+        let cache = self.result_cache.get_mut();
+        cache.capacity = capacity;
+        cache.evict();
+    }
+
+    /// Stop caching and drop every cached page
+    #[wasm_bindgen(js_name = "disableResultCache")]
+    pub fn disable_result_cache(&mut self) {
+        let cache = self.result_cache.get_mut();
+        cache.capacity = 0;
+        cache.clear();
+    }
+
+    /// Hit and occupancy counters as a `ResultCacheStats` object
+    #[wasm_bindgen(js_name = "resultCacheStats")]
+    pub fn result_cache_stats(&self) -> Result<JsValue, JsValue> {
+        to_js(&self.result_cache.borrow().stats())
+    }
+}
+
+impl VectorIndex {
+    /// `page`, answered from the result cache when possible
+    pub(crate) fn cached_page(
+        &self,
+        query: &[f32],
+        offset: usize,
+        page_size: usize,
+        options: &QueryOptions,
+    ) -> Result<ResultPage, VectorError> {
+        let cacheable = self.result_cache.borrow().capacity > 0
+            && !options.trace
+            && options.custom_metric.is_none();
+        if !cacheable {
+            return self.page(query, offset, page_size, options);
+        }
+
+        let timer = events::start();
+        let key = CacheKey::new(query, offset, page_size, options);
+        // Released before any listener runs, in case it queries again
+        let hit = self.result_cache.borrow_mut().get(&key, self.sequence);
+        if let Some(page) = hit {
+            events::search_started("VectorIndex", page_size, self.dimensions);
+            let scan = ScanCounts::default();
+            events::search_completed("VectorIndex", timer, &scan, page.hits.len(), true);
+            return Ok(page);
+        }
+
+        let page = self.page(query, offset, page_size, options)?;
+        self.result_cache.borrow_mut().put(key, page.clone());
+        Ok(page)
+    }
+
+    /// Drop cached pages after a change that can alter results without
+    /// bumping the sequence, such as rebuilding the cluster tree
+    pub(crate) fn invalidate_results(&mut self) {
+        self.result_cache.get_mut().clear();
+    }
+}
@@ -0,0 +1,117 @@
+//! Server-side result sets paged out on demand.
+//!
+//! A query with a very large `k` would otherwise copy every hit across the
+//! boundary at once. `openResultSet` ranks once and keeps the hits in WASM
+//! behind a numeric handle; `nextPage` then hands them out in order. The
+//! ranking is fixed when the set is opened, so later writes never shift
+//! pages, but hits may name IDs removed since; compare the page's
+//! `sequence` with the index's to tell.
+//!
+//! A set is released once its last page has been served, or early with
+//! `closeResultSet`.
+
+use std::collections::HashMap;
+
+use wasm_bindgen::prelude::*;
+
+use super::{IndexHit, ResultPage, VectorIndex};
+use crate::error::VectorError;
+use crate::to_js;
+use crate::trace::QueryTrace;
+
+/// Open result sets by handle
+#[derive(Debug, Default)]
+pub(crate) struct ResultSets {
+    next_handle: u32,
+    open: HashMap<u32, ResultSet>,
+}
+
+#[derive(Debug)]
+struct ResultSet {
+    hits: Vec<IndexHit>,
+    sequence: u64,
+    /// Hits already handed out
+    served: usize,
+    /// Trace of the ranking query, reported with the first page
+    trace: Option<QueryTrace>,
+}
+
+impl ResultSets {
+    fn open(&mut self, page: ResultPage) -> u32 {
+        let handle = self.next_handle;
+        self.next_handle = self.next_handle.wrapping_add(1);
+        self.open.insert(
+            handle,
+            ResultSet {
+                hits: page.hits,
+                sequence: page.sequence,
+                served: 0,
+                trace: page.trace,
+            },
+        );
+        handle
+    }
+
+    fn next_page(&mut self, handle: u32, page_size: usize) -> Result<ResultPage, VectorError> {
+        let set = self
+            .open
+            .get_mut(&handle)
+            .ok_or(VectorError::UnknownResultSet { handle })?;
+
+        let offset = set.served;
+        let end = offset.saturating_add(page_size).min(set.hits.len());
+        set.served = end;
+        let page = ResultPage {
+            hits: set.hits[offset..end].to_vec(),
+            sequence: set.sequence,
+            offset,
+            next_offset: (end < set.hits.len()).then_some(end),
+            trace: set.trace.take(),
+        };
+
+        if page.next_offset.is_none() {
+            self.open.remove(&handle);
+        }
+        Ok(page)
+    }
+}
+
+#[wasm_bindgen]
+impl VectorIndex {
+    /// Rank the top `k` results for `query` and keep them in WASM, returning
+    /// a handle to page through them with `nextPage`. Takes the same options
+    /// as `search`.
+    #[wasm_bindgen(js_name = "openResultSet")]
+    pub fn open_result_set(
+        &mut self,
+        query: &[f32],
+        k: usize,
+        options: JsValue,
+    ) -> Result<u32, JsValue> {
+        let query = self.validation.check(query, None)?;
+        let options = self.query_defaults.resolve(options)?;
+        let page = self.page(&query, 0, k, &options)?;
+        Ok(self.result_sets.open(page))
+    }
+
+    /// Return the next `pageSize` hits of an open result set as a
+    /// `ResultPage`. The set is released with its last page; fails with
+    /// `UnknownResultSet` after that or for a handle never opened.
+    #[wasm_bindgen(js_name = "nextPage")]
+    pub fn next_page(&mut self, handle: u32, page_size: usize) -> Result<JsValue, JsValue> {
+        to_js(&self.result_sets.next_page(handle, page_size)?)
+    }
+
+    /// Release a result set before its last page; returns whether it was
+    /// still open
+    #[wasm_bindgen(js_name = "closeResultSet")]
+    pub fn close_result_set(&mut self, handle: u32) -> bool {
+        self.result_sets.open.remove(&handle).is_some()
+    }
+
+    /// Number of result sets still holding hits
+    #[wasm_bindgen(getter, js_name = "openResultSets")]
+    pub fn open_result_sets(&self) -> usize {
+        self.result_sets.open.len()
+    }
+}
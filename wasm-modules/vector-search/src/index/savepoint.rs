@@ -0,0 +1,200 @@
+//! Named savepoints over an undo log of index mutations.
+//!
+//! The log is only kept while at least one savepoint exists. Each insert or
+//! remove recorded in it stores what is needed to reverse it, including a
+//! copy of any vector it overwrote or removed, so long-lived savepoints
+//! across large bulk edits cost memory.
+
+use wasm_bindgen::prelude::*;
+
+use super::VectorIndex;
+use crate::error::VectorError;
+
+/// Reverse of a single recorded mutation
+#[derive(Clone, Debug)]
+pub(crate) enum Undo {
+    /// The ID was newly inserted; undo by removing it
+    Remove(u32),
+    /// The ID held this vector before being overwritten or removed
+    Restore(u32, Vec<f32>),
+}
+
+#[wasm_bindgen]
+impl VectorIndex {
+    /// Mark the current state under `name`, moving it if it already exists.
+    ///
+    /// Later inserts and removes can be undone with `rollbackTo(name)`.
+    pub fn savepoint(&mut self, name: &str) {
+        self.savepoints.retain(|(existing, _)| existing != name);
+        self.savepoints
+            .push((name.to_string(), self.undo_log.len()));
+    }
+
+    /// Undo every mutation made since `savepoint(name)`, returning how many
+    /// were reverted.
+    ///
+    /// The savepoint itself survives, so it can be rolled back to again;
+    /// savepoints created after it are discarded. Rolled-back changes bump
+    /// the sequence like any other mutation, invalidating open cursors.
+    #[wasm_bindgen(js_name = "rollbackTo")]
+    pub fn rollback_to(&mut self, name: &str) -> Result<usize, JsValue> {
+        self.check_no_readers()?;
+        Ok(self.rollback(name)?)
+    }
+
+    /// Forget `name` and any savepoints created after it, keeping the
+    /// changes made since
+    #[wasm_bindgen(js_name = "releaseSavepoint")]
+    pub fn release_savepoint(&mut self, name: &str) -> Result<(), JsValue> {
+        let position = self.savepoint_position(name)?;
+        self.savepoints.truncate(position);
+        if self.savepoints.is_empty() {
+            self.undo_log.clear();
+        }
+        Ok(())
+    }
+
+    /// Names of the active savepoints, oldest first
+    #[wasm_bindgen(getter)]
+    pub fn savepoints(&self) -> Vec<String> {
+        self.savepoints
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+}
+
+impl VectorIndex {
+    fn savepoint_position(&self, name: &str) -> Result<usize, VectorError> {
+        self.savepoints
+            .iter()
+            .position(|(existing, _)| existing == name)
+            .ok_or_else(|| VectorError::UnknownSavepoint {
+                name: name.to_string(),
+            })
+    }
+
+    pub(crate) fn rollback(&mut self, name: &str) -> Result<usize, VectorError> {
+        let position = self.savepoint_position(name)?;
+        let mark = self.savepoints[position].1;
+        self.savepoints.truncate(position + 1);
+
+        let undo = self.undo_log.split_off(mark);
+        let reverted = undo.len();
+
+        // Replaying through insert/remove keeps norms, codes and the tree in
+        // sync; savepoints are detached so the replay itself isn't recorded
+        let savepoints = std::mem::take(&mut self.savepoints);
+        for entry in undo.into_iter().rev() {
+            match entry {
+                Undo::Remove(id) => {
+                    self.remove(id);
+                }
+                Undo::Restore(id, vector) => self.store(id, &vector),
+            }
+        }
+        self.savepoints = savepoints;
+
+        Ok(reverted)
+    }
+
+    /// Log the reverse of inserting `id`, before it happens
+    pub(super) fn record_insert(&mut self, id: u32) {
+        if self.savepoints.is_empty() {
+            return;
+        }
+
+        let undo = match self.positions.get(&id) {
+            Some(&slot) => Undo::Restore(id, self.slot(slot).into_owned()),
+            None => Undo::Remove(id),
+        };
+        self.undo_log.push(undo);
+    }
+
+    /// Log the reverse of removing the vector at `slot`, before it happens
+    pub(super) fn record_remove(&mut self, id: u32, slot: usize) {
+        if !self.savepoints.is_empty() {
+            let vector = self.slot(slot).into_owned();
+            self.undo_log.push(Undo::Restore(id, vector));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::rng::SplitMix64;
+    use crate::search::QueryOptions;
+
+    const DIMENSIONS: usize = 4;
+
+    fn vector(rng: &mut SplitMix64) -> Vec<f32> {
+        (0..DIMENSIONS)
+            .map(|_| rng.next_f64() as f32 * 2.0 - 1.0)
+            .collect()
+    }
+
+    /// Every stored vector by ID, bit for bit
+    fn state(index: &VectorIndex) -> BTreeMap<u32, Vec<u32>> {
+        index
+            .positions
+            .iter()
+            .map(|(&id, &slot)| (id, index.slot(slot).iter().map(|x| x.to_bits()).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn rollback_restores_exact_state() {
+        let mut rng = SplitMix64::new(3);
+        let mut index = VectorIndex::new(DIMENSIONS);
+        for id in 0..20 {
+            index.insert(id, &vector(&mut rng)).unwrap();
+        }
+        index.flush();
+        let before = state(&index);
+
+        index.savepoint("a");
+        for id in 20..30 {
+            index.insert(id, &vector(&mut rng)).unwrap();
+        }
+        index.insert(3, &vector(&mut rng)).unwrap();
+        index.remove(5);
+        index.remove(25);
+        let middle = state(&index);
+
+        index.savepoint("b");
+        index.insert(3, &vector(&mut rng)).unwrap();
+        index.remove(0);
+
+        assert_eq!(index.rollback("b").unwrap(), 2);
+        assert_eq!(state(&index), middle);
+        assert_eq!(index.rollback("a").unwrap(), 13);
+        assert_eq!(state(&index), before);
+        assert_eq!(index.size(), 20);
+
+        // Later savepoints are gone, and the restored rows are searchable
+        assert_eq!(index.savepoints(), vec!["a".to_string()]);
+        assert!(matches!(
+            index.rollback("b"),
+            Err(VectorError::UnknownSavepoint { .. })
+        ));
+        let query = index.slot(index.positions[&5]).into_owned();
+        let page = index.page(&query, 0, 1, &QueryOptions::default()).unwrap();
+        assert_eq!(page.hits[0].id, 5);
+    }
+
+    #[test]
+    fn changes_without_savepoints_are_not_logged() {
+        let mut index = VectorIndex::new(DIMENSIONS);
+        index.insert(1, &[1.0, 0.0, 0.0, 0.0]).unwrap();
+        assert!(index.undo_log.is_empty());
+
+        index.savepoint("a");
+        index.insert(2, &[0.0, 1.0, 0.0, 0.0]).unwrap();
+        index.release_savepoint("a").unwrap();
+        assert!(index.undo_log.is_empty());
+        assert_eq!(index.size(), 2);
+    }
+}
@@ -0,0 +1,133 @@
+//! Vector storage split into fixed-size segments.
+//!
+//! One contiguous `Vec<f32>` has to be reallocated (and briefly held twice)
+//! every time it doubles, and near the top of the 4GB address space wasm
+//! memory growth for such a block fails long before memory is actually
+//! exhausted. Rows are instead packed into segments of at most
+//! `SEGMENT_BYTES`, so growing allocates one more segment and shrinking
+//! frees whole segments back to the allocator. A row never straddles two
+//! segments, so kernels can keep scanning plain slices segment by segment.
+
+/// Target size of one segment
+pub(crate) const SEGMENT_BYTES: usize = 16 << 20;
+
+/// Dense row storage for `VectorIndex`, addressed by slot
+#[derive(Clone, Debug)]
+pub(crate) struct Segments {
+    dimensions: usize,
+    rows_per_segment: usize,
+    /// Full except for the last, which is never empty
+    segments: Vec<Vec<f32>>,
+    rows: usize,
+}
+
+impl Segments {
+    pub(crate) fn new(dimensions: usize) -> Self {
+        let row_bytes = dimensions.max(1) * std::mem::size_of::<f32>();
+        Self {
+            dimensions,
+            rows_per_segment: (SEGMENT_BYTES / row_bytes).max(1),
+            segments: Vec::new(),
+            rows: 0,
+        }
+    }
+
+    pub(crate) fn segment_count(&self) -> usize {
+        self.segments.len()
+    }
+
+    /// Bytes reserved by every segment, including unused capacity in the
+    /// last one
+    pub(crate) fn allocated_bytes(&self) -> usize {
+        self.segments
+            .iter()
+            .map(|segment| segment.capacity() * std::mem::size_of::<f32>())
+            .sum()
+    }
+
+    fn locate(&self, slot: usize) -> (usize, usize) {
+        let offset = (slot % self.rows_per_segment) * self.dimensions;
+        (slot / self.rows_per_segment, offset)
+    }
+
+    pub(crate) fn row(&self, slot: usize) -> &[f32] {
+        let (segment, offset) = self.locate(slot);
+        &self.segments[segment][offset..offset + self.dimensions]
+    }
+
+    pub(crate) fn row_mut(&mut self, slot: usize) -> &mut [f32] {
+        let (segment, offset) = self.locate(slot);
+        &mut self.segments[segment][offset..offset + self.dimensions]
+    }
+
+    pub(crate) fn push(&mut self, vector: &[f32]) {
+        let segment_len = self.rows_per_segment * self.dimensions;
+        if self.rows.is_multiple_of(self.rows_per_segment) {
+            self.segments.push(Vec::new());
+        }
+
+        let last = self
+            .segments
+            .last_mut()
+            .expect("a segment was just ensured");
+        if last.len() == last.capacity() {
+            // Grow geometrically, but never past one segment, so a full
+            // segment holds no slack
+            let target = (last.capacity() * 2)
+                .max(self.dimensions * 4)
+                .min(segment_len);
+            last.reserve_exact(target - last.len());
+        }
+        last.extend_from_slice(vector);
+        self.rows += 1;
+    }
+
+    /// Overwrite the row at `to` with the row at `from`
+    pub(crate) fn copy_row(&mut self, from: usize, to: usize) {
+        let (from_segment, from_offset) = self.locate(from);
+        let (to_segment, to_offset) = self.locate(to);
+        let dims = self.dimensions;
+
+        if from_segment == to_segment {
+            self.segments[to_segment].copy_within(from_offset..from_offset + dims, to_offset);
+        } else {
+            let (low, high) = self.segments.split_at_mut(from_segment.max(to_segment));
+            let (source, target) = if from_segment > to_segment {
+                (&high[0], &mut low[to_segment])
+            } else {
+                (&low[from_segment], &mut high[0])
+            };
+            target[to_offset..to_offset + dims]
+                .copy_from_slice(&source[from_offset..from_offset + dims]);
+        }
+    }
+
+    /// Drop the last row, freeing its segment once it is empty
+    pub(crate) fn pop(&mut self) {
+        if self.rows == 0 {
+            return;
+        }
+
+        self.rows -= 1;
+        if self.rows.is_multiple_of(self.rows_per_segment) {
+            self.segments.pop();
+        } else {
+            let last = self.segments.last_mut().expect("rows live in segments");
+            last.truncate(last.len() - self.dimensions);
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.segments = Vec::new();
+        self.rows = 0;
+    }
+
+    /// Each segment's rows as one slice, with the slot of its first row
+    pub(crate) fn chunks(&self) -> impl Iterator<Item = (usize, &[f32])> {
+        let rows_per_segment = self.rows_per_segment;
+        self.segments
+            .iter()
+            .enumerate()
+            .map(move |(i, segment)| (i * rows_per_segment, segment.as_slice()))
+    }
+}
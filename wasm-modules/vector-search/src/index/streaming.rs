@@ -0,0 +1,212 @@
+//! Top-k results delivered one at a time as their rank becomes final.
+//!
+//! With a cluster tree, leaves are visited best-first by the optimistic
+//! score of their subtree. A held candidate is final once it beats the
+//! bound of every unvisited subtree, so the best hits usually reach the
+//! callback after a few leaves, long before the walk ends. Without a tree,
+//! or when no bound applies (weights, `customMetric`, `searchDims`,
+//! `exact`), the ranking completes first and the hits follow in order.
+
+use std::collections::HashSet;
+
+use wasm_bindgen::prelude::*;
+
+use super::{IndexHit, VectorIndex};
+use crate::error::VectorError;
+use crate::events;
+use crate::metric::MetricKind;
+use crate::prune::BOUND_SLACK;
+use crate::search::{BestFirst, QueryOptions, TopK};
+use crate::to_js;
+use crate::trace::{ScanCounts, ScanStrategy};
+
+/// Whether `score` is at least as good as anything that could still reach
+/// `bound`, with slack so rounding never confirms a hit too early
+fn proven(metric: MetricKind, score: f64, bound: f64) -> bool {
+    let slack = BOUND_SLACK * bound.abs().max(1.0);
+    if metric.higher_is_better() {
+        score >= bound + slack
+    } else {
+        score <= bound - slack
+    }
+}
+
+#[wasm_bindgen]
+impl VectorIndex {
+    /// Find the top `k` hits for `query`, calling `onResult(hit, rank)` for
+    /// each as soon as its rank is final, best first.
+    ///
+    /// Hits have the shape `search` returns. `false` from the callback stops
+    /// the query; an exception thrown by it is rethrown. Resolves options
+    /// like `search`, except that `autoK`, `normalize` and
+    /// `collapseDuplicates` need the whole result set and are rejected.
+    /// Returns the number of hits delivered.
+    #[wasm_bindgen(js_name = "findTopKStreaming")]
+    pub fn find_top_k_streaming(
+        &self,
+        query: &[f32],
+        k: usize,
+        options: JsValue,
+        on_result: &js_sys::Function,
+    ) -> Result<usize, JsValue> {
+        let query = self.validation.check(query, None)?;
+        let options = self.query_defaults.resolve(options)?;
+        if options.auto_k.is_some() || options.normalize.is_some() || options.collapse_duplicates {
+            return Err(VectorError::InvalidOptions {
+                message:
+                    "autoK, normalize and collapseDuplicates cannot be combined with streaming"
+                        .to_string(),
+            }
+            .into());
+        }
+        if options.groups.is_some() || options.allowed_groups.is_some() || options.parents.is_some()
+        {
+            return Err(VectorError::InvalidOptions {
+                message: "groups and parents are not supported by VectorIndex".to_string(),
+            }
+            .into());
+        }
+
+        let timer = events::start();
+        events::search_started("VectorIndex", k, self.dimensions);
+
+        let mut scan = ScanCounts::default();
+        let delivered = self.streamed(&query, k, &options, &mut scan, |rank, slot, score| {
+            let hit = IndexHit {
+                id: self.ids[slot],
+                score: options.reported_score(score),
+                normalized: None,
+                collapsed: None,
+            };
+            let verdict = on_result.call2(&JsValue::NULL, &to_js(&hit)?, &JsValue::from(rank))?;
+            Ok(verdict != JsValue::FALSE)
+        })?;
+
+        events::search_completed("VectorIndex", timer, &scan, delivered, false);
+        Ok(delivered)
+    }
+}
+
+impl VectorIndex {
+    /// Hand the top `k` accepted `(slot, score)` pairs to `deliver` with
+    /// their rank, each as soon as it is final. `deliver` returns false to
+    /// stop. Returns how many were delivered.
+    pub(crate) fn streamed(
+        &self,
+        query: &[f32],
+        k: usize,
+        options: &QueryOptions,
+        scan: &mut ScanCounts,
+        mut deliver: impl FnMut(usize, usize, f64) -> Result<bool, JsValue>,
+    ) -> Result<usize, JsValue> {
+        let excluded: HashSet<usize> = options
+            .exclude
+            .iter()
+            .filter_map(|id| self.positions.get(&(*id as u32)).copied())
+            .collect();
+        let accepts = |slot| !excluded.contains(&slot);
+
+        let bound_metric = match options.metric {
+            _ if options.weights.is_some() || options.exact || self.truncates(options) => None,
+            MetricKind::Custom => None,
+            MetricKind::Cosine if self.assume_normalized || options.assume_normalized => {
+                Some(MetricKind::Dot)
+            }
+            metric => Some(metric),
+        };
+        let (Some(tree), Some(bound_metric)) = (&self.tree, bound_metric) else {
+            let ranked = self.ranked(query, k, options, accepts, scan)?;
+            let mut delivered = 0;
+            for (rank, (slot, score)) in ranked.into_iter().enumerate() {
+                delivered += 1;
+                if !deliver(rank, slot, score)? {
+                    break;
+                }
+            }
+            return Ok(delivered);
+        };
+
+        let scorer = self.scorer(query, options);
+        let use_norms = self.uses_cached_norms(options);
+        scan.strategy = ScanStrategy::ClusterTree;
+
+        // `top` tracks the k-th best score; `held` hands candidates out in
+        // order. Anything evicted from `top` ranks below k and is never
+        // reached in `held`.
+        let mut top = TopK::new(k, options.metric);
+        let mut held = BestFirst::new(options.metric);
+        let consider = |top: &mut TopK, held: &mut BestFirst, slot: usize, score: f64| {
+            if top
+                .threshold()
+                .is_none_or(|threshold| !proven(options.metric, threshold, score))
+            {
+                top.push(slot, score);
+                held.push(slot, score);
+            }
+        };
+
+        // Pending records are not in the tree yet, so scan them directly
+        for id in &self.pending {
+            let slot = self.positions[id];
+            if accepts(slot) {
+                scan.vectors += 1;
+                let score = self.score_slot(&scorer, use_norms, slot);
+                consider(&mut top, &mut held, slot, score);
+                scorer.check()?;
+            }
+        }
+
+        let mut frontier = tree.frontier(scorer.query(), bound_metric);
+        let mut delivered = 0;
+        while delivered < k {
+            // Once the k-th best held beats every unvisited subtree, the
+            // rest of the tree can't change the results
+            let bound = frontier.bound();
+            if let (Some(threshold), Some(bound)) = (top.threshold(), bound) {
+                if proven(options.metric, threshold, bound) {
+                    frontier.clear();
+                }
+            }
+
+            let bound = frontier.bound();
+            while delivered < k {
+                let Some((slot, score)) = held.peek() else {
+                    break;
+                };
+                if bound.is_some_and(|bound| !proven(options.metric, score, bound)) {
+                    break;
+                }
+                held.pop();
+                delivered += 1;
+                if !deliver(delivered - 1, slot, score)? {
+                    scan.centroids += frontier.centroids_scored;
+                    return Ok(delivered);
+                }
+            }
+
+            let Some(members) = frontier.next_leaf() else {
+                if held.peek().is_none() {
+                    break;
+                }
+                continue;
+            };
+            for &id in members {
+                if self.pending.contains(&id) {
+                    continue;
+                }
+                let Some(&slot) = self.positions.get(&id) else {
+                    continue;
+                };
+                if accepts(slot) {
+                    scan.vectors += 1;
+                    let score = self.score_slot(&scorer, use_norms, slot);
+                    consider(&mut top, &mut held, slot, score);
+                    scorer.check()?;
+                }
+            }
+        }
+
+        scan.centroids += frontier.centroids_scored;
+        Ok(delivered)
+    }
+}
@@ -0,0 +1,121 @@
+//! Triplet-margin evaluation of embedding quality over stored vectors.
+
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use super::VectorIndex;
+use crate::error::VectorError;
+use crate::search::QueryOptions;
+use crate::to_js;
+
+/// How well stored embeddings separate positives from negatives
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TripletReport {
+    /// Triplets whose three IDs are all stored
+    pub evaluated: usize,
+    /// Triplets skipped because an ID is not stored
+    pub missing: usize,
+    /// Evaluated triplets whose achieved margin reaches `margin`
+    pub satisfied: usize,
+    pub satisfaction_rate: f64,
+    /// How much closer the positive is than the negative, averaged over
+    /// evaluated triplets: `score(a, p) - score(a, n)` for similarities and
+    /// `distance(a, n) - distance(a, p)` for distances
+    pub mean_margin: f64,
+    pub min_margin: f64,
+    /// Mean triplet hinge loss, `max(0, margin - achieved)`
+    pub mean_loss: f64,
+}
+
+#[wasm_bindgen]
+impl VectorIndex {
+    /// Evaluate `(anchors[i], positives[i], negatives[i])` ID triplets
+    /// against `margin`.
+    ///
+    /// Scores use `options.metric` (cosine by default) and the other query
+    /// options that affect scoring, such as `weights`. All statistics are
+    /// zero when no triplet could be evaluated.
+    #[wasm_bindgen(js_name = "evaluateTriplets")]
+    pub fn evaluate_triplets(
+        &self,
+        anchors: &[u32],
+        positives: &[u32],
+        negatives: &[u32],
+        margin: f64,
+        options: JsValue,
+    ) -> Result<JsValue, JsValue> {
+        let options = self.query_defaults.resolve(options)?;
+        to_js(&self.triplet_report(anchors, positives, negatives, margin, &options)?)
+    }
+}
+
+impl VectorIndex {
+    pub(crate) fn triplet_report(
+        &self,
+        anchors: &[u32],
+        positives: &[u32],
+        negatives: &[u32],
+        margin: f64,
+        options: &QueryOptions,
+    ) -> Result<TripletReport, VectorError> {
+        if anchors.len() != positives.len() || anchors.len() != negatives.len() {
+            panic!("Triplet arrays length mismatch");
+        }
+
+        let higher_is_better = options.metric.higher_is_better();
+        let use_norms = self.uses_cached_norms(options);
+
+        let mut missing = 0;
+        let mut achieved = Vec::with_capacity(anchors.len());
+        for ((anchor, positive), negative) in anchors.iter().zip(positives).zip(negatives) {
+            let slots = (
+                self.positions.get(anchor),
+                self.positions.get(positive),
+                self.positions.get(negative),
+            );
+            let (Some(&anchor), Some(&positive), Some(&negative)) = slots else {
+                missing += 1;
+                continue;
+            };
+
+            let query = self.slot(anchor);
+            let scorer = self.scorer(&query, options);
+            let to_positive = self.score_slot(&scorer, use_norms, positive);
+            let to_negative = self.score_slot(&scorer, use_norms, negative);
+            scorer.check()?;
+
+            achieved.push(if higher_is_better {
+                to_positive - to_negative
+            } else {
+                to_negative - to_positive
+            });
+        }
+
+        let evaluated = achieved.len();
+        if evaluated == 0 {
+            return Ok(TripletReport {
+                evaluated,
+                missing,
+                satisfied: 0,
+                satisfaction_rate: 0.0,
+                mean_margin: 0.0,
+                min_margin: 0.0,
+                mean_loss: 0.0,
+            });
+        }
+
+        let satisfied = achieved.iter().filter(|&&m| m >= margin).count();
+        let total_loss: f64 = achieved.iter().map(|&m| (margin - m).max(0.0)).sum();
+
+        Ok(TripletReport {
+            evaluated,
+            missing,
+            satisfied,
+            satisfaction_rate: satisfied as f64 / evaluated as f64,
+            mean_margin: achieved.iter().sum::<f64>() / evaluated as f64,
+            min_margin: achieved.iter().copied().fold(f64::INFINITY, f64::min),
+            mean_loss: total_loss / evaluated as f64,
+        })
+    }
+}
@@ -0,0 +1,434 @@
+//! Blocked batch-scoring kernels.
+//!
+//! Candidates are scored in tiles of `ROW_TILE` rows so each query element
+//! is loaded once per tile and the per-row accumulators stay in registers.
+//! The multi-query kernel additionally walks the corpus in blocks of
+//! `BLOCK_ROWS` rows, scoring every query against a block while it is still
+//! in cache.
+//!
+//! With the `simd` feature, f64 pairs and the multi-query tiles are also
+//! scored with f64x2 (v128) kernels, two lanes per instruction with a scalar
+//! tail for odd dimensions.
+
+#[cfg(feature = "simd")]
+use packed_simd::{f32x4, f64x2};
+
+/// Candidate rows scored together by one register tile
+const ROW_TILE: usize = 4;
+
+/// Candidate rows kept hot in cache while every query is scored against them
+const BLOCK_ROWS: usize = 64;
+
+#[inline]
+fn finish_cosine(dot: f64, query_norm: f64, row_norm_sq: f64) -> f64 {
+    let magnitude = query_norm * row_norm_sq.sqrt();
+    if magnitude == 0.0 {
+        0.0
+    } else {
+        dot / magnitude
+    }
+}
+
+/// Cosine similarity of `query` against every row of `vectors`, 4 rows by
+/// 2 lanes at a time with SIMD. Zero-width rows push nothing.
+pub(crate) fn cosine_rows_f64(query: &[f64], vectors: &[f64], out: &mut Vec<f64>) {
+    let dims = query.len();
+    if dims == 0 {
+        return;
+    }
+
+    let query_norm = query.iter().map(|x| x * x).sum::<f64>().sqrt();
+    let rows = vectors.len() / dims;
+    let tiled = rows - rows % ROW_TILE;
+
+    out.reserve(rows);
+
+    #[cfg(feature = "simd")]
+    {
+        let lanes = dims / 2;
+        let tail = lanes * 2;
+
+        for tile in vectors[..tiled * dims].chunks_exact(ROW_TILE * dims) {
+            let mut dot = [f64x2::splat(0.0); ROW_TILE];
+            let mut norm = [f64x2::splat(0.0); ROW_TILE];
+
+            for l in 0..lanes {
+                let q = f64x2::from_slice_unaligned(&query[l * 2..l * 2 + 2]);
+                for i in 0..ROW_TILE {
+                    let start = i * dims + l * 2;
+                    let a = f64x2::from_slice_unaligned(&tile[start..start + 2]);
+                    dot[i] += q * a;
+                    norm[i] += a * a;
+                }
+            }
+
+            for i in 0..ROW_TILE {
+                let row = &tile[i * dims..(i + 1) * dims];
+                let mut d = dot[i].sum();
+                let mut n = norm[i].sum();
+                for (q, a) in query[tail..].iter().zip(&row[tail..]) {
+                    d += q * a;
+                    n += a * a;
+                }
+                out.push(finish_cosine(d, query_norm, n));
+            }
+        }
+    }
+
+    #[cfg(not(feature = "simd"))]
+    for tile in vectors[..tiled * dims].chunks_exact(ROW_TILE * dims) {
+        let (r0, rest) = tile.split_at(dims);
+        let (r1, rest) = rest.split_at(dims);
+        let (r2, r3) = rest.split_at(dims);
+
+        let mut dot = [0.0f64; ROW_TILE];
+        let mut norm = [0.0f64; ROW_TILE];
+
+        for j in 0..dims {
+            let q = query[j];
+            let (a0, a1, a2, a3) = (r0[j], r1[j], r2[j], r3[j]);
+            dot[0] += q * a0;
+            dot[1] += q * a1;
+            dot[2] += q * a2;
+            dot[3] += q * a3;
+            norm[0] += a0 * a0;
+            norm[1] += a1 * a1;
+            norm[2] += a2 * a2;
+            norm[3] += a3 * a3;
+        }
+
+        for i in 0..ROW_TILE {
+            out.push(finish_cosine(dot[i], query_norm, norm[i]));
+        }
+    }
+
+    for row in vectors[tiled * dims..].chunks_exact(dims) {
+        let mut dot = 0.0;
+        let mut norm = 0.0;
+        for (q, a) in query.iter().zip(row) {
+            dot += q * a;
+            norm += a * a;
+        }
+        out.push(finish_cosine(dot, query_norm, norm));
+    }
+}
+
+/// Cosine similarity of an f32 `query` against every row of `vectors`,
+/// 4 rows by 4 lanes at a time with SIMD. Zero-width rows push nothing.
+pub(crate) fn cosine_rows_f32(query: &[f32], vectors: &[f32], out: &mut Vec<f32>) {
+    let dims = query.len();
+    if dims == 0 {
+        return;
+    }
+
+    let rows = vectors.len() / dims;
+
+    out.reserve(rows);
+
+    #[cfg(feature = "simd")]
+    {
+        let lanes = dims / 4;
+        let tail = lanes * 4;
+
+        let mut query_norm = f32x4::splat(0.0);
+        for l in 0..lanes {
+            let q = f32x4::from_slice_unaligned(&query[l * 4..l * 4 + 4]);
+            query_norm += q * q;
+        }
+        let query_norm =
+            (query_norm.sum() + query[tail..].iter().map(|x| x * x).sum::<f32>()).sqrt() as f64;
+
+        let tiled = rows - rows % ROW_TILE;
+
+        for tile in vectors[..tiled * dims].chunks_exact(ROW_TILE * dims) {
+            let mut dot = [f32x4::splat(0.0); ROW_TILE];
+            let mut norm = [f32x4::splat(0.0); ROW_TILE];
+
+            for l in 0..lanes {
+                let q = f32x4::from_slice_unaligned(&query[l * 4..l * 4 + 4]);
+                for i in 0..ROW_TILE {
+                    let start = i * dims + l * 4;
+                    let a = f32x4::from_slice_unaligned(&tile[start..start + 4]);
+                    dot[i] += q * a;
+                    norm[i] += a * a;
+                }
+            }
+
+            for i in 0..ROW_TILE {
+                let row = &tile[i * dims..(i + 1) * dims];
+                let mut d = dot[i].sum();
+                let mut n = norm[i].sum();
+                for (q, a) in query[tail..].iter().zip(&row[tail..]) {
+                    d += q * a;
+                    n += a * a;
+                }
+                out.push(finish_cosine(d as f64, query_norm, n as f64) as f32);
+            }
+        }
+
+        for row in vectors[tiled * dims..].chunks_exact(dims) {
+            let mut dot = f32x4::splat(0.0);
+            let mut norm = f32x4::splat(0.0);
+            for l in 0..lanes {
+                let q = f32x4::from_slice_unaligned(&query[l * 4..l * 4 + 4]);
+                let a = f32x4::from_slice_unaligned(&row[l * 4..l * 4 + 4]);
+                dot += q * a;
+                norm += a * a;
+            }
+
+            let mut d = dot.sum();
+            let mut n = norm.sum();
+            for (q, a) in query[tail..].iter().zip(&row[tail..]) {
+                d += q * a;
+                n += a * a;
+            }
+            out.push(finish_cosine(d as f64, query_norm, n as f64) as f32);
+        }
+    }
+
+    #[cfg(not(feature = "simd"))]
+    {
+        let query_norm = query.iter().map(|x| x * x).sum::<f32>().sqrt() as f64;
+        for row in vectors.chunks_exact(dims) {
+            let mut dot = 0.0f32;
+            let mut norm = 0.0f32;
+            for (q, a) in query.iter().zip(row) {
+                dot += q * a;
+                norm += a * a;
+            }
+            out.push(finish_cosine(dot as f64, query_norm, norm as f64) as f32);
+        }
+    }
+}
+
+/// Dot products of `query` against every row of `block`, 4 rows by 2 lanes
+/// at a time with SIMD
+fn dot_rows_f64(query: &[f64], block: &[f64], out: &mut [f64]) {
+    let dims = query.len();
+    let rows = block.len() / dims;
+    let tiled = rows - rows % ROW_TILE;
+
+    #[cfg(feature = "simd")]
+    {
+        let lanes = dims / 2;
+        let tail = lanes * 2;
+
+        for (t, tile) in block[..tiled * dims]
+            .chunks_exact(ROW_TILE * dims)
+            .enumerate()
+        {
+            let mut dot = [f64x2::splat(0.0); ROW_TILE];
+            for l in 0..lanes {
+                let q = f64x2::from_slice_unaligned(&query[l * 2..l * 2 + 2]);
+                for (i, dot) in dot.iter_mut().enumerate() {
+                    let start = i * dims + l * 2;
+                    *dot += q * f64x2::from_slice_unaligned(&tile[start..start + 2]);
+                }
+            }
+
+            for (i, dot) in dot.iter().enumerate() {
+                let row = &tile[i * dims..(i + 1) * dims];
+                let mut d = dot.sum();
+                for (q, a) in query[tail..].iter().zip(&row[tail..]) {
+                    d += q * a;
+                }
+                out[t * ROW_TILE + i] = d;
+            }
+        }
+    }
+
+    #[cfg(not(feature = "simd"))]
+    for (t, tile) in block[..tiled * dims]
+        .chunks_exact(ROW_TILE * dims)
+        .enumerate()
+    {
+        let (r0, rest) = tile.split_at(dims);
+        let (r1, rest) = rest.split_at(dims);
+        let (r2, r3) = rest.split_at(dims);
+
+        let mut dot = [0.0f64; ROW_TILE];
+        for j in 0..dims {
+            let q = query[j];
+            dot[0] += q * r0[j];
+            dot[1] += q * r1[j];
+            dot[2] += q * r2[j];
+            dot[3] += q * r3[j];
+        }
+
+        out[t * ROW_TILE..(t + 1) * ROW_TILE].copy_from_slice(&dot);
+    }
+
+    for (i, row) in block[tiled * dims..].chunks_exact(dims).enumerate() {
+        out[tiled + i] = query.iter().zip(row).map(|(q, a)| q * a).sum();
+    }
+}
+
+/// Squared L2 norm, with f64x2 lanes under SIMD
+pub(crate) fn squared_norm_f64(vector: &[f64]) -> f64 {
+    #[cfg(feature = "simd")]
+    return dot_f64(vector, vector);
+
+    #[cfg(not(feature = "simd"))]
+    vector.iter().map(|x| x * x).sum()
+}
+
+/// Divide every element by `divisor` in place, 2 lanes at a time with SIMD
+pub(crate) fn divide_f64(vector: &mut [f64], divisor: f64) {
+    #[cfg(feature = "simd")]
+    {
+        let tail = vector.len() - vector.len() % 2;
+        let divisor_lanes = f64x2::splat(divisor);
+        for lane in vector[..tail].chunks_exact_mut(2) {
+            (f64x2::from_slice_unaligned(lane) / divisor_lanes).write_to_slice_unaligned(lane);
+        }
+        for val in &mut vector[tail..] {
+            *val /= divisor;
+        }
+    }
+
+    #[cfg(not(feature = "simd"))]
+    for val in vector.iter_mut() {
+        *val /= divisor;
+    }
+}
+
+/// Cosine similarity of every query against every row of `vectors`.
+///
+/// Output is row-major by query: `out[q * rows + r]`. Row norms are computed
+/// once per block and shared by all queries.
+pub(crate) fn cosine_matrix_f64(queries: &[f64], vectors: &[f64], dims: usize) -> Vec<f64> {
+    if dims == 0 {
+        return Vec::new();
+    }
+
+    let query_count = queries.len() / dims;
+    let rows = vectors.len() / dims;
+
+    let query_norms: Vec<f64> = queries
+        .chunks_exact(dims)
+        .map(|q| squared_norm_f64(q).sqrt())
+        .collect();
+
+    let mut out = vec![0.0; query_count * rows];
+    let mut dots = vec![0.0; BLOCK_ROWS];
+    let mut row_norms = vec![0.0; BLOCK_ROWS];
+
+    for (b, block) in vectors.chunks(BLOCK_ROWS * dims).enumerate() {
+        let block_rows = block.len() / dims;
+        let first_row = b * BLOCK_ROWS;
+
+        for (norm, row) in row_norms.iter_mut().zip(block.chunks_exact(dims)) {
+            *norm = squared_norm_f64(row);
+        }
+
+        for (q, query) in queries.chunks_exact(dims).enumerate() {
+            dot_rows_f64(query, block, &mut dots[..block_rows]);
+
+            let out_row = &mut out[q * rows + first_row..q * rows + first_row + block_rows];
+            for ((score, &dot), &norm) in out_row.iter_mut().zip(&dots).zip(&row_norms) {
+                *score = finish_cosine(dot, query_norms[q], norm);
+            }
+        }
+    }
+
+    out
+}
+
+/// Dot product of two f64 vectors
+#[cfg(feature = "simd")]
+pub(crate) fn dot_f64(vec1: &[f64], vec2: &[f64]) -> f64 {
+    let tail = vec1.len() - vec1.len() % 2;
+    let mut dot = f64x2::splat(0.0);
+    for (a, b) in vec1[..tail]
+        .chunks_exact(2)
+        .zip(vec2[..tail].chunks_exact(2))
+    {
+        let (a, b) = (
+            f64x2::from_slice_unaligned(a),
+            f64x2::from_slice_unaligned(b),
+        );
+        dot += a * b;
+    }
+
+    let mut dot = dot.sum();
+    for (a, b) in vec1[tail..].iter().zip(&vec2[tail..]) {
+        dot += a * b;
+    }
+    dot
+}
+
+/// Dot product and the squared norm of `vec2`, in one pass
+#[cfg(feature = "simd")]
+pub(crate) fn dot_and_norm_f64(vec1: &[f64], vec2: &[f64]) -> (f64, f64) {
+    let tail = vec1.len() - vec1.len() % 2;
+    let mut dot = f64x2::splat(0.0);
+    let mut norm = f64x2::splat(0.0);
+    for (a, b) in vec1[..tail]
+        .chunks_exact(2)
+        .zip(vec2[..tail].chunks_exact(2))
+    {
+        let (a, b) = (
+            f64x2::from_slice_unaligned(a),
+            f64x2::from_slice_unaligned(b),
+        );
+        dot += a * b;
+        norm += b * b;
+    }
+
+    let (mut dot, mut norm) = (dot.sum(), norm.sum());
+    for (a, b) in vec1[tail..].iter().zip(&vec2[tail..]) {
+        dot += a * b;
+        norm += b * b;
+    }
+    (dot, norm)
+}
+
+/// Dot product and both squared norms, in one pass
+#[cfg(feature = "simd")]
+pub(crate) fn cosine_terms_f64(vec1: &[f64], vec2: &[f64]) -> (f64, f64, f64) {
+    let tail = vec1.len() - vec1.len() % 2;
+    let mut dot = f64x2::splat(0.0);
+    let mut norm1 = f64x2::splat(0.0);
+    let mut norm2 = f64x2::splat(0.0);
+    for (a, b) in vec1[..tail]
+        .chunks_exact(2)
+        .zip(vec2[..tail].chunks_exact(2))
+    {
+        let (a, b) = (
+            f64x2::from_slice_unaligned(a),
+            f64x2::from_slice_unaligned(b),
+        );
+        dot += a * b;
+        norm1 += a * a;
+        norm2 += b * b;
+    }
+
+    let (mut dot, mut norm1, mut norm2) = (dot.sum(), norm1.sum(), norm2.sum());
+    for (a, b) in vec1[tail..].iter().zip(&vec2[tail..]) {
+        dot += a * b;
+        norm1 += a * a;
+        norm2 += b * b;
+    }
+    (dot, norm1, norm2)
+}
+
+/// Squared euclidean distance between two f64 vectors
+#[cfg(feature = "simd")]
+pub(crate) fn squared_distance_f64(vec1: &[f64], vec2: &[f64]) -> f64 {
+    let tail = vec1.len() - vec1.len() % 2;
+    let mut sum = f64x2::splat(0.0);
+    for (a, b) in vec1[..tail]
+        .chunks_exact(2)
+        .zip(vec2[..tail].chunks_exact(2))
+    {
+        let diff = f64x2::from_slice_unaligned(a) - f64x2::from_slice_unaligned(b);
+        sum += diff * diff;
+    }
+
+    let mut sum = sum.sum();
+    for (a, b) in vec1[tail..].iter().zip(&vec2[tail..]) {
+        let diff = a - b;
+        sum += diff * diff;
+    }
+    sum
+}
@@ -1,5 +1,4 @@
 use wasm_bindgen::prelude::*;
-use web_sys::console;
 
 #[cfg(feature = "simd")]
 use packed_simd::f32x4;
@@ -8,10 +7,106 @@ use packed_simd::f32x4;
 macro_rules! log {
     ($($t:tt)*) => {
         #[cfg(debug_assertions)]
-        console::log_1(&format!($($t)*).into());
+        web_sys::console::log_1(&format!($($t)*).into());
     };
 }
 
+mod accumulator;
+mod benchmark;
+mod bf16;
+mod calibration;
+mod cluster_tree;
+mod cutoff;
+mod dedup;
+mod error;
+mod events;
+mod fixtures;
+#[cfg(feature = "webgpu")]
+mod gpu;
+mod index;
+mod kernels;
+mod mahalanobis;
+mod metric;
+mod mixed;
+mod projection;
+mod prune;
+mod quantization;
+mod query_defaults;
+#[cfg(feature = "remote")]
+mod remote;
+mod rng;
+mod rounding;
+mod rows;
+mod search;
+mod shared;
+mod snapshot;
+mod stats;
+mod streaming;
+mod subset;
+mod summary;
+mod testdata;
+mod trace;
+mod validation;
+
+pub use accumulator::TopKAccumulator;
+pub use benchmark::{
+    BenchmarkComparison, BenchmarkOptions, BenchmarkReport, MemoryDelta, OperationDelta,
+    OperationTiming,
+};
+pub use bf16::{bf16_to_f32_array, bf16_to_f64_array, f32_to_bf16_array};
+pub use calibration::{DistanceTransform, NormalizationMethod, ScoreNormalization};
+pub use cluster_tree::{ClusterTreeParams, ClusterTreeStats};
+pub use cutoff::AutoK;
+pub use dedup::DuplicateGroup;
+pub use error::VectorError;
+pub use events::{set_event_listener, BuildKind, VectorEvent};
+pub use fixtures::{KnownAnswerCase, KnownAnswerSuite};
+#[cfg(feature = "webgpu")]
+pub use gpu::{GpuScorer, GpuScorerOptions};
+#[cfg(feature = "parquet")]
+pub use index::{ColumnSchema, ColumnType, MetadataValue, ParquetExportOptions};
+pub use index::{
+    ConflictPolicy, IndexHit, IndexShape, InsertBatchReport, MaintenanceProgress, NegativeStrategy,
+    RebuildPlan, RebuildReport, RebuildStep, RebuildStepKind, RebuildTarget, RejectReason,
+    RejectedRow, ResultCacheStats, ResultPage, TripletReport, VectorIndex,
+};
+pub use mahalanobis::MahalanobisMetric;
+pub use metric::MetricKind;
+pub use mixed::MixedIndex;
+pub use projection::ProjectionParams;
+pub use quantization::{
+    Calibration, CodecKind, Int8Mode, Int8Overflow, QuantizationOptions, QuantizationStats,
+    MAX_BLOCK_SIZE, MAX_EXACT_DIMENSIONS,
+};
+#[cfg(feature = "remote")]
+pub use remote::{RemoteSnapshot, RemoteSnapshotOptions};
+pub use rounding::ScoreRounding;
+pub use search::{QueryOptions, SearchHit};
+pub use shared::SharedCorpus;
+pub use snapshot::SnapshotOptions;
+pub use stats::{CorpusStats, HistogramBucket};
+pub use summary::SummaryCluster;
+pub use testdata::{ClusteredData, VectorTestData};
+pub use trace::{QueryTrace, ScanStrategy};
+pub use validation::{ValidationMode, ValidationOptions, VectorDefect, VectorProblem};
+
+/// Convert a serializable result into a plain JS object
+pub(crate) fn to_js<T: serde::Serialize>(value: &T) -> Result<JsValue, JsValue> {
+    serde_wasm_bindgen::to_value(value).map_err(JsValue::from)
+}
+
+/// Read an options object from JS, treating `undefined`/`null` as defaults
+pub(crate) fn options_from_js<T>(value: JsValue) -> Result<T, JsValue>
+where
+    T: serde::de::DeserializeOwned + Default,
+{
+    if value.is_undefined() || value.is_null() {
+        Ok(T::default())
+    } else {
+        serde_wasm_bindgen::from_value(value).map_err(JsValue::from)
+    }
+}
+
 #[wasm_bindgen]
 pub struct VectorSearch {
     dimensions: usize,
@@ -25,29 +120,15 @@ impl VectorSearch {
         Self { dimensions }
     }
 
-    /// Calculate cosine similarity between two vectors
+    /// Calculate cosine similarity between two vectors (f64x2 SIMD with the
+    /// `simd` feature)
     #[wasm_bindgen(js_name = "cosineSimilarity")]
     pub fn cosine_similarity(&self, vec1: &[f64], vec2: &[f64]) -> f64 {
         if vec1.len() != vec2.len() || vec1.len() != self.dimensions {
             panic!("Vector dimensions mismatch");
         }
 
-        let mut dot_product = 0.0;
-        let mut norm1 = 0.0;
-        let mut norm2 = 0.0;
-
-        for i in 0..vec1.len() {
-            dot_product += vec1[i] * vec2[i];
-            norm1 += vec1[i] * vec1[i];
-            norm2 += vec2[i] * vec2[i];
-        }
-
-        let magnitude = (norm1.sqrt()) * (norm2.sqrt());
-        if magnitude == 0.0 {
-            0.0
-        } else {
-            dot_product / magnitude
-        }
+        metric::cosine(vec1, vec2)
     }
 
     /// Calculate cosine similarity with SIMD optimization (for f32 vectors)
@@ -96,35 +177,26 @@ impl VectorSearch {
         }
     }
 
-    /// Calculate euclidean distance between two vectors
+    /// Calculate euclidean distance between two vectors (f64x2 SIMD with the
+    /// `simd` feature)
     #[wasm_bindgen(js_name = "euclideanDistance")]
     pub fn euclidean_distance(&self, vec1: &[f64], vec2: &[f64]) -> f64 {
         if vec1.len() != vec2.len() || vec1.len() != self.dimensions {
             panic!("Vector dimensions mismatch");
         }
 
-        let mut sum = 0.0;
-        for i in 0..vec1.len() {
-            let diff = vec1[i] - vec2[i];
-            sum += diff * diff;
-        }
-
-        sum.sqrt()
+        metric::euclidean(vec1, vec2)
     }
 
-    /// Calculate dot product of two vectors
+    /// Calculate dot product of two vectors (f64x2 SIMD with the `simd`
+    /// feature)
     #[wasm_bindgen(js_name = "dotProduct")]
     pub fn dot_product(&self, vec1: &[f64], vec2: &[f64]) -> f64 {
         if vec1.len() != vec2.len() || vec1.len() != self.dimensions {
             panic!("Vector dimensions mismatch");
         }
 
-        let mut product = 0.0;
-        for i in 0..vec1.len() {
-            product += vec1[i] * vec2[i];
-        }
-
-        product
+        metric::dot(vec1, vec2)
     }
 
     /// Normalize a vector
@@ -147,6 +219,62 @@ impl VectorSearch {
         }
     }
 
+    /// Normalize a flattened batch of vectors in place.
+    ///
+    /// Returns the number of zero-norm vectors encountered; those are left
+    /// unchanged, matching `normalizeVector`. Norms and scaling run 2 lanes
+    /// at a time with SIMD.
+    #[wasm_bindgen(js_name = "normalizeBatch")]
+    pub fn normalize_batch(&self, vectors: &mut [f64], count: usize) -> usize {
+        if vectors.len() != count * self.dimensions {
+            panic!("Vectors array size mismatch");
+        }
+
+        // Zero-width vectors have nothing to scale
+        if self.dimensions == 0 {
+            return 0;
+        }
+
+        let mut zero_norms = 0;
+        for vec in vectors.chunks_exact_mut(self.dimensions) {
+            let magnitude = kernels::squared_norm_f64(vec).sqrt();
+
+            if magnitude > 0.0 {
+                kernels::divide_f64(vec, magnitude);
+            } else {
+                zero_norms += 1;
+            }
+        }
+
+        zero_norms
+    }
+
+    /// Normalize a flattened batch of f32 vectors in place with SIMD
+    /// optimization, returning the number of zero-norm vectors encountered
+    #[wasm_bindgen(js_name = "normalizeBatchSIMD")]
+    pub fn normalize_batch_simd(&self, vectors: &mut [f32], count: usize) -> usize {
+        if vectors.len() != count * self.dimensions {
+            panic!("Vectors array size mismatch");
+        }
+
+        if self.dimensions == 0 {
+            return 0;
+        }
+
+        let mut zero_norms = 0;
+        for vec in vectors.chunks_exact_mut(self.dimensions) {
+            let magnitude = Self::squared_norm_f32(vec).sqrt();
+
+            if magnitude > 0.0 {
+                Self::scale_f32(vec, 1.0 / magnitude);
+            } else {
+                zero_norms += 1;
+            }
+        }
+
+        zero_norms
+    }
+
     /// Batch calculate similarities for multiple vectors
     #[wasm_bindgen(js_name = "batchCosineSimilarity")]
     pub fn batch_cosine_similarity(
@@ -163,18 +291,58 @@ impl VectorSearch {
             panic!("Vectors array size mismatch");
         }
 
+        // Zero-width vectors have no magnitude, so every similarity is 0
         let mut similarities = Vec::with_capacity(count);
+        kernels::cosine_rows_f64(query, vectors, &mut similarities);
+        similarities.resize(count, 0.0);
+        similarities
+    }
 
-        for i in 0..count {
-            let start = i * self.dimensions;
-            let end = start + self.dimensions;
-            let vec = &vectors[start..end];
-            similarities.push(self.cosine_similarity(query, vec));
+    /// Batch calculate similarities for multiple f32 vectors with SIMD
+    /// optimization
+    #[wasm_bindgen(js_name = "batchCosineSimilaritySIMD")]
+    pub fn batch_cosine_similarity_simd(
+        &self,
+        query: &[f32],
+        vectors: &[f32],
+        count: usize,
+    ) -> Vec<f32> {
+        if query.len() != self.dimensions {
+            panic!("Query vector dimension mismatch");
         }
 
+        if vectors.len() != count * self.dimensions {
+            panic!("Vectors array size mismatch");
+        }
+
+        // Zero-width vectors have no magnitude, so every similarity is 0
+        let mut similarities = Vec::with_capacity(count);
+        kernels::cosine_rows_f32(query, vectors, &mut similarities);
+        similarities.resize(count, 0.0);
         similarities
     }
 
+    /// Batch calculate similarities of several queries against the same
+    /// vectors, returned row-major by query (`result[q * count + i]`)
+    #[wasm_bindgen(js_name = "batchCosineSimilarityMulti")]
+    pub fn batch_cosine_similarity_multi(
+        &self,
+        queries: &[f64],
+        query_count: usize,
+        vectors: &[f64],
+        count: usize,
+    ) -> Vec<f64> {
+        if queries.len() != query_count * self.dimensions {
+            panic!("Queries array size mismatch");
+        }
+
+        if vectors.len() != count * self.dimensions {
+            panic!("Vectors array size mismatch");
+        }
+
+        kernels::cosine_matrix_f64(queries, vectors, self.dimensions)
+    }
+
     /// Find top K most similar vectors
     #[wasm_bindgen(js_name = "findTopK")]
     pub fn find_top_k(
@@ -203,6 +371,54 @@ impl VectorSearch {
             .collect()
     }
 
+    // Internal helper for the f32 squared norm, 4 lanes at a time with SIMD
+    fn squared_norm_f32(vec: &[f32]) -> f32 {
+        #[cfg(feature = "simd")]
+        {
+            let chunks = vec.len() / 4;
+            let mut norm = f32x4::splat(0.0);
+            for i in 0..chunks {
+                let a = f32x4::from_slice_unaligned(&vec[i * 4..i * 4 + 4]);
+                norm += a * a;
+            }
+
+            let mut norm = norm.sum();
+            for val in &vec[chunks * 4..] {
+                norm += val * val;
+            }
+            norm
+        }
+
+        #[cfg(not(feature = "simd"))]
+        {
+            vec.iter().map(|val| val * val).sum()
+        }
+    }
+
+    // Internal helper scaling an f32 vector in place, 4 lanes at a time with SIMD
+    fn scale_f32(vec: &mut [f32], factor: f32) {
+        #[cfg(feature = "simd")]
+        {
+            let chunks = vec.len() / 4;
+            let scale = f32x4::splat(factor);
+            for i in 0..chunks {
+                let lane = &mut vec[i * 4..i * 4 + 4];
+                (f32x4::from_slice_unaligned(lane) * scale).write_to_slice_unaligned(lane);
+            }
+
+            for val in &mut vec[chunks * 4..] {
+                *val *= factor;
+            }
+        }
+
+        #[cfg(not(feature = "simd"))]
+        {
+            for val in vec.iter_mut() {
+                *val *= factor;
+            }
+        }
+    }
+
     // Internal helper for f32 cosine similarity without SIMD
     fn cosine_similarity_f32(&self, vec1: &[f32], vec2: &[f32]) -> f32 {
         let mut dot_product = 0.0;
@@ -0,0 +1,372 @@
+//! Mahalanobis distance under a fitted or supplied covariance.
+//!
+//! `sqrt((x - y)ᵀ Σ⁻¹ (x - y))` measures distance in units of the data's own
+//! spread, so a point off the usual correlation structure stands out even
+//! when its Euclidean distance is small; that makes it a natural anomaly
+//! score. The matrix is factored once with Cholesky and kept. A vector is
+//! then whitened with one triangular solve (or product, for a precision
+//! matrix), after which the distance is plain Euclidean.
+
+use wasm_bindgen::prelude::*;
+
+use crate::error::VectorError;
+use crate::metric::MetricKind;
+use crate::search::{SearchHit, TopK};
+use crate::to_js;
+
+/// Lower-triangular Cholesky factor `L`, row-major
+#[derive(Clone, Debug)]
+enum Factor {
+    /// `Σ = L Lᵀ`; whitening solves `L z = v`
+    Covariance(Vec<f64>),
+    /// `Σ⁻¹ = L Lᵀ`; whitening computes `z = Lᵀ v`
+    Precision(Vec<f64>),
+}
+
+/// Factor a symmetric positive-definite `dimensions`² matrix as `L Lᵀ`
+fn cholesky(matrix: &[f64], dimensions: usize) -> Result<Vec<f64>, VectorError> {
+    let d = dimensions;
+    let mut l = vec![0.0; d * d];
+    for i in 0..d {
+        for j in 0..=i {
+            let dot: f64 = (0..j).map(|k| l[i * d + k] * l[j * d + k]).sum();
+            let value = matrix[i * d + j] - dot;
+            if i == j {
+                if value <= 0.0 || !value.is_finite() {
+                    return Err(VectorError::NotPositiveDefinite { dimension: i });
+                }
+                l[i * d + i] = value.sqrt();
+            } else {
+                l[i * d + j] = value / l[j * d + j];
+            }
+        }
+    }
+    Ok(l)
+}
+
+/// Mahalanobis distance with a cached Cholesky factor
+#[wasm_bindgen]
+pub struct MahalanobisMetric {
+    dimensions: usize,
+    factor: Factor,
+    /// Center for `anomalyScores`: the fitted mean, or the origin
+    mean: Vec<f64>,
+}
+
+#[wasm_bindgen]
+impl MahalanobisMetric {
+    /// Estimate mean and covariance from `count` flattened vectors. The
+    /// sample covariance gets `regularization` added to its diagonal, which
+    /// keeps it invertible when there are fewer vectors than dimensions.
+    pub fn fit(
+        vectors: &[f64],
+        count: usize,
+        dimensions: usize,
+        regularization: f64,
+    ) -> Result<MahalanobisMetric, JsValue> {
+        Ok(Self::fitted(vectors, count, dimensions, regularization)?)
+    }
+
+    /// Use a given `dimensions`² covariance matrix (row-major), centered on
+    /// `mean` or the origin
+    #[wasm_bindgen(js_name = "fromCovariance")]
+    pub fn from_covariance(
+        covariance: &[f64],
+        dimensions: usize,
+        mean: Option<Vec<f64>>,
+    ) -> Result<MahalanobisMetric, JsValue> {
+        Ok(Self::factored(
+            covariance,
+            dimensions,
+            mean,
+            Factor::Covariance,
+        )?)
+    }
+
+    /// Use a given `dimensions`² precision (inverse covariance) matrix,
+    /// skipping the inversion
+    #[wasm_bindgen(js_name = "fromPrecision")]
+    pub fn from_precision(
+        precision: &[f64],
+        dimensions: usize,
+        mean: Option<Vec<f64>>,
+    ) -> Result<MahalanobisMetric, JsValue> {
+        Ok(Self::factored(
+            precision,
+            dimensions,
+            mean,
+            Factor::Precision,
+        )?)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    /// The center `anomalyScores` measures from
+    #[wasm_bindgen(getter)]
+    pub fn mean(&self) -> Vec<f64> {
+        self.mean.clone()
+    }
+
+    /// Mahalanobis distance between two vectors
+    pub fn distance(&self, a: &[f64], b: &[f64]) -> f64 {
+        if a.len() != self.dimensions || b.len() != self.dimensions {
+            panic!("Vector dimension mismatch");
+        }
+        let difference: Vec<f64> = a.iter().zip(b).map(|(x, y)| x - y).collect();
+        norm(&self.whitened(&difference))
+    }
+
+    /// Distance from `query` to each of `count` flattened vectors
+    #[wasm_bindgen(js_name = "batchDistance")]
+    pub fn batch_distance(&self, query: &[f64], vectors: &[f64], count: usize) -> Vec<f64> {
+        self.distances(query, vectors, count)
+    }
+
+    /// The `k` vectors nearest to `query`, as `{ index, score }` objects with
+    /// the distance as score, nearest first
+    #[wasm_bindgen(js_name = "findTopK")]
+    pub fn find_top_k(
+        &self,
+        query: &[f64],
+        vectors: &[f64],
+        count: usize,
+        k: usize,
+    ) -> Result<JsValue, JsValue> {
+        to_js(&self.nearest(query, vectors, count, k))
+    }
+
+    /// Distance of each of `count` flattened vectors from `mean`; large
+    /// values flag outliers
+    #[wasm_bindgen(js_name = "anomalyScores")]
+    pub fn anomaly_scores(&self, vectors: &[f64], count: usize) -> Vec<f64> {
+        self.distances(&self.mean, vectors, count)
+    }
+
+    /// Map `count` flattened vectors into the space where this distance is
+    /// Euclidean, so whitened copies can be cached and searched with
+    /// `metric: "euclidean"`
+    pub fn whiten(&self, vectors: &[f64], count: usize) -> Vec<f64> {
+        if vectors.len() != count * self.dimensions {
+            panic!("Vectors array size mismatch");
+        }
+        vectors
+            .chunks_exact(self.dimensions.max(1))
+            .flat_map(|row| self.whitened(row))
+            .collect()
+    }
+}
+
+impl MahalanobisMetric {
+    pub(crate) fn fitted(
+        vectors: &[f64],
+        count: usize,
+        dimensions: usize,
+        regularization: f64,
+    ) -> Result<Self, VectorError> {
+        if vectors.len() != count * dimensions {
+            panic!("Vectors array size mismatch");
+        }
+        let d = dimensions;
+
+        let mut mean = vec![0.0; d];
+        for row in vectors.chunks_exact(d.max(1)) {
+            for (m, &x) in mean.iter_mut().zip(row) {
+                *m += x;
+            }
+        }
+        for m in &mut mean {
+            *m /= count.max(1) as f64;
+        }
+
+        let mut covariance = vec![0.0; d * d];
+        for row in vectors.chunks_exact(d.max(1)) {
+            for i in 0..d {
+                let di = row[i] - mean[i];
+                for j in 0..=i {
+                    covariance[i * d + j] += di * (row[j] - mean[j]);
+                }
+            }
+        }
+        let scale = 1.0 / count.saturating_sub(1).max(1) as f64;
+        for i in 0..d {
+            for j in 0..=i {
+                covariance[i * d + j] *= scale;
+                covariance[j * d + i] = covariance[i * d + j];
+            }
+            covariance[i * d + i] += regularization;
+        }
+
+        Ok(Self {
+            dimensions,
+            factor: Factor::Covariance(cholesky(&covariance, d)?),
+            mean,
+        })
+    }
+
+    fn factored(
+        matrix: &[f64],
+        dimensions: usize,
+        mean: Option<Vec<f64>>,
+        kind: fn(Vec<f64>) -> Factor,
+    ) -> Result<Self, VectorError> {
+        if matrix.len() != dimensions * dimensions {
+            panic!("Matrix size mismatch");
+        }
+        let mean = mean.unwrap_or_else(|| vec![0.0; dimensions]);
+        if mean.len() != dimensions {
+            panic!("Mean vector dimension mismatch");
+        }
+
+        Ok(Self {
+            dimensions,
+            factor: kind(cholesky(matrix, dimensions)?),
+            mean,
+        })
+    }
+
+    /// `v` in whitened coordinates, where the distance is Euclidean
+    fn whitened(&self, v: &[f64]) -> Vec<f64> {
+        let d = self.dimensions;
+        match &self.factor {
+            Factor::Covariance(l) => {
+                let mut z = vec![0.0; d];
+                for i in 0..d {
+                    let dot: f64 = (0..i).map(|j| l[i * d + j] * z[j]).sum();
+                    z[i] = (v[i] - dot) / l[i * d + i];
+                }
+                z
+            }
+            Factor::Precision(l) => (0..d)
+                .map(|i| (i..d).map(|j| l[j * d + i] * v[j]).sum())
+                .collect(),
+        }
+    }
+
+    pub(crate) fn distances(&self, query: &[f64], vectors: &[f64], count: usize) -> Vec<f64> {
+        if query.len() != self.dimensions {
+            panic!("Query vector dimension mismatch");
+        }
+        let query = self.whitened(query);
+        self.whiten(vectors, count)
+            .chunks_exact(self.dimensions.max(1))
+            .map(|row| {
+                row.iter()
+                    .zip(&query)
+                    .map(|(x, q)| (x - q) * (x - q))
+                    .sum::<f64>()
+                    .sqrt()
+            })
+            .collect()
+    }
+
+    pub(crate) fn nearest(
+        &self,
+        query: &[f64],
+        vectors: &[f64],
+        count: usize,
+        k: usize,
+    ) -> Vec<SearchHit> {
+        let mut top = TopK::new(k, MetricKind::Euclidean);
+        for (index, distance) in self
+            .distances(query, vectors, count)
+            .into_iter()
+            .enumerate()
+        {
+            top.push(index, distance);
+        }
+        top.into_sorted()
+            .into_iter()
+            .map(|(index, score)| SearchHit {
+                index,
+                score,
+                normalized: None,
+            })
+            .collect()
+    }
+}
+
+fn norm(v: &[f64]) -> f64 {
+    v.iter().map(|x| x * x).sum::<f64>().sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Symmetric positive-definite matrix with an integer Cholesky factor
+    const COVARIANCE: [f64; 9] = [4.0, 12.0, -16.0, 12.0, 37.0, -43.0, -16.0, -43.0, 98.0];
+    const FACTOR: [f64; 9] = [2.0, 0.0, 0.0, 6.0, 1.0, 0.0, -8.0, 5.0, 3.0];
+    /// `COVARIANCE⁻¹ * 36`
+    const INVERSE_36: [f64; 9] = [1777.0, -488.0, 76.0, -488.0, 136.0, -20.0, 76.0, -20.0, 4.0];
+
+    fn quadratic_form(matrix: &[f64], v: &[f64]) -> f64 {
+        let d = v.len();
+        (0..d)
+            .map(|i| v[i] * (0..d).map(|j| matrix[i * d + j] * v[j]).sum::<f64>())
+            .sum()
+    }
+
+    #[test]
+    fn cholesky_matches_known_factor() {
+        let l = cholesky(&COVARIANCE, 3).unwrap();
+        for (actual, expected) in l.iter().zip(&FACTOR) {
+            assert!((actual - expected).abs() < 1e-12);
+        }
+
+        let indefinite = [1.0, 2.0, 2.0, 1.0];
+        assert!(matches!(
+            cholesky(&indefinite, 2),
+            Err(VectorError::NotPositiveDefinite { dimension: 1 })
+        ));
+    }
+
+    #[test]
+    fn whitening_applies_the_known_inverse() {
+        let inverse: Vec<f64> = INVERSE_36.iter().map(|x| x / 36.0).collect();
+        let covariance =
+            MahalanobisMetric::factored(&COVARIANCE, 3, None, Factor::Covariance).unwrap();
+        let precision = MahalanobisMetric::factored(&inverse, 3, None, Factor::Precision).unwrap();
+
+        for v in [[1.0, 0.0, 0.0], [0.5, -2.0, 1.5], [-3.0, 0.25, 0.75]] {
+            let expected = quadratic_form(&inverse, &v);
+            for metric in [&covariance, &precision] {
+                let z = metric.whitened(&v);
+                let squared = z.iter().map(|x| x * x).sum::<f64>();
+                assert!(
+                    (squared - expected).abs() <= 1e-9 * expected,
+                    "{} vs {}",
+                    squared,
+                    expected
+                );
+            }
+            assert!((covariance.distance(&v, &[0.0; 3]) - expected.sqrt()).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn fitted_whitening_decorrelates() {
+        let mut vectors = Vec::new();
+        for i in 0..500 {
+            let t = (i as f64 * 0.37).sin() * 3.0;
+            vectors.extend([t, 0.5 * t + (i as f64 * 1.3).cos()]);
+        }
+        let metric = MahalanobisMetric::fitted(&vectors, 500, 2, 0.0).unwrap();
+        let white = metric.whiten(&vectors, 500);
+
+        let mean = metric.whitened(&metric.mean);
+        let mut covariance = [0.0; 4];
+        for row in white.chunks_exact(2) {
+            for i in 0..2 {
+                for j in 0..2 {
+                    covariance[i * 2 + j] += (row[i] - mean[i]) * (row[j] - mean[j]) / 499.0;
+                }
+            }
+        }
+        for (actual, expected) in covariance.iter().zip(&[1.0, 0.0, 0.0, 1.0]) {
+            assert!((actual - expected).abs() < 1e-9, "{:?}", covariance);
+        }
+    }
+}
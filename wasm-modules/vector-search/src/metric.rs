@@ -0,0 +1,428 @@
+use std::cell::RefCell;
+
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::error::VectorError;
+#[cfg(feature = "simd")]
+use crate::kernels;
+use crate::VectorSearch;
+
+/// Similarity or distance function used to rank candidates
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum MetricKind {
+    #[default]
+    Cosine,
+    Euclidean,
+    Dot,
+    /// Similarity computed by a JS callback passed as `customMetric`; higher
+    /// is better. The callback crosses the JS boundary once per scored pair,
+    /// so expect it to be orders of magnitude slower than the built-ins. If
+    /// it throws, the scan stops and the exception is rethrown
+    Custom,
+}
+
+/// Vector element types the scoring kernels accept; accumulation is always f64.
+///
+/// The unweighted pair kernels are trait methods so f64 can swap in its
+/// f64x2 SIMD versions; the defaults are scalar.
+pub(crate) trait Element: Copy + Into<f64> {
+    fn dot(vec1: &[Self], vec2: &[Self]) -> f64 {
+        vec1.iter()
+            .zip(vec2)
+            .map(|(&a, &b)| a.into() * b.into())
+            .sum()
+    }
+
+    /// Dot product and the squared norm of `vec2`
+    fn dot_and_norm(vec1: &[Self], vec2: &[Self]) -> (f64, f64) {
+        let mut dot_product = 0.0;
+        let mut norm = 0.0;
+        for (&a, &b) in vec1.iter().zip(vec2) {
+            let (a, b): (f64, f64) = (a.into(), b.into());
+            dot_product += a * b;
+            norm += b * b;
+        }
+        (dot_product, norm)
+    }
+
+    /// Dot product and both squared norms
+    fn cosine_terms(vec1: &[Self], vec2: &[Self]) -> (f64, f64, f64) {
+        let mut dot_product = 0.0;
+        let mut norm1 = 0.0;
+        let mut norm2 = 0.0;
+        for (&a, &b) in vec1.iter().zip(vec2) {
+            let (a, b): (f64, f64) = (a.into(), b.into());
+            dot_product += a * b;
+            norm1 += a * a;
+            norm2 += b * b;
+        }
+        (dot_product, norm1, norm2)
+    }
+
+    fn squared_distance(vec1: &[Self], vec2: &[Self]) -> f64 {
+        vec1.iter()
+            .zip(vec2)
+            .map(|(&a, &b)| {
+                let diff = a.into() - b.into();
+                diff * diff
+            })
+            .sum()
+    }
+}
+
+impl Element for f32 {}
+
+#[cfg(not(feature = "simd"))]
+impl Element for f64 {}
+
+#[cfg(feature = "simd")]
+impl Element for f64 {
+    fn dot(vec1: &[f64], vec2: &[f64]) -> f64 {
+        kernels::dot_f64(vec1, vec2)
+    }
+
+    fn dot_and_norm(vec1: &[f64], vec2: &[f64]) -> (f64, f64) {
+        kernels::dot_and_norm_f64(vec1, vec2)
+    }
+
+    fn cosine_terms(vec1: &[f64], vec2: &[f64]) -> (f64, f64, f64) {
+        kernels::cosine_terms_f64(vec1, vec2)
+    }
+
+    fn squared_distance(vec1: &[f64], vec2: &[f64]) -> f64 {
+        kernels::squared_distance_f64(vec1, vec2)
+    }
+}
+
+impl MetricKind {
+    /// Whether larger scores rank first (similarities) or last (distances)
+    pub(crate) fn higher_is_better(self) -> bool {
+        !matches!(self, MetricKind::Euclidean)
+    }
+
+    /// Score a pair of vectors, optionally weighting each dimension
+    pub(crate) fn score<T: Element>(self, vec1: &[T], vec2: &[T], weights: Option<&[f64]>) -> f64 {
+        match (self, weights) {
+            (MetricKind::Cosine, None) => cosine(vec1, vec2),
+            (MetricKind::Euclidean, None) => euclidean(vec1, vec2),
+            (MetricKind::Dot, None) => dot(vec1, vec2),
+            (MetricKind::Cosine, Some(w)) => weighted_cosine(vec1, vec2, w),
+            (MetricKind::Euclidean, Some(w)) => weighted_euclidean(vec1, vec2, w),
+            (MetricKind::Dot, Some(w)) => weighted_dot(vec1, vec2, w),
+            (MetricKind::Custom, _) => panic!("Custom metric requires a customMetric callback"),
+        }
+    }
+}
+
+pub(crate) fn dot<T: Element>(vec1: &[T], vec2: &[T]) -> f64 {
+    T::dot(vec1, vec2)
+}
+
+pub(crate) fn cosine<T: Element>(vec1: &[T], vec2: &[T]) -> f64 {
+    let (dot_product, norm1, norm2) = T::cosine_terms(vec1, vec2);
+
+    let magnitude = norm1.sqrt() * norm2.sqrt();
+    if magnitude == 0.0 {
+        0.0
+    } else {
+        dot_product / magnitude
+    }
+}
+
+pub(crate) fn euclidean<T: Element>(vec1: &[T], vec2: &[T]) -> f64 {
+    T::squared_distance(vec1, vec2).sqrt()
+}
+
+pub(crate) fn weighted_dot<T: Element>(vec1: &[T], vec2: &[T], weights: &[f64]) -> f64 {
+    vec1.iter()
+        .zip(vec2)
+        .zip(weights)
+        .map(|((&a, &b), w)| w * a.into() * b.into())
+        .sum()
+}
+
+pub(crate) fn weighted_cosine<T: Element>(vec1: &[T], vec2: &[T], weights: &[f64]) -> f64 {
+    let mut dot_product = 0.0;
+    let mut norm1 = 0.0;
+    let mut norm2 = 0.0;
+
+    for ((&a, &b), w) in vec1.iter().zip(vec2).zip(weights) {
+        let (a, b): (f64, f64) = (a.into(), b.into());
+        dot_product += w * a * b;
+        norm1 += w * a * a;
+        norm2 += w * b * b;
+    }
+
+    let magnitude = norm1.sqrt() * norm2.sqrt();
+    if magnitude == 0.0 {
+        0.0
+    } else {
+        dot_product / magnitude
+    }
+}
+
+pub(crate) fn weighted_euclidean<T: Element>(vec1: &[T], vec2: &[T], weights: &[f64]) -> f64 {
+    vec1.iter()
+        .zip(vec2)
+        .zip(weights)
+        .map(|((&a, &b), w)| {
+            let diff = a.into() - b.into();
+            w * diff * diff
+        })
+        .sum::<f64>()
+        .sqrt()
+}
+
+/// Per-query scoring state, so work that only depends on the query (its norm)
+/// is done once rather than once per candidate
+pub(crate) struct Scorer<'a, T: Element> {
+    /// The query, or its scored prefix when truncated
+    query: &'a [T],
+    /// Length of candidates, which are cut to the query's length
+    dimensions: usize,
+    metric: MetricKind,
+    weights: Option<&'a [f64]>,
+    query_norm: f64,
+    assume_normalized: bool,
+    custom: Option<CustomScorer<'a>>,
+}
+
+/// JS callback and the query already copied into a typed array for it
+struct CustomScorer<'a> {
+    function: &'a js_sys::Function,
+    query: js_sys::Float64Array,
+    weights: JsValue,
+    /// The first exception the callback threw
+    error: RefCell<Option<JsValue>>,
+}
+
+impl CustomScorer<'_> {
+    fn score<T: Element>(&self, candidate: &[T]) -> f64 {
+        if self.error.borrow().is_some() {
+            return f64::NEG_INFINITY;
+        }
+
+        let candidate: Vec<f64> = candidate.iter().map(|&x| x.into()).collect();
+        let candidate = js_sys::Float64Array::from(&candidate[..]);
+
+        let result = self
+            .function
+            .call3(&JsValue::NULL, &self.query, &candidate, &self.weights);
+        let score = match result {
+            Ok(score) => score,
+            Err(error) => {
+                *self.error.borrow_mut() = Some(error);
+                return f64::NEG_INFINITY;
+            }
+        };
+
+        // Non-numeric or NaN results rank last rather than poisoning the sort
+        match score.as_f64() {
+            Some(score) if !score.is_nan() => score,
+            _ => f64::NEG_INFINITY,
+        }
+    }
+}
+
+impl<'a, T: Element> Scorer<'a, T> {
+    /// `assume_normalized` declares that query and candidates are unit
+    /// vectors, which reduces unweighted cosine to a dot product
+    pub(crate) fn new(
+        query: &'a [T],
+        metric: MetricKind,
+        weights: Option<&'a [f64]>,
+        assume_normalized: bool,
+    ) -> Self {
+        let skip_norms = metric != MetricKind::Cosine || (assume_normalized && weights.is_none());
+        let query_norm = if skip_norms {
+            1.0
+        } else {
+            match weights {
+                Some(w) => weighted_dot(query, query, w).sqrt(),
+                None => dot(query, query).sqrt(),
+            }
+        };
+
+        Self {
+            query,
+            dimensions: query.len(),
+            metric,
+            weights,
+            query_norm,
+            assume_normalized,
+            custom: None,
+        }
+    }
+
+    /// Score only the first `dims` dimensions of query and candidates, for
+    /// Matryoshka (MRL) embeddings whose prefixes are embeddings in their
+    /// own right. Call before `with_callback`.
+    ///
+    /// Prefixes of unit vectors aren't unit vectors, so `assume_normalized`
+    /// is dropped, and norms cached for whole candidates no longer apply.
+    pub(crate) fn truncated(self, dims: Option<usize>) -> Self {
+        let Some(dims) = dims else {
+            return self;
+        };
+        if dims == 0 {
+            panic!("searchDims must be at least 1");
+        }
+        if dims >= self.dimensions {
+            return self;
+        }
+
+        let weights = self.weights.map(|w| &w[..dims]);
+        Self {
+            dimensions: self.dimensions,
+            ..Self::new(&self.query[..dims], self.metric, weights, false)
+        }
+    }
+
+    /// Attach the callback used by `MetricKind::Custom`, called as
+    /// `callback(query, candidate, weights)` with `Float64Array`s (`weights`
+    /// is `undefined` when unset)
+    pub(crate) fn with_callback(mut self, function: Option<&'a js_sys::Function>) -> Self {
+        if self.metric == MetricKind::Custom {
+            let function = function.expect("Custom metric requires a customMetric callback");
+            let query: Vec<f64> = self.query.iter().map(|&x| x.into()).collect();
+            let weights = match self.weights {
+                Some(w) => js_sys::Float64Array::from(w).into(),
+                None => JsValue::UNDEFINED,
+            };
+
+            self.custom = Some(CustomScorer {
+                function,
+                query: js_sys::Float64Array::from(&query[..]),
+                weights,
+                error: RefCell::new(None),
+            });
+        }
+        self
+    }
+
+    pub(crate) fn query(&self) -> &'a [T] {
+        self.query
+    }
+
+    /// Length of the candidates being scored
+    pub(crate) fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    pub(crate) fn metric(&self) -> MetricKind {
+        self.metric
+    }
+
+    /// Fails with the first exception the `customMetric` callback threw.
+    /// From then on every score is `-Infinity` without calling back, so
+    /// scans check this as they go and stop.
+    pub(crate) fn check(&self) -> Result<(), VectorError> {
+        let error = self
+            .custom
+            .as_ref()
+            .and_then(|custom| custom.error.borrow().clone());
+        match error {
+            Some(error) => Err(VectorError::CallbackFailed { error }),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn score(&self, candidate: &[T]) -> f64 {
+        let candidate = &candidate[..self.query.len()];
+        if let Some(custom) = &self.custom {
+            return custom.score(candidate);
+        }
+
+        match (self.metric, self.weights) {
+            (MetricKind::Cosine, None) if self.assume_normalized => dot(self.query, candidate),
+            (MetricKind::Cosine, weights) => {
+                fused_cosine(self.query, self.query_norm, candidate, weights)
+            }
+            (metric, weights) => metric.score(self.query, candidate, weights),
+        }
+    }
+
+    /// Unweighted cosine against a candidate whose norm is already known,
+    /// leaving only the dot product to compute
+    pub(crate) fn score_with_norm(&self, candidate: &[T], candidate_norm: f64) -> f64 {
+        let magnitude = self.query_norm * candidate_norm;
+        if magnitude == 0.0 {
+            0.0
+        } else {
+            dot(self.query, candidate) / magnitude
+        }
+    }
+}
+
+/// Cosine against a query whose norm is already known, accumulating the dot
+/// product and the candidate norm in a single pass
+pub(crate) fn fused_cosine<T: Element>(
+    query: &[T],
+    query_norm: f64,
+    candidate: &[T],
+    weights: Option<&[f64]>,
+) -> f64 {
+    if query_norm == 0.0 {
+        return 0.0;
+    }
+
+    let (dot_product, norm) = match weights {
+        Some(weights) => {
+            let mut dot_product = 0.0;
+            let mut norm = 0.0;
+            for ((&a, &b), w) in query.iter().zip(candidate).zip(weights) {
+                let (a, b): (f64, f64) = (a.into(), b.into());
+                dot_product += w * a * b;
+                norm += w * b * b;
+            }
+            (dot_product, norm)
+        }
+        None => T::dot_and_norm(query, candidate),
+    };
+
+    if norm == 0.0 {
+        0.0
+    } else {
+        dot_product / (query_norm * norm.sqrt())
+    }
+}
+
+#[wasm_bindgen]
+impl VectorSearch {
+    /// Calculate cosine similarity with a per-dimension weight applied to
+    /// both vectors, i.e. the cosine of `sqrt(w) * vec1` and `sqrt(w) * vec2`
+    #[wasm_bindgen(js_name = "weightedCosineSimilarity")]
+    pub fn weighted_cosine_similarity(&self, vec1: &[f64], vec2: &[f64], weights: &[f64]) -> f64 {
+        self.check_weighted_dimensions(vec1, vec2, weights);
+        weighted_cosine(vec1, vec2, weights)
+    }
+
+    /// Calculate euclidean distance with each squared difference scaled by
+    /// its dimension weight
+    #[wasm_bindgen(js_name = "weightedEuclideanDistance")]
+    pub fn weighted_euclidean_distance(&self, vec1: &[f64], vec2: &[f64], weights: &[f64]) -> f64 {
+        self.check_weighted_dimensions(vec1, vec2, weights);
+        weighted_euclidean(vec1, vec2, weights)
+    }
+
+    /// Calculate dot product with each term scaled by its dimension weight
+    #[wasm_bindgen(js_name = "weightedDotProduct")]
+    pub fn weighted_dot_product(&self, vec1: &[f64], vec2: &[f64], weights: &[f64]) -> f64 {
+        self.check_weighted_dimensions(vec1, vec2, weights);
+        weighted_dot(vec1, vec2, weights)
+    }
+}
+
+impl VectorSearch {
+    fn check_weighted_dimensions(&self, vec1: &[f64], vec2: &[f64], weights: &[f64]) {
+        if vec1.len() != vec2.len() || vec1.len() != self.dimensions {
+            panic!("Vector dimensions mismatch");
+        }
+
+        if weights.len() != self.dimensions {
+            panic!("Weight vector dimension mismatch");
+        }
+    }
+}
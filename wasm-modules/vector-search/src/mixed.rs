@@ -0,0 +1,260 @@
+//! Collections mixing vectors from embedding models of different widths.
+//!
+//! Rather than zero-padding every record to the widest model, which wastes
+//! memory and lets padded lanes dilute the SIMD kernels, records are kept in
+//! one `VectorIndex` per dimensionality. Each group has its own storage,
+//! norm table, cluster tree and codes, and queries are routed to the group
+//! matching their length.
+
+use std::collections::{BTreeMap, HashMap};
+
+use wasm_bindgen::prelude::*;
+
+use crate::cluster_tree::ClusterTreeParams;
+use crate::error::VectorError;
+use crate::index::{VectorIndex, DEFAULT_BATCH_SIZE};
+use crate::quantization::QuantizationOptions;
+use crate::query_defaults::QueryDefaults;
+use crate::validation::ValidationOptions;
+use crate::{options_from_js, to_js};
+
+/// Vectors of several dimensionalities addressed by one ID space.
+///
+/// An ID lives in exactly one group: re-inserting it with a vector of a
+/// different length moves it, e.g. when a record is re-embedded with a
+/// newer model.
+#[wasm_bindgen]
+pub struct MixedIndex {
+    groups: BTreeMap<usize, VectorIndex>,
+    /// Group each stored ID belongs to
+    dimensions_of: HashMap<u32, usize>,
+    /// Settings applied to every group, including ones created later
+    batch_size: Option<usize>,
+    assume_normalized: bool,
+    validation: ValidationOptions,
+    /// Options every query starts from before its own overrides
+    query_defaults: QueryDefaults,
+}
+
+impl Default for MixedIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen]
+impl MixedIndex {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self {
+            groups: BTreeMap::new(),
+            dimensions_of: HashMap::new(),
+            batch_size: None,
+            assume_normalized: false,
+            validation: ValidationOptions::default(),
+            query_defaults: QueryDefaults::default(),
+        }
+    }
+
+    /// Number of stored vectors across all groups
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> usize {
+        self.dimensions_of.len()
+    }
+
+    /// Dimensionalities that have a group, in ascending order. Groups are
+    /// kept once created, even when emptied, so their cluster tree and
+    /// codes survive records moving out and back in.
+    #[wasm_bindgen(getter)]
+    pub fn groups(&self) -> Vec<usize> {
+        self.groups.keys().copied().collect()
+    }
+
+    /// Number of vectors stored with `dimensions` dimensions
+    #[wasm_bindgen(js_name = "groupSize")]
+    pub fn group_size(&self, dimensions: usize) -> usize {
+        self.groups.get(&dimensions).map_or(0, VectorIndex::size)
+    }
+
+    /// Dimensionality of the vector stored under `id`, if any
+    #[wasm_bindgen(js_name = "dimensionsOf")]
+    pub fn dimensions_of(&self, id: u32) -> Option<usize> {
+        self.dimensions_of.get(&id).copied()
+    }
+
+    /// Number of pending updates that triggers an automatic flush, per group
+    #[wasm_bindgen(getter, js_name = "batchSize")]
+    pub fn batch_size(&self) -> usize {
+        self.batch_size.unwrap_or(DEFAULT_BATCH_SIZE)
+    }
+
+    #[wasm_bindgen(setter, js_name = "batchSize")]
+    pub fn set_batch_size(&mut self, value: usize) {
+        self.batch_size = Some(value);
+        for group in self.groups.values_mut() {
+            group.set_batch_size(value);
+        }
+    }
+
+    /// Whether stored vectors and queries are unit vectors, in every group
+    #[wasm_bindgen(getter, js_name = "assumeNormalized")]
+    pub fn assume_normalized(&self) -> bool {
+        self.assume_normalized
+    }
+
+    #[wasm_bindgen(setter, js_name = "assumeNormalized")]
+    pub fn set_assume_normalized(&mut self, value: bool) {
+        self.assume_normalized = value;
+        for group in self.groups.values_mut() {
+            group.set_assume_normalized(value);
+        }
+    }
+
+    /// Validate inserted vectors and queries in every group, as
+    /// `VectorIndex.setValidation`
+    #[wasm_bindgen(js_name = "setValidation")]
+    pub fn set_validation(&mut self, options: JsValue) -> Result<(), JsValue> {
+        self.validation = options_from_js(options)?;
+        for group in self.groups.values_mut() {
+            group.set_validation_options(self.validation.clone());
+        }
+        Ok(())
+    }
+
+    /// Query options applied to every query, as
+    /// `VectorIndex.setQueryDefaults`
+    #[wasm_bindgen(js_name = "setQueryDefaults")]
+    pub fn set_query_defaults(&mut self, options: JsValue) -> Result<(), JsValue> {
+        self.query_defaults = QueryDefaults::from_js(options)?;
+        Ok(())
+    }
+
+    /// Query options as resolved before per-query overrides, as
+    /// `VectorIndex.queryDefaults`
+    #[wasm_bindgen(js_name = "queryDefaults")]
+    pub fn query_defaults(&self) -> Result<JsValue, JsValue> {
+        self.query_defaults.to_js()
+    }
+
+    /// Insert a vector into the group matching its length, replacing any
+    /// existing vector with the same ID
+    pub fn insert(&mut self, id: u32, vector: &[f32]) -> Result<(), JsValue> {
+        if vector.is_empty() {
+            panic!("Vector must have at least one dimension");
+        }
+
+        let dimensions = vector.len();
+        let (batch_size, assume_normalized) = (self.batch_size, self.assume_normalized);
+        let validation = &self.validation;
+        self.groups
+            .entry(dimensions)
+            .or_insert_with(|| {
+                let mut group = VectorIndex::new(dimensions);
+                if let Some(batch_size) = batch_size {
+                    group.set_batch_size(batch_size);
+                }
+                group.set_assume_normalized(assume_normalized);
+                group.set_validation_options(validation.clone());
+                group
+            })
+            .insert(id, vector)?;
+
+        if let Some(previous) = self.dimensions_of.insert(id, dimensions) {
+            if previous != dimensions {
+                if let Some(group) = self.groups.get_mut(&previous) {
+                    group.remove(id);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove a vector by ID, returning whether it existed
+    pub fn remove(&mut self, id: u32) -> bool {
+        match self.dimensions_of.remove(&id) {
+            Some(dimensions) => self
+                .groups
+                .get_mut(&dimensions)
+                .is_some_and(|group| group.remove(id)),
+            None => false,
+        }
+    }
+
+    /// Whether a vector with this ID is stored in any group
+    pub fn contains(&self, id: u32) -> bool {
+        self.dimensions_of.contains_key(&id)
+    }
+
+    /// Flush pending updates in every group, returning how many records were
+    /// updated
+    pub fn flush(&mut self) -> usize {
+        self.groups.values_mut().map(VectorIndex::flush).sum()
+    }
+
+    /// Build a cluster tree over one group, as `VectorIndex.buildClusterTree`
+    #[wasm_bindgen(js_name = "buildClusterTree")]
+    pub fn build_cluster_tree(
+        &mut self,
+        dimensions: usize,
+        params: JsValue,
+    ) -> Result<JsValue, JsValue> {
+        let params: ClusterTreeParams = options_from_js(params)?;
+        to_js(&self.group_mut(dimensions)?.build_tree(params))
+    }
+
+    /// Train compressed codes for one group, as
+    /// `VectorIndex.enableQuantization`
+    #[wasm_bindgen(js_name = "enableQuantization")]
+    pub fn enable_quantization(
+        &mut self,
+        dimensions: usize,
+        options: JsValue,
+    ) -> Result<JsValue, JsValue> {
+        let options: QuantizationOptions = options_from_js(options)?;
+        to_js(&self.group_mut(dimensions)?.quantize(&options)?)
+    }
+
+    /// Return the first page of results from the group matching the query's
+    /// length, as `VectorIndex.search`.
+    ///
+    /// Fails with an `UnknownGroup` error when no vectors of that length are
+    /// stored, which usually means the query came from the wrong model.
+    pub fn search(&self, query: &[f32], k: usize, options: JsValue) -> Result<JsValue, JsValue> {
+        let group = self.group(query.len())?;
+        let query = self.validation.check(query, None)?;
+        let options = self.query_defaults.resolve(options)?;
+        to_js(&group.page(&query, 0, k, &options)?)
+    }
+
+    /// Return a later page of a result set started by `search`. Sequences
+    /// are tracked per group, so writes to other groups do not invalidate it.
+    #[wasm_bindgen(js_name = "searchPage")]
+    pub fn search_page(
+        &self,
+        query: &[f32],
+        offset: usize,
+        page_size: usize,
+        sequence: f64,
+        options: JsValue,
+    ) -> Result<JsValue, JsValue> {
+        let group = self.group(query.len())?;
+        group.check_sequence(sequence as u64)?;
+        let query = self.validation.check(query, None)?;
+        let options = self.query_defaults.resolve(options)?;
+        to_js(&group.page(&query, offset, page_size, &options)?)
+    }
+}
+
+impl MixedIndex {
+    fn group(&self, dimensions: usize) -> Result<&VectorIndex, VectorError> {
+        self.groups
+            .get(&dimensions)
+            .ok_or(VectorError::UnknownGroup { dimensions })
+    }
+
+    fn group_mut(&mut self, dimensions: usize) -> Result<&mut VectorIndex, VectorError> {
+        self.groups
+            .get_mut(&dimensions)
+            .ok_or(VectorError::UnknownGroup { dimensions })
+    }
+}
@@ -0,0 +1,436 @@
+//! UMAP-style 2D layouts of embedding neighborhoods for visualization.
+//!
+//! The layout is computed in three steps:
+//! 1. an exact k-nearest-neighbor graph, with each point's edge weights
+//!    calibrated so they sum to `log2(k)` (UMAP's fuzzy simplicial set),
+//!    then symmetrized with the probabilistic union `a + b - a * b`
+//! 2. an initial layout along the first two principal components
+//! 3. stochastic gradient descent that pulls neighbors together along
+//!    sampled edges and pushes random pairs apart
+//!
+//! The neighbor graph is brute force, `O(count^2 * dimensions)`, which suits
+//! the few thousand points a neighborhood view shows.
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use wasm_bindgen::prelude::*;
+
+use crate::error::VectorError;
+use crate::metric::{self, MetricKind};
+use crate::rng::SplitMix64;
+use crate::search::TopK;
+use crate::{options_from_js, VectorSearch};
+
+/// Largest coordinate of the initial layout
+const INIT_SCALE: f64 = 10.0;
+
+/// Gradients are clipped to this magnitude per coordinate, as in UMAP
+const GRADIENT_CLIP: f64 = 4.0;
+
+/// Parameters for `project2d`; every field is optional on the JS side
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ProjectionParams {
+    /// Neighbors per point in the graph; larger values favour global
+    /// structure over local detail
+    pub neighbors: usize,
+    /// Optimization epochs
+    pub iterations: usize,
+    /// How tightly neighbors may pack in the layout
+    pub min_dist: f64,
+    /// Scale of the layout's distance falloff
+    pub spread: f64,
+    pub learning_rate: f64,
+    /// Repulsive samples drawn per attractive edge update
+    pub negative_samples: usize,
+    /// Cosine or euclidean distance between input vectors
+    pub metric: MetricKind,
+    pub seed: u64,
+    /// Called as `onProgress(completed, iterations)` every few epochs.
+    /// Functions don't survive serde, so this is read separately by
+    /// `ProjectionParams::from_js`
+    #[serde(skip)]
+    pub on_progress: Option<js_sys::Function>,
+}
+
+impl Default for ProjectionParams {
+    fn default() -> Self {
+        Self {
+            neighbors: 15,
+            iterations: 200,
+            min_dist: 0.1,
+            spread: 1.0,
+            learning_rate: 1.0,
+            negative_samples: 5,
+            metric: MetricKind::Cosine,
+            seed: 42,
+            on_progress: None,
+        }
+    }
+}
+
+impl ProjectionParams {
+    /// Read parameters from JS, lifting out the `onProgress` callback before
+    /// the plain-data fields are deserialized
+    pub(crate) fn from_js(value: JsValue) -> Result<Self, JsValue> {
+        let key = JsValue::from_str("onProgress");
+        let callback = if value.is_object() {
+            js_sys::Reflect::get(&value, &key)?
+        } else {
+            JsValue::UNDEFINED
+        };
+
+        if callback.is_undefined() {
+            return options_from_js(value);
+        }
+
+        let rest = js_sys::Object::assign(&js_sys::Object::new(), value.unchecked_ref());
+        js_sys::Reflect::delete_property(&rest, &key)?;
+
+        let function =
+            callback
+                .dyn_into::<js_sys::Function>()
+                .map_err(|_| VectorError::InvalidOptions {
+                    message: "onProgress must be a function".to_string(),
+                })?;
+
+        let mut params: ProjectionParams = options_from_js(rest.into())?;
+        params.on_progress = Some(function);
+        Ok(params)
+    }
+
+    fn validate(&self) -> Result<(), VectorError> {
+        let message = if !matches!(self.metric, MetricKind::Cosine | MetricKind::Euclidean) {
+            "projection supports the cosine and euclidean metrics"
+        } else if self.spread.is_nan() || self.spread <= 0.0 {
+            "spread must be positive"
+        } else if !(0.0..self.spread).contains(&self.min_dist) {
+            "minDist must be at least 0 and below spread"
+        } else if self.learning_rate.is_nan() || self.learning_rate <= 0.0 {
+            "learningRate must be positive"
+        } else {
+            return Ok(());
+        };
+        Err(VectorError::InvalidOptions {
+            message: message.to_string(),
+        })
+    }
+}
+
+#[wasm_bindgen]
+impl VectorSearch {
+    /// Lay out `count` flattened vectors in 2D, returning interleaved
+    /// `[x0, y0, x1, y1, ...]` coordinates.
+    ///
+    /// Nearby vectors land close together; distances between far-apart
+    /// clusters are only loosely meaningful. The same inputs and `seed`
+    /// always produce the same layout.
+    #[wasm_bindgen(js_name = "project2d")]
+    pub fn project_2d(
+        &self,
+        vectors: &[f32],
+        count: usize,
+        params: JsValue,
+    ) -> Result<Vec<f32>, JsValue> {
+        let params = ProjectionParams::from_js(params)?;
+        let callback = params.on_progress.clone();
+        let mut progress = |completed: usize, total: usize| -> Result<(), JsValue> {
+            if let Some(callback) = &callback {
+                callback.call2(
+                    &JsValue::NULL,
+                    &JsValue::from_f64(completed as f64),
+                    &JsValue::from_f64(total as f64),
+                )?;
+            }
+            Ok(())
+        };
+        self.projection(vectors, count, &params, &mut progress)
+    }
+}
+
+impl VectorSearch {
+    pub(crate) fn projection(
+        &self,
+        vectors: &[f32],
+        count: usize,
+        params: &ProjectionParams,
+        progress: &mut dyn FnMut(usize, usize) -> Result<(), JsValue>,
+    ) -> Result<Vec<f32>, JsValue> {
+        if vectors.len() != count * self.dimensions {
+            panic!("Vectors array size mismatch");
+        }
+        params.validate()?;
+        if count < 2 || self.dimensions == 0 {
+            return Ok(vec![0.0; count * 2]);
+        }
+
+        let mut rng = SplitMix64::new(params.seed);
+        let edges = fuzzy_graph(vectors, self.dimensions, params);
+        let mut layout = initial_layout(vectors, self.dimensions, &mut rng);
+        let (a, b) = fit_curve(params.min_dist, params.spread);
+        optimize(&mut layout, &edges, a, b, params, &mut rng, progress)?;
+
+        log!(
+            "Projected {} vectors over {} edges in {} epochs",
+            count,
+            edges.len(),
+            params.iterations
+        );
+        Ok(layout.into_iter().map(|x| x as f32).collect())
+    }
+}
+
+/// Symmetric weighted k-nearest-neighbor graph as `(i, j, weight)` edges
+fn fuzzy_graph(
+    vectors: &[f32],
+    dims: usize,
+    params: &ProjectionParams,
+) -> Vec<(usize, usize, f64)> {
+    let count = vectors.len() / dims;
+    let k = params.neighbors.clamp(1, count - 1);
+    let row = |i: usize| &vectors[i * dims..(i + 1) * dims];
+    let distance = |x: &[f32], y: &[f32]| match params.metric {
+        MetricKind::Euclidean => metric::euclidean(x, y),
+        _ => 1.0 - metric::cosine(x, y),
+    };
+
+    // Directed memberships, then combined per unordered pair
+    let target = (k as f64).log2().max(1.0);
+    let mut weights: HashMap<(usize, usize), (f64, f64)> = HashMap::new();
+    for i in 0..count {
+        // Distances sort lower-first like euclidean scores
+        let mut top = TopK::new(k, MetricKind::Euclidean);
+        for j in (0..count).filter(|&j| j != i) {
+            top.push(j, distance(row(i), row(j)).max(0.0));
+        }
+        let neighbors = top.into_sorted();
+
+        // Distance to the nearest distinct neighbor counts as fully connected
+        let rho = neighbors
+            .iter()
+            .map(|&(_, d)| d)
+            .find(|&d| d > 0.0)
+            .unwrap_or(0.0);
+        let sigma = calibrate_sigma(&neighbors, rho, target);
+
+        for (j, d) in neighbors {
+            let w = (-(d - rho).max(0.0) / sigma).exp();
+            let entry = weights.entry((i.min(j), i.max(j))).or_insert((0.0, 0.0));
+            if i < j {
+                entry.0 = w;
+            } else {
+                entry.1 = w;
+            }
+        }
+    }
+
+    let mut edges: Vec<(usize, usize, f64)> = weights
+        .into_iter()
+        .map(|((i, j), (w1, w2))| (i, j, w1 + w2 - w1 * w2))
+        .filter(|&(_, _, w)| w > 0.0)
+        .collect();
+    // Hash order varies between runs; sorting keeps layouts reproducible
+    edges.sort_unstable_by_key(|&(i, j, _)| (i, j));
+    edges
+}
+
+/// Bandwidth at which a point's neighbor memberships sum to `target`
+fn calibrate_sigma(neighbors: &[(usize, f64)], rho: f64, target: f64) -> f64 {
+    let (mut low, mut high, mut sigma) = (0.0, f64::INFINITY, 1.0);
+    for _ in 0..64 {
+        let sum: f64 = neighbors
+            .iter()
+            .map(|&(_, d)| (-(d - rho).max(0.0) / sigma).exp())
+            .sum();
+        if (sum - target).abs() < 1e-5 {
+            break;
+        }
+        if sum > target {
+            high = sigma;
+            sigma = (low + high) / 2.0;
+        } else {
+            low = sigma;
+            sigma = if high.is_finite() {
+                (low + high) / 2.0
+            } else {
+                sigma * 2.0
+            };
+        }
+    }
+    sigma.max(1e-6)
+}
+
+/// Coordinates along the first two principal components, scaled to
+/// `INIT_SCALE`, with a little jitter so identical inputs can separate
+fn initial_layout(vectors: &[f32], dims: usize, rng: &mut SplitMix64) -> Vec<f64> {
+    let count = vectors.len() / dims;
+    let mut mean = vec![0.0f64; dims];
+    for row in vectors.chunks_exact(dims) {
+        for (m, &x) in mean.iter_mut().zip(row) {
+            *m += x as f64 / count as f64;
+        }
+    }
+    let centered = |i: usize, d: usize| vectors[i * dims + d] as f64 - mean[d];
+
+    let mut components: Vec<Vec<f64>> = Vec::with_capacity(2);
+    let mut layout = vec![0.0f64; count * 2];
+    for axis in 0..2 {
+        // Power iteration on the covariance, deflated against earlier axes
+        let mut v: Vec<f64> = (0..dims).map(|_| rng.next_gaussian()).collect();
+        for _ in 0..50 {
+            let projected: Vec<f64> = (0..count)
+                .map(|i| (0..dims).map(|d| centered(i, d) * v[d]).sum())
+                .collect();
+            let mut next = vec![0.0f64; dims];
+            for (i, &p) in projected.iter().enumerate() {
+                for (d, n) in next.iter_mut().enumerate() {
+                    *n += centered(i, d) * p;
+                }
+            }
+            for component in &components {
+                let overlap: f64 = next.iter().zip(component).map(|(a, b)| a * b).sum();
+                for (n, c) in next.iter_mut().zip(component) {
+                    *n -= overlap * c;
+                }
+            }
+            let norm = next.iter().map(|x| x * x).sum::<f64>().sqrt();
+            if norm == 0.0 {
+                break;
+            }
+            v = next.into_iter().map(|x| x / norm).collect();
+        }
+
+        for i in 0..count {
+            layout[i * 2 + axis] = (0..dims).map(|d| centered(i, d) * v[d]).sum();
+        }
+        components.push(v);
+    }
+
+    let extent = layout.iter().fold(0.0f64, |m, x| m.max(x.abs()));
+    let scale = if extent > 0.0 {
+        INIT_SCALE / extent
+    } else {
+        0.0
+    };
+    for x in &mut layout {
+        *x = *x * scale + rng.next_gaussian() * 1e-4 * INIT_SCALE;
+    }
+    layout
+}
+
+/// Fit UMAP's `1 / (1 + a * d^(2b))` curve to the target falloff, which is
+/// 1 up to `min_dist` and decays as `exp(-(d - min_dist) / spread)` beyond
+fn fit_curve(min_dist: f64, spread: f64) -> (f64, f64) {
+    let samples: Vec<(f64, f64)> = (1..=300)
+        .map(|i| {
+            let d = i as f64 * spread * 3.0 / 300.0;
+            let target = if d < min_dist {
+                1.0
+            } else {
+                (-(d - min_dist) / spread).exp()
+            };
+            (d, target)
+        })
+        .collect();
+    let error = |a: f64, b: f64| -> f64 {
+        samples
+            .iter()
+            .map(|&(d, t)| {
+                let e = 1.0 / (1.0 + a * d.powf(2.0 * b)) - t;
+                e * e
+            })
+            .sum()
+    };
+
+    // Coarse grid, then a finer one around the best cell
+    let (mut best_a, mut best_b, mut step_a, mut step_b) = (1.0, 1.0, 0.25, 0.1);
+    let (mut low_a, mut low_b) = (0.05, 0.1);
+    let (mut steps_a, mut steps_b) = (40, 20);
+    for _ in 0..3 {
+        let mut best = f64::INFINITY;
+        for i in 0..=steps_a {
+            for j in 0..=steps_b {
+                let (a, b) = (low_a + i as f64 * step_a, low_b + j as f64 * step_b);
+                let e = error(a, b);
+                if e < best {
+                    (best, best_a, best_b) = (e, a, b);
+                }
+            }
+        }
+        (low_a, low_b) = ((best_a - step_a).max(1e-3), (best_b - step_b).max(1e-3));
+        (step_a, step_b) = (step_a / 10.0, step_b / 10.0);
+        (steps_a, steps_b) = (20, 20);
+    }
+    (best_a, best_b)
+}
+
+fn clip(x: f64) -> f64 {
+    x.clamp(-GRADIENT_CLIP, GRADIENT_CLIP)
+}
+
+fn optimize(
+    layout: &mut [f64],
+    edges: &[(usize, usize, f64)],
+    a: f64,
+    b: f64,
+    params: &ProjectionParams,
+    rng: &mut SplitMix64,
+    progress: &mut dyn FnMut(usize, usize) -> Result<(), JsValue>,
+) -> Result<(), JsValue> {
+    let count = layout.len() / 2;
+    let max_weight = edges.iter().fold(0.0f64, |m, &(_, _, w)| m.max(w));
+    let iterations = params.iterations;
+    let report_every = (iterations / 20).max(1);
+
+    for epoch in 0..iterations {
+        let alpha = params.learning_rate * (1.0 - epoch as f64 / iterations as f64);
+
+        for &(i, j, w) in edges {
+            // Heavier edges are sampled proportionally more often
+            if rng.next_f64() * max_weight > w {
+                continue;
+            }
+
+            let (dx, dy) = (
+                layout[i * 2] - layout[j * 2],
+                layout[i * 2 + 1] - layout[j * 2 + 1],
+            );
+            let d2 = dx * dx + dy * dy;
+            if d2 > 0.0 {
+                let coef = -2.0 * a * b * d2.powf(b - 1.0) / (1.0 + a * d2.powf(b));
+                let (gx, gy) = (clip(coef * dx) * alpha, clip(coef * dy) * alpha);
+                layout[i * 2] += gx;
+                layout[i * 2 + 1] += gy;
+                layout[j * 2] -= gx;
+                layout[j * 2 + 1] -= gy;
+            }
+
+            for _ in 0..params.negative_samples {
+                let other = rng.next_below(count);
+                if other == i {
+                    continue;
+                }
+                let (dx, dy) = (
+                    layout[i * 2] - layout[other * 2],
+                    layout[i * 2 + 1] - layout[other * 2 + 1],
+                );
+                let d2 = dx * dx + dy * dy;
+                let (gx, gy) = if d2 > 0.0 {
+                    let coef = 2.0 * b / ((0.001 + d2) * (1.0 + a * d2.powf(b)));
+                    (clip(coef * dx), clip(coef * dy))
+                } else {
+                    (GRADIENT_CLIP, GRADIENT_CLIP)
+                };
+                layout[i * 2] += gx * alpha;
+                layout[i * 2 + 1] += gy * alpha;
+            }
+        }
+
+        let completed = epoch + 1;
+        if completed % report_every == 0 || completed == iterations {
+            progress(completed, iterations)?;
+        }
+    }
+    Ok(())
+}
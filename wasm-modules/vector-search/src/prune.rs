@@ -0,0 +1,177 @@
+//! Early termination for exhaustive scans, enabled with `prune: true` in
+//! query options.
+//!
+//! Each candidate is read in cascaded prefixes (an eighth, a quarter, then
+//! half of its dimensions). After each prefix the best score the candidate
+//! could still reach is bounded, and once the scan holds `k` results a
+//! candidate whose bound cannot beat the worst of them is skipped without
+//! reading the rest. Survivors are scored exactly as without pruning, so
+//! pruning never changes results, only how much is read.
+//!
+//! Bounds:
+//! - dot and cosine: the remaining dot product is at most
+//!   `|q_rest| * |x_rest|` (Cauchy–Schwarz), with `|x_rest|` derived from
+//!   the candidate's cached norm and the prefix read so far
+//! - euclidean: the squared distance over a prefix never exceeds the full
+//!   squared distance
+
+use crate::metric::MetricKind;
+
+/// Scans below this many dimensions are not worth checking early
+const MIN_PRUNE_DIMENSIONS: usize = 32;
+
+/// Relative slack applied to bounds so floating-point rounding can never
+/// prune a candidate that belongs in the results
+pub(crate) const BOUND_SLACK: f64 = 1e-9;
+
+/// Per-query state for bounding partially read candidates
+pub(crate) struct PruneBounds<'a> {
+    query: &'a [f32],
+    metric: MetricKind,
+    query_norm: f64,
+    /// Prefix lengths to check at, with the query's norm past each one
+    checkpoints: Vec<(usize, f64)>,
+}
+
+impl<'a> PruneBounds<'a> {
+    /// Bounds for an unweighted built-in metric, or `None` when pruning
+    /// cannot help. `metric` is cosine, dot or euclidean; callers map cosine
+    /// over unit vectors to dot, as the cluster tree does.
+    pub(crate) fn new(query: &'a [f32], metric: MetricKind) -> Option<Self> {
+        if metric == MetricKind::Custom || query.len() < MIN_PRUNE_DIMENSIONS {
+            return None;
+        }
+
+        let mut checkpoints: Vec<(usize, f64)> = [8, 4, 2]
+            .iter()
+            .map(|divisor| query.len() / divisor)
+            .map(|prefix| {
+                let rest: f64 = query[prefix..].iter().map(|&x| x as f64 * x as f64).sum();
+                (prefix, rest.sqrt())
+            })
+            .collect();
+        checkpoints.dedup_by_key(|(prefix, _)| *prefix);
+
+        let query_norm = query
+            .iter()
+            .map(|&x| x as f64 * x as f64)
+            .sum::<f64>()
+            .sqrt();
+
+        Some(Self {
+            query,
+            metric,
+            query_norm,
+            checkpoints,
+        })
+    }
+
+    /// Whether `candidate` provably scores worse than `threshold`, reading as
+    /// little of it as possible. `candidate_norm` is its L2 norm, or NaN when
+    /// unknown, in which case only euclidean can be pruned.
+    pub(crate) fn can_skip(&self, candidate: &[f32], candidate_norm: f64, threshold: f64) -> bool {
+        let needs_norm = self.metric != MetricKind::Euclidean;
+        if needs_norm && candidate_norm.is_nan() {
+            return false;
+        }
+
+        let mut dot = 0.0;
+        let mut candidate_sq = 0.0;
+        let mut distance_sq = 0.0;
+        let mut read = 0;
+
+        for &(prefix, query_rest) in &self.checkpoints {
+            for (&q, &x) in self.query[read..prefix]
+                .iter()
+                .zip(&candidate[read..prefix])
+            {
+                let (q, x) = (q as f64, x as f64);
+                if needs_norm {
+                    dot += q * x;
+                    candidate_sq += x * x;
+                } else {
+                    distance_sq += (q - x) * (q - x);
+                }
+            }
+            read = prefix;
+
+            let pruned = match self.metric {
+                MetricKind::Euclidean => distance_sq > threshold * threshold * (1.0 + BOUND_SLACK),
+                _ => {
+                    let candidate_rest = (candidate_norm * candidate_norm - candidate_sq)
+                        .max(0.0)
+                        .sqrt();
+                    let upper = dot + query_rest * candidate_rest;
+                    let slack = BOUND_SLACK * self.query_norm * candidate_norm;
+
+                    if self.metric == MetricKind::Cosine {
+                        let magnitude = self.query_norm * candidate_norm;
+                        // Zero vectors score exactly 0
+                        let upper = if magnitude == 0.0 {
+                            0.0
+                        } else {
+                            (upper + slack) / magnitude
+                        };
+                        upper < threshold
+                    } else {
+                        upper + slack < threshold
+                    }
+                }
+            };
+
+            if pruned {
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::search::QueryOptions;
+    use crate::testdata::VectorTestData;
+    use crate::trace::ScanCounts;
+    use crate::{MetricKind, VectorIndex};
+
+    #[test]
+    fn pruning_never_changes_results() {
+        let dimensions = 96;
+        let count = 3000;
+        let mut data = VectorTestData::new(11.0);
+        let vectors = data.blobs(count, dimensions, 20, 0.3).vectors();
+
+        let mut index = VectorIndex::new(dimensions);
+        for (id, vector) in vectors.chunks_exact(dimensions).enumerate() {
+            index.insert(id as u32, vector).unwrap();
+        }
+        index.flush();
+        let query = data.gaussian(1, dimensions, 0.0, 0.5);
+
+        for metric in [MetricKind::Cosine, MetricKind::Dot, MetricKind::Euclidean] {
+            for k in [1, 10, 100] {
+                let plain = QueryOptions {
+                    metric,
+                    ..Default::default()
+                };
+                let pruned = QueryOptions {
+                    prune: true,
+                    ..plain.clone()
+                };
+
+                let mut plain_scan = ScanCounts::default();
+                let mut pruned_scan = ScanCounts::default();
+                let expected = index
+                    .ranked(&query, k, &plain, |slot| slot != 5, &mut plain_scan)
+                    .unwrap();
+                let actual = index
+                    .ranked(&query, k, &pruned, |slot| slot != 5, &mut pruned_scan)
+                    .unwrap();
+
+                assert_eq!(actual, expected, "{:?} k={}", metric, k);
+                assert_eq!(pruned_scan.pruned + pruned_scan.vectors, count - 1);
+            }
+        }
+    }
+}
@@ -0,0 +1,867 @@
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "simd")]
+use packed_simd::{i16x16, i32x16, i8x16, Cast};
+
+use crate::error::VectorError;
+use crate::metric::MetricKind;
+use crate::rows::Rows;
+
+/// Compressed representation kept alongside (or instead of) full-precision
+/// vectors
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum CodecKind {
+    /// One bit per dimension (above/below the trained per-dimension mean),
+    /// compared by Hamming distance
+    #[default]
+    Binary,
+    /// One signed byte per dimension with a trained scale and offset per
+    /// dimension
+    Int8,
+}
+
+/// How int8 ranges are mapped onto the code space
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Int8Mode {
+    /// `[min, max]` maps onto `[-128, 127]` with a per-dimension offset
+    #[default]
+    Asymmetric,
+    /// `[-absmax, absmax]` maps onto `[-127, 127]` with no offset
+    Symmetric,
+}
+
+/// What int8 scoring does when its i32 dot-product accumulator could
+/// overflow, which takes more than `MAX_EXACT_DIMENSIONS` dimensions.
+///
+/// Chosen per collection through `enableQuantization`. Block codes
+/// (`blockSize`) accumulate one block at a time and can't overflow, so the
+/// mode only matters for per-dimension codes. There is no int4 codec, so
+/// int4 kernels are out of scope here.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Int8Overflow {
+    /// Wrap around silently; fastest, and exact below the limit
+    #[default]
+    Wrapping,
+    /// Clamp dot products to the i32 range
+    Saturating,
+    /// Refuse to quantize collections wide enough to overflow, failing
+    /// `enableQuantization` with `AccumulatorOverflow`
+    Checked,
+    /// Accumulate in i64 past the limit, at some cost in speed
+    Widening,
+}
+
+impl Int8Overflow {
+    /// Fail if `dimensions` could overflow and the mode forbids it
+    pub(crate) fn check(self, dimensions: usize) -> Result<(), VectorError> {
+        if self == Int8Overflow::Checked && dimensions > MAX_EXACT_DIMENSIONS {
+            return Err(VectorError::AccumulatorOverflow {
+                dimensions,
+                limit: MAX_EXACT_DIMENSIONS,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Widest vectors whose int8 dot product always fits in an i32: every term
+/// is at most `128 * 128` in magnitude
+pub const MAX_EXACT_DIMENSIONS: usize = i32::MAX as usize / (128 * 128);
+
+/// Largest accepted `blockSize`
+pub const MAX_BLOCK_SIZE: usize = 256;
+
+/// Dimensions whose values int8 training gathers per pass over the rows
+const COLUMN_BLOCK: usize = 64;
+
+/// How per-dimension ranges are estimated during training
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Calibration {
+    /// Exact minimum and maximum of the training data
+    #[default]
+    MinMax,
+    /// Clip to the `percentile` / `1 - percentile` quantiles, so a few
+    /// outliers don't stretch the range for everyone else
+    Percentile,
+}
+
+/// Options for `enableQuantization`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct QuantizationOptions {
+    pub codec: CodecKind,
+    /// Int8 only: range mapping
+    pub mode: Int8Mode,
+    /// Int8 only: range estimation
+    pub calibration: Calibration,
+    /// Upper quantile used by percentile calibration
+    pub percentile: f64,
+    /// Int8 only: accumulator overflow handling
+    pub overflow: Int8Overflow,
+    /// Int8 only: quantize each vector in blocks of this many dimensions,
+    /// each block with its own symmetric scale stored alongside the codes.
+    /// Replaces the trained per-dimension ranges, so `mode` and
+    /// `calibration` are ignored; a few spiky dimensions then only cost
+    /// precision within their own block. Must be a multiple of 16 up to
+    /// `MAX_BLOCK_SIZE`; 32 or 64 are typical.
+    pub block_size: Option<usize>,
+    /// Keep the f32 vectors for re-ranking. When false (int8 only) the
+    /// originals are dropped, cutting memory roughly 4x, and re-ranking uses
+    /// dequantized vectors instead
+    pub keep_originals: bool,
+}
+
+impl QuantizationOptions {
+    /// Fail if the options can't be used for vectors of `dimensions`
+    pub(crate) fn check(&self, dimensions: usize) -> Result<(), VectorError> {
+        // Only int8 codes can stand in for the full-precision vectors
+        if self.codec != CodecKind::Int8 {
+            if !self.keep_originals {
+                return Err(VectorError::InvalidOptions {
+                    message: format!(
+                        "keepOriginals: false requires a decodable codec, not {:?}",
+                        self.codec
+                    ),
+                });
+            }
+            return Ok(());
+        }
+
+        match self.block_size {
+            // Blocks are scored separately, so no accumulator sees more
+            // than one block's worth of terms
+            Some(block) if block == 0 || block > MAX_BLOCK_SIZE || !block.is_multiple_of(16) => {
+                Err(VectorError::InvalidOptions {
+                    message: format!(
+                        "blockSize must be a multiple of 16 up to {}, got {}",
+                        MAX_BLOCK_SIZE, block
+                    ),
+                })
+            }
+            Some(_) => Ok(()),
+            None => self.overflow.check(dimensions),
+        }
+    }
+
+    /// Code size per vector these options train, matching
+    /// `QuantizationStats::bytes_per_vector`
+    pub(crate) fn bytes_per_vector(&self, dimensions: usize) -> usize {
+        let float = std::mem::size_of::<f32>();
+        match (self.codec, self.block_size) {
+            (CodecKind::Binary, _) => dimensions.div_ceil(64) * 8,
+            (CodecKind::Int8, None) => dimensions + float,
+            (CodecKind::Int8, Some(block)) => dimensions + (dimensions.div_ceil(block) + 1) * float,
+        }
+    }
+}
+
+impl Default for QuantizationOptions {
+    fn default() -> Self {
+        Self {
+            codec: CodecKind::default(),
+            mode: Int8Mode::default(),
+            calibration: Calibration::default(),
+            percentile: 0.999,
+            overflow: Int8Overflow::default(),
+            block_size: None,
+            keep_originals: true,
+        }
+    }
+}
+
+/// Summary of the trained codes, returned to JS
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuantizationStats {
+    pub codec: CodecKind,
+    pub vectors: usize,
+    pub bytes_per_vector: usize,
+    /// Size of full-precision f32 storage divided by code size
+    pub compression_ratio: f64,
+    pub originals_kept: bool,
+    /// Dimensions per scale block, for block-quantized int8
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_size: Option<usize>,
+}
+
+/// Sign codes relative to the per-dimension mean, packed 64 dimensions per
+/// word
+#[derive(Clone, Debug)]
+pub(crate) struct BinaryCodes {
+    dimensions: usize,
+    words: usize,
+    means: Vec<f32>,
+    bits: Vec<u64>,
+}
+
+impl BinaryCodes {
+    /// Compute the per-dimension means without encoding anything
+    fn fit(rows: &impl Rows) -> Self {
+        let dimensions = rows.dimensions();
+        let count = rows.count();
+        let mut sums = vec![0.0f64; dimensions];
+        for slot in 0..count {
+            for (s, &x) in sums.iter_mut().zip(rows.row(slot).iter()) {
+                *s += x as f64;
+            }
+        }
+
+        let scale = 1.0 / count.max(1) as f64;
+        Self {
+            dimensions,
+            words: dimensions.div_ceil(64),
+            means: sums.iter().map(|s| (s * scale) as f32).collect(),
+            bits: Vec::with_capacity(count * dimensions.div_ceil(64)),
+        }
+    }
+
+    fn encode_into(&self, vector: &[f32], out: &mut [u64]) {
+        out.fill(0);
+        for (d, (&x, &mean)) in vector.iter().zip(&self.means).enumerate() {
+            if x > mean {
+                out[d / 64] |= 1 << (d % 64);
+            }
+        }
+    }
+
+    fn push(&mut self, vector: &[f32]) {
+        let mut code = vec![0u64; self.words];
+        self.encode_into(vector, &mut code);
+        self.bits.extend_from_slice(&code);
+    }
+
+    fn set(&mut self, slot: usize, vector: &[f32]) {
+        let mut code = vec![0u64; self.words];
+        self.encode_into(vector, &mut code);
+        self.bits[slot * self.words..(slot + 1) * self.words].copy_from_slice(&code);
+    }
+
+    fn swap_remove(&mut self, slot: usize) {
+        let last = self.bits.len() / self.words - 1;
+        if slot != last {
+            self.bits.copy_within(
+                last * self.words..(last + 1) * self.words,
+                slot * self.words,
+            );
+        }
+        self.bits.truncate(last * self.words);
+    }
+
+    /// Negated Hamming distance, so larger is better like a similarity
+    fn score_all(&self, query: &[f32]) -> Vec<f64> {
+        let mut code = vec![0u64; self.words];
+        self.encode_into(query, &mut code);
+
+        self.bits
+            .chunks_exact(self.words)
+            .map(|stored| {
+                let distance: u32 = stored
+                    .iter()
+                    .zip(&code)
+                    .map(|(a, b)| (a ^ b).count_ones())
+                    .sum();
+                -(distance as f64)
+            })
+            .collect()
+    }
+}
+
+/// Dot product of two int8 vectors with i32 accumulation, 16 lanes at a time
+/// with SIMD
+pub(crate) fn dot_i8(a: &[i8], b: &[i8]) -> i32 {
+    #[cfg(feature = "simd")]
+    {
+        let chunks = a.len() / 16;
+        let mut acc = i32x16::splat(0);
+        for i in 0..chunks {
+            let x: i16x16 = i8x16::from_slice_unaligned(&a[i * 16..i * 16 + 16]).cast();
+            let y: i16x16 = i8x16::from_slice_unaligned(&b[i * 16..i * 16 + 16]).cast();
+            // i8 * i8 always fits in i16; widen before accumulating
+            let product: i32x16 = (x * y).cast();
+            acc += product;
+        }
+
+        let mut sum = acc.wrapping_sum();
+        for (&x, &y) in a[chunks * 16..].iter().zip(&b[chunks * 16..]) {
+            sum = sum.wrapping_add(x as i32 * y as i32);
+        }
+        sum
+    }
+
+    #[cfg(not(feature = "simd"))]
+    {
+        a.iter()
+            .zip(b)
+            .fold(0i32, |sum, (&x, &y)| sum.wrapping_add(x as i32 * y as i32))
+    }
+}
+
+/// `dot_i8` with an i64 accumulator, exact at any length. The i32 kernel
+/// runs over blocks short enough not to overflow and the block sums are
+/// widened.
+pub(crate) fn dot_i8_wide(a: &[i8], b: &[i8]) -> i64 {
+    // Keep each i32 lane, and with SIMD the sum of 16 lanes, in range
+    const BLOCK: usize = MAX_EXACT_DIMENSIONS / 16 * 16;
+
+    a.chunks(BLOCK)
+        .zip(b.chunks(BLOCK))
+        .map(|(a, b)| dot_i8(a, b) as i64)
+        .sum()
+}
+
+/// Dot product of two block-quantized int8 vectors: each block's integer
+/// dot product is scaled by both blocks' scales. Blocks are a multiple of 16
+/// long, so `dot_i8` runs entirely in SIMD lanes except in a shorter final
+/// block.
+pub(crate) fn dot_i8_blocks(
+    a: &[i8],
+    b: &[i8],
+    a_scales: &[f32],
+    b_scales: &[f32],
+    block: usize,
+) -> f32 {
+    a.chunks(block)
+        .zip(b.chunks(block))
+        .zip(a_scales.iter().zip(b_scales))
+        .map(|((a, b), (&sa, &sb))| dot_i8(a, b) as f32 * (sa * sb))
+        .sum()
+}
+
+/// Map an estimated dot product to a ranking score for `metric`, given the
+/// query's norm and the stored vector's decoded norm
+fn estimated_score(dot: f64, query_norm: f64, norm: f64, metric: MetricKind) -> f64 {
+    match metric {
+        // Custom metrics have no code-space estimate; callers scan them at
+        // full precision instead
+        MetricKind::Dot | MetricKind::Custom => dot,
+        MetricKind::Cosine if query_norm * norm == 0.0 => 0.0,
+        MetricKind::Cosine => dot / (query_norm * norm),
+        // |q - x|² = |q|² - 2 q·x + |x|²; |q|² is the same for every
+        // candidate, so it is left out of the ranking key
+        MetricKind::Euclidean => 2.0 * dot - norm * norm,
+    }
+}
+
+/// Per-dimension int8 codes, decoded as `code * scale + offset`
+#[derive(Clone, Debug)]
+pub(crate) struct Int8Codes {
+    dimensions: usize,
+    scales: Vec<f32>,
+    offsets: Vec<f32>,
+    mode: Int8Mode,
+    overflow: Int8Overflow,
+    codes: Vec<i8>,
+    /// L2 norm of each decoded vector, for cosine and euclidean estimates
+    norms: Vec<f32>,
+}
+
+impl Int8Codes {
+    /// Estimate the per-dimension ranges without encoding anything
+    fn fit(rows: &impl Rows, options: &QuantizationOptions) -> Self {
+        let dimensions = rows.dimensions();
+        let count = rows.count();
+        let mut scales = vec![0.0f32; dimensions];
+        let mut offsets = vec![0.0f32; dimensions];
+
+        // Columns are gathered a block at a time, one pass over the rows per
+        // block, so only `COLUMN_BLOCK` of them are held at once
+        let mut columns = vec![Vec::with_capacity(count); COLUMN_BLOCK.min(dimensions)];
+        for start in (0..dimensions).step_by(COLUMN_BLOCK) {
+            let end = (start + COLUMN_BLOCK).min(dimensions);
+            for column in &mut columns {
+                column.clear();
+            }
+            for slot in 0..count {
+                let row = rows.row(slot);
+                for (column, &x) in columns.iter_mut().zip(&row[start..end]) {
+                    column.push(x);
+                }
+            }
+
+            for (d, column) in (start..end).zip(&mut columns) {
+                (scales[d], offsets[d]) = Self::calibrate(column, options);
+            }
+        }
+
+        Self {
+            dimensions,
+            scales,
+            offsets,
+            mode: options.mode,
+            overflow: options.overflow,
+            codes: Vec::with_capacity(count * dimensions),
+            norms: Vec::with_capacity(count),
+        }
+    }
+
+    /// One dimension's scale and offset, from every value it takes
+    fn calibrate(column: &mut [f32], options: &QuantizationOptions) -> (f32, f32) {
+        let (lo, hi) = match options.calibration {
+            _ if column.is_empty() => (0.0, 0.0),
+            Calibration::MinMax => column
+                .iter()
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), &x| {
+                    (lo.min(x), hi.max(x))
+                }),
+            Calibration::Percentile => {
+                column.sort_by(|a, b| a.total_cmp(b));
+                let p = options.percentile.clamp(0.5, 1.0);
+                let last = column.len() - 1;
+                let hi_idx = ((last as f64) * p).round() as usize;
+                let lo_idx = ((last as f64) * (1.0 - p)).round() as usize;
+                (column[lo_idx], column[hi_idx])
+            }
+        };
+
+        match options.mode {
+            Int8Mode::Symmetric => (lo.abs().max(hi.abs()) / 127.0, 0.0),
+            Int8Mode::Asymmetric => {
+                let scale = (hi - lo) / 255.0;
+                (scale, lo + 128.0 * scale)
+            }
+        }
+    }
+
+    fn encode_into(&self, vector: &[f32], out: &mut [i8]) -> f32 {
+        let min = match self.mode {
+            Int8Mode::Symmetric => -127.0,
+            Int8Mode::Asymmetric => -128.0,
+        };
+
+        let mut norm = 0.0f32;
+        for (d, (&x, code)) in vector.iter().zip(out.iter_mut()).enumerate() {
+            let scale = self.scales[d];
+            *code = if scale > 0.0 {
+                ((x - self.offsets[d]) / scale).round().clamp(min, 127.0) as i8
+            } else {
+                0
+            };
+
+            let decoded = *code as f32 * scale + self.offsets[d];
+            norm += decoded * decoded;
+        }
+        norm.sqrt()
+    }
+
+    fn push(&mut self, vector: &[f32]) {
+        let start = self.codes.len();
+        self.codes.resize(start + self.dimensions, 0);
+        let mut code = vec![0i8; self.dimensions];
+        let norm = self.encode_into(vector, &mut code);
+        self.codes[start..].copy_from_slice(&code);
+        self.norms.push(norm);
+    }
+
+    fn set(&mut self, slot: usize, vector: &[f32]) {
+        let mut code = vec![0i8; self.dimensions];
+        self.norms[slot] = self.encode_into(vector, &mut code);
+        self.codes[slot * self.dimensions..(slot + 1) * self.dimensions].copy_from_slice(&code);
+    }
+
+    fn swap_remove(&mut self, slot: usize) {
+        let dims = self.dimensions;
+        let last = self.norms.len() - 1;
+        if slot != last {
+            self.codes
+                .copy_within(last * dims..(last + 1) * dims, slot * dims);
+        }
+        self.codes.truncate(last * dims);
+        self.norms.swap_remove(slot);
+    }
+
+    fn decode(&self, slot: usize) -> Vec<f32> {
+        self.codes[slot * self.dimensions..(slot + 1) * self.dimensions]
+            .iter()
+            .zip(self.scales.iter().zip(&self.offsets))
+            .map(|(&c, (&scale, &offset))| c as f32 * scale + offset)
+            .collect()
+    }
+
+    /// Int8 dot product under the overflow mode; below the limit every
+    /// mode takes the plain i32 kernel. `Checked` collections never get
+    /// past the limit, since `enableQuantization` rejects them.
+    fn dot(&self, query: &[i8], code: &[i8]) -> f64 {
+        if self.dimensions <= MAX_EXACT_DIMENSIONS {
+            return dot_i8(query, code) as f64;
+        }
+
+        match self.overflow {
+            Int8Overflow::Wrapping | Int8Overflow::Checked => dot_i8(query, code) as f64,
+            Int8Overflow::Saturating => {
+                dot_i8_wide(query, code).clamp(i32::MIN as i64, i32::MAX as i64) as f64
+            }
+            Int8Overflow::Widening => dot_i8_wide(query, code) as f64,
+        }
+    }
+
+    /// Estimate scores for every slot with int8 arithmetic.
+    ///
+    /// `q·x = Σ (q_i s_i) c_i + Σ q_i o_i`; the per-dimension scales are
+    /// folded into the query, which is then itself quantized to int8 with a
+    /// single scale so the inner loop is a pure int8 dot product.
+    fn score_all(&self, query: &[f32], metric: MetricKind) -> Vec<f64> {
+        let folded: Vec<f32> = query.iter().zip(&self.scales).map(|(q, s)| q * s).collect();
+        let max = folded.iter().fold(0.0f32, |m, x| m.max(x.abs()));
+        let query_scale = if max > 0.0 { max / 127.0 } else { 1.0 };
+        let query_codes: Vec<i8> = folded
+            .iter()
+            .map(|x| (x / query_scale).round() as i8)
+            .collect();
+
+        let bias: f64 = query
+            .iter()
+            .zip(&self.offsets)
+            .map(|(&q, &o)| q as f64 * o as f64)
+            .sum();
+        let query_norm = query
+            .iter()
+            .map(|&q| q as f64 * q as f64)
+            .sum::<f64>()
+            .sqrt();
+
+        self.codes
+            .chunks_exact(self.dimensions)
+            .zip(&self.norms)
+            .map(|(code, &norm)| {
+                let dot = query_scale as f64 * self.dot(&query_codes, code) + bias;
+                estimated_score(dot, query_norm, norm as f64, metric)
+            })
+            .collect()
+    }
+}
+
+/// Int8 codes with a symmetric scale per block of `block` dimensions of
+/// each vector, decoded as `code * scale`
+#[derive(Clone, Debug)]
+pub(crate) struct BlockInt8Codes {
+    dimensions: usize,
+    block: usize,
+    /// Blocks per vector; the last may be shorter than `block`
+    blocks: usize,
+    codes: Vec<i8>,
+    /// `blocks` scales per slot
+    scales: Vec<f32>,
+    /// L2 norm of each decoded vector, for cosine and euclidean estimates
+    norms: Vec<f32>,
+}
+
+impl BlockInt8Codes {
+    /// Scales are per vector, so there is nothing to learn up front
+    fn fit(dimensions: usize, block: usize) -> Self {
+        Self {
+            dimensions,
+            block,
+            blocks: dimensions.div_ceil(block),
+            codes: Vec::new(),
+            scales: Vec::new(),
+            norms: Vec::new(),
+        }
+    }
+
+    /// Encode `vector` block by block, returning the decoded norm
+    fn encode_into(&self, vector: &[f32], out: &mut [i8], scales: &mut [f32]) -> f32 {
+        let mut norm = 0.0f32;
+        for ((values, codes), scale) in vector
+            .chunks(self.block)
+            .zip(out.chunks_mut(self.block))
+            .zip(scales.iter_mut())
+        {
+            let max = values.iter().fold(0.0f32, |m, x| m.max(x.abs()));
+            *scale = max / 127.0;
+            for (&x, code) in values.iter().zip(codes.iter_mut()) {
+                *code = if *scale > 0.0 {
+                    (x / *scale).round().clamp(-127.0, 127.0) as i8
+                } else {
+                    0
+                };
+
+                let decoded = *code as f32 * *scale;
+                norm += decoded * decoded;
+            }
+        }
+        norm.sqrt()
+    }
+
+    fn push(&mut self, vector: &[f32]) {
+        let mut code = vec![0i8; self.dimensions];
+        let mut scales = vec![0.0f32; self.blocks];
+        let norm = self.encode_into(vector, &mut code, &mut scales);
+        self.codes.extend_from_slice(&code);
+        self.scales.extend_from_slice(&scales);
+        self.norms.push(norm);
+    }
+
+    fn set(&mut self, slot: usize, vector: &[f32]) {
+        let mut code = vec![0i8; self.dimensions];
+        let mut scales = vec![0.0f32; self.blocks];
+        self.norms[slot] = self.encode_into(vector, &mut code, &mut scales);
+        self.codes[slot * self.dimensions..(slot + 1) * self.dimensions].copy_from_slice(&code);
+        self.scales[slot * self.blocks..(slot + 1) * self.blocks].copy_from_slice(&scales);
+    }
+
+    fn swap_remove(&mut self, slot: usize) {
+        let (dims, blocks) = (self.dimensions, self.blocks);
+        let last = self.norms.len() - 1;
+        if slot != last {
+            self.codes
+                .copy_within(last * dims..(last + 1) * dims, slot * dims);
+            self.scales
+                .copy_within(last * blocks..(last + 1) * blocks, slot * blocks);
+        }
+        self.codes.truncate(last * dims);
+        self.scales.truncate(last * blocks);
+        self.norms.swap_remove(slot);
+    }
+
+    fn decode(&self, slot: usize) -> Vec<f32> {
+        let code = &self.codes[slot * self.dimensions..(slot + 1) * self.dimensions];
+        let scales = &self.scales[slot * self.blocks..(slot + 1) * self.blocks];
+        code.chunks(self.block)
+            .zip(scales)
+            .flat_map(|(codes, &scale)| codes.iter().map(move |&c| c as f32 * scale))
+            .collect()
+    }
+
+    /// Estimate scores for every slot. The query is block-quantized the
+    /// same way, so each block is a pure int8 dot product times two scales.
+    fn score_all(&self, query: &[f32], metric: MetricKind) -> Vec<f64> {
+        let mut query_codes = vec![0i8; self.dimensions];
+        let mut query_scales = vec![0.0f32; self.blocks];
+        self.encode_into(query, &mut query_codes, &mut query_scales);
+        let query_norm = query
+            .iter()
+            .map(|&q| q as f64 * q as f64)
+            .sum::<f64>()
+            .sqrt();
+
+        self.codes
+            .chunks_exact(self.dimensions)
+            .zip(self.scales.chunks_exact(self.blocks))
+            .zip(&self.norms)
+            .map(|((code, scales), &norm)| {
+                let dot = dot_i8_blocks(&query_codes, code, &query_scales, scales, self.block);
+                estimated_score(dot as f64, query_norm, norm as f64, metric)
+            })
+            .collect()
+    }
+}
+
+/// Slot-aligned compressed codes for every stored vector
+#[derive(Clone, Debug)]
+pub(crate) enum Codes {
+    Binary(BinaryCodes),
+    Int8(Int8Codes),
+    BlockInt8(BlockInt8Codes),
+}
+
+impl Codes {
+    /// Train codes over `rows` and encode every one of them
+    pub(crate) fn train(options: &QuantizationOptions, rows: &impl Rows) -> Self {
+        let mut codes = Self::fit(options, rows);
+        for slot in 0..rows.count() {
+            codes.push(&rows.row(slot));
+        }
+        codes
+    }
+
+    /// Learn the codec's parameters from `rows` without encoding them,
+    /// leaving codes to be pushed slot by slot
+    pub(crate) fn fit(options: &QuantizationOptions, rows: &impl Rows) -> Self {
+        match options.codec {
+            CodecKind::Binary => Codes::Binary(BinaryCodes::fit(rows)),
+            CodecKind::Int8 => match options.block_size {
+                Some(block) => Codes::BlockInt8(BlockInt8Codes::fit(rows.dimensions(), block)),
+                None => Codes::Int8(Int8Codes::fit(rows, options)),
+            },
+        }
+    }
+
+    pub(crate) fn kind(&self) -> CodecKind {
+        match self {
+            Codes::Binary(_) => CodecKind::Binary,
+            Codes::Int8(_) | Codes::BlockInt8(_) => CodecKind::Int8,
+        }
+    }
+
+    fn dimensions(&self) -> usize {
+        match self {
+            Codes::Binary(codes) => codes.dimensions,
+            Codes::Int8(codes) => codes.dimensions,
+            Codes::BlockInt8(codes) => codes.dimensions,
+        }
+    }
+
+    pub(crate) fn bytes_per_vector(&self) -> usize {
+        match self {
+            Codes::Binary(codes) => codes.words * 8,
+            Codes::Int8(codes) => codes.dimensions + std::mem::size_of::<f32>(),
+            Codes::BlockInt8(codes) => {
+                codes.dimensions + (codes.blocks + 1) * std::mem::size_of::<f32>()
+            }
+        }
+    }
+
+    pub(crate) fn stats(&self, vectors: usize, originals_kept: bool) -> QuantizationStats {
+        let bytes = self.bytes_per_vector();
+
+        QuantizationStats {
+            codec: self.kind(),
+            vectors,
+            bytes_per_vector: bytes,
+            compression_ratio: (self.dimensions() * 4) as f64 / bytes.max(1) as f64,
+            originals_kept,
+            block_size: match self {
+                Codes::BlockInt8(codes) => Some(codes.block),
+                _ => None,
+            },
+        }
+    }
+
+    /// Append the code for a newly stored vector
+    pub(crate) fn push(&mut self, vector: &[f32]) {
+        match self {
+            Codes::Binary(codes) => codes.push(vector),
+            Codes::Int8(codes) => codes.push(vector),
+            Codes::BlockInt8(codes) => codes.push(vector),
+        }
+    }
+
+    /// Re-encode the vector stored at `slot`
+    pub(crate) fn set(&mut self, slot: usize, vector: &[f32]) {
+        match self {
+            Codes::Binary(codes) => codes.set(slot, vector),
+            Codes::Int8(codes) => codes.set(slot, vector),
+            Codes::BlockInt8(codes) => codes.set(slot, vector),
+        }
+    }
+
+    /// Mirror the storage's swap-remove of `slot`
+    pub(crate) fn swap_remove(&mut self, slot: usize) {
+        match self {
+            Codes::Binary(codes) => codes.swap_remove(slot),
+            Codes::Int8(codes) => codes.swap_remove(slot),
+            Codes::BlockInt8(codes) => codes.swap_remove(slot),
+        }
+    }
+
+    /// Reconstruct the vector at `slot`, if the codec supports it
+    pub(crate) fn decode(&self, slot: usize) -> Option<Vec<f32>> {
+        match self {
+            Codes::Binary(_) => None,
+            Codes::Int8(codes) => Some(codes.decode(slot)),
+            Codes::BlockInt8(codes) => Some(codes.decode(slot)),
+        }
+    }
+
+    /// Approximate scores for every slot, larger is better whatever the
+    /// query metric
+    pub(crate) fn score_all(&self, query: &[f32], metric: MetricKind) -> Vec<f64> {
+        match self {
+            Codes::Binary(codes) => codes.score_all(query),
+            Codes::Int8(codes) => codes.score_all(query, metric),
+            Codes::BlockInt8(codes) => codes.score_all(query, metric),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::SplitMix64;
+
+    fn random_vector(rng: &mut SplitMix64, dimensions: usize) -> Vec<f32> {
+        (0..dimensions)
+            .map(|_| rng.next_f64() as f32 * 2.0 - 1.0)
+            .collect()
+    }
+
+    fn dot_f32(a: &[f32], b: &[f32]) -> f64 {
+        a.iter().zip(b).map(|(&x, &y)| x as f64 * y as f64).sum()
+    }
+
+    #[test]
+    fn block_dot_matches_scalar() {
+        let a: Vec<i8> = (0..70).map(|i| (i * 7 % 255) as i8).collect();
+        let b: Vec<i8> = (0..70).map(|i| (i * 13 % 255) as i8).collect();
+        let a_scales = [0.5f32, 2.0, 1.0];
+        let b_scales = [1.0f32, 0.25, 3.0];
+
+        let expected: f32 = (0..70)
+            .map(|i| a[i] as f32 * b[i] as f32 * a_scales[i / 32] * b_scales[i / 32])
+            .sum();
+        let actual = dot_i8_blocks(&a, &b, &a_scales, &b_scales, 32);
+        assert!(
+            (actual - expected).abs() < 1e-3,
+            "{} vs {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn block_codes_track_f32_dot() {
+        let dimensions = 100;
+        let mut rng = SplitMix64::new(21);
+        let mut codes = BlockInt8Codes::fit(dimensions, 32);
+
+        let mut vectors = Vec::new();
+        for i in 0..50 {
+            let mut vector = random_vector(&mut rng, dimensions);
+            // One spiky dimension per vector only costs precision in its block
+            vector[i * 7 % dimensions] *= 40.0;
+            codes.push(&vector);
+            vectors.push(vector);
+        }
+
+        for (slot, vector) in vectors.iter().enumerate() {
+            let decoded = codes.decode(slot);
+            for (block, values) in vector.chunks(32).enumerate() {
+                let scale = codes.scales[slot * codes.blocks + block];
+                for (j, &x) in values.iter().enumerate() {
+                    assert!((decoded[block * 32 + j] - x).abs() <= scale / 2.0 + 1e-6);
+                }
+            }
+        }
+
+        let query = random_vector(&mut rng, dimensions);
+        let query_norm = dot_f32(&query, &query).sqrt();
+        for (vector, estimate) in vectors.iter().zip(codes.score_all(&query, MetricKind::Dot)) {
+            let exact = dot_f32(&query, vector);
+            let norm = dot_f32(vector, vector).sqrt();
+            assert!(
+                (estimate - exact).abs() <= 0.02 * query_norm * norm,
+                "{} vs {}",
+                estimate,
+                exact
+            );
+        }
+    }
+
+    #[test]
+    fn checked_overflow_rejects_wide_collections() {
+        let checked = QuantizationOptions {
+            codec: CodecKind::Int8,
+            overflow: Int8Overflow::Checked,
+            ..Default::default()
+        };
+        assert!(checked.check(MAX_EXACT_DIMENSIONS).is_ok());
+        assert!(matches!(
+            checked.check(MAX_EXACT_DIMENSIONS + 1),
+            Err(VectorError::AccumulatorOverflow { .. })
+        ));
+
+        let blocks = QuantizationOptions {
+            block_size: Some(64),
+            ..checked.clone()
+        };
+        assert!(blocks.check(MAX_EXACT_DIMENSIONS + 1).is_ok());
+
+        let n = MAX_EXACT_DIMENSIONS + 37;
+        let a = vec![-128i8; n];
+        assert_eq!(dot_i8_wide(&a, &a), n as i64 * 128 * 128);
+    }
+}
@@ -0,0 +1,79 @@
+//! Per-collection default query options.
+//!
+//! Options resolve in three layers: the module defaults of `QueryOptions`,
+//! then the defaults set on a collection with `setQueryDefaults`, then the
+//! options passed with each query. A key given per query replaces the
+//! collection's value outright; nested objects such as `normalize` are not
+//! merged field by field.
+//!
+//! `VectorIndex.snapshot` writes the collection layer into the snapshot and
+//! `RemoteSnapshot.open` restores it.
+
+use wasm_bindgen::prelude::*;
+
+use crate::error::VectorError;
+use crate::search::QueryOptions;
+use crate::to_js;
+
+/// Collection-level layer of query options, unset until `setQueryDefaults`
+#[derive(Clone, Debug, Default)]
+pub(crate) struct QueryDefaults(Option<QueryOptions>);
+
+impl QueryDefaults {
+    /// Read defaults from JS; `null` or `undefined` clears them
+    pub(crate) fn from_js(value: JsValue) -> Result<Self, JsValue> {
+        if value.is_undefined() || value.is_null() {
+            return Ok(Self(None));
+        }
+        Ok(Self(Some(QueryOptions::from_js(value)?)))
+    }
+
+    /// Every option as the collection resolves it, module defaults included,
+    /// so it can be stored with the collection and restored later. A
+    /// `customMetric` callback can't be serialized and is left out.
+    pub(crate) fn to_js(&self) -> Result<JsValue, JsValue> {
+        match &self.0 {
+            Some(defaults) => to_js(defaults),
+            None => to_js(&QueryOptions::default()),
+        }
+    }
+
+    /// Defaults as JSON for a snapshot, empty when none are set
+    pub(crate) fn to_json(&self) -> Result<Vec<u8>, JsValue> {
+        match &self.0 {
+            Some(defaults) => {
+                let json: String = js_sys::JSON::stringify(&to_js(defaults)?)?.into();
+                Ok(json.into_bytes())
+            }
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Read defaults written by `to_json`
+    #[cfg_attr(not(feature = "remote"), allow(dead_code))]
+    pub(crate) fn from_json(bytes: &[u8]) -> Result<Self, JsValue> {
+        if bytes.is_empty() {
+            return Ok(Self(None));
+        }
+        let json = std::str::from_utf8(bytes).map_err(|_| VectorError::InvalidSnapshot {
+            message: "query defaults are not UTF-8".to_string(),
+        })?;
+        Self::from_js(js_sys::JSON::parse(json)?)
+    }
+
+    /// Options for one query: `overrides` laid over the collection defaults
+    pub(crate) fn resolve(&self, overrides: JsValue) -> Result<QueryOptions, JsValue> {
+        let Some(defaults) = &self.0 else {
+            return QueryOptions::from_js(overrides);
+        };
+
+        let merged: js_sys::Object = to_js(defaults)?.unchecked_into();
+        if let Some(callback) = &defaults.custom_metric {
+            js_sys::Reflect::set(&merged, &JsValue::from_str("customMetric"), callback)?;
+        }
+        if overrides.is_object() {
+            js_sys::Object::assign(&merged, overrides.unchecked_ref());
+        }
+        QueryOptions::from_js(merged.into())
+    }
+}
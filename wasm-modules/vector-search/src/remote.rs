@@ -0,0 +1,274 @@
+//! Read-only queries over a snapshot hosted remotely, compiled with the
+//! `remote` feature.
+//!
+//! Bytes are pulled through a caller-supplied range callback, so the
+//! snapshot can sit behind HTTP range requests, a CDN or any other byte
+//! store. Opening reads the header and coarse index; each query then fetches
+//! only the lists it probes, keeping recently used lists in a small cache.
+
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use serde::Deserialize;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+
+use crate::error::VectorError;
+use crate::index::IndexHit;
+use crate::metric::Scorer;
+use crate::options_from_js;
+use crate::query_defaults::QueryDefaults;
+use crate::search::QueryOptions;
+use crate::snapshot::{
+    check_block_options, rank_blocks, CoarseIndex, ListBlock, SnapshotHeader, HEADER_BYTES,
+};
+use crate::to_js;
+
+/// Options for `RemoteSnapshot.open`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RemoteSnapshotOptions {
+    /// Lists probed per query unless the query sets `beamWidth`
+    pub probes: usize,
+    /// Fetched lists kept in memory, evicted oldest first
+    pub cache_lists: usize,
+}
+
+impl Default for RemoteSnapshotOptions {
+    fn default() -> Self {
+        Self {
+            probes: 4,
+            cache_lists: 32,
+        }
+    }
+}
+
+struct RemoteState {
+    fetch_range: js_sys::Function,
+    header: SnapshotHeader,
+    coarse: CoarseIndex,
+    query_defaults: QueryDefaults,
+    options: RemoteSnapshotOptions,
+    cache: RefCell<VecDeque<(usize, Rc<ListBlock>)>>,
+    bytes_fetched: Cell<u64>,
+}
+
+/// Query interface over a snapshot written by `VectorIndex.snapshot`.
+///
+/// `fetchRange(offset, length)` must resolve to a `Uint8Array` or
+/// `ArrayBuffer` holding exactly those bytes, e.g. by issuing a fetch with a
+/// `Range: bytes=offset-(offset + length - 1)` header. Results are
+/// approximate: only the `probes` lists nearest the query are scanned.
+#[wasm_bindgen]
+pub struct RemoteSnapshot {
+    state: Rc<RemoteState>,
+}
+
+#[wasm_bindgen]
+impl RemoteSnapshot {
+    /// Read the header, coarse index and the collection's query defaults
+    /// through `fetchRange`
+    pub async fn open(
+        fetch_range: js_sys::Function,
+        options: JsValue,
+    ) -> Result<RemoteSnapshot, JsValue> {
+        let options: RemoteSnapshotOptions = options_from_js(options)?;
+        if options.probes == 0 {
+            return Err(VectorError::InvalidOptions {
+                message: "probes must be at least 1".to_string(),
+            }
+            .into());
+        }
+
+        let bytes_fetched = Cell::new(0);
+        let header_bytes = fetch(&fetch_range, 0, HEADER_BYTES, &bytes_fetched).await?;
+        let header = SnapshotHeader::decode(&header_bytes)?;
+        let coarse_len = header.coarse_len()?;
+        let coarse_bytes = fetch(
+            &fetch_range,
+            HEADER_BYTES as u64,
+            coarse_len + header.defaults_len,
+            &bytes_fetched,
+        )
+        .await?;
+        let coarse = CoarseIndex::decode(&header, &coarse_bytes[..coarse_len])?;
+        let query_defaults = QueryDefaults::from_json(&coarse_bytes[coarse_len..])?;
+
+        log!(
+            "Opened remote snapshot of {} vectors in {} lists",
+            header.count,
+            header.lists
+        );
+        Ok(RemoteSnapshot {
+            state: Rc::new(RemoteState {
+                fetch_range,
+                header,
+                coarse,
+                query_defaults,
+                options,
+                cache: RefCell::new(VecDeque::new()),
+                bytes_fetched,
+            }),
+        })
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn dimensions(&self) -> usize {
+        self.state.header.dimensions
+    }
+
+    /// Number of stored vectors
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> f64 {
+        self.state.header.count as f64
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn lists(&self) -> usize {
+        self.state.header.lists
+    }
+
+    /// Query options stored with the snapshot, as `VectorIndex.queryDefaults`
+    #[wasm_bindgen(js_name = "queryDefaults")]
+    pub fn query_defaults(&self) -> Result<JsValue, JsValue> {
+        self.state.query_defaults.to_js()
+    }
+
+    /// Total bytes received through `fetchRange` so far
+    #[wasm_bindgen(getter, js_name = "bytesFetched")]
+    pub fn bytes_fetched(&self) -> f64 {
+        self.state.bytes_fetched.get() as f64
+    }
+
+    /// Resolve to the top `k` hits as `{ id, score }` objects.
+    ///
+    /// Accepts the `VectorIndex.search` options that shape scoring and
+    /// results (`metric`, `weights`, `exclude`, `autoK`, `normalize`,
+    /// `scoreRounding`); `beamWidth` sets how many lists to probe.
+    /// `collapseDuplicates`, `parents` and group labels are rejected. The
+    /// snapshot's query defaults apply under these options.
+    pub fn search(
+        &self,
+        query: Vec<f32>,
+        k: usize,
+        options: JsValue,
+    ) -> Result<js_sys::Promise, JsValue> {
+        let options = self.state.query_defaults.resolve(options)?;
+        check_block_options(&options)?;
+        let dimensions = self.state.header.dimensions;
+        if query.len() != dimensions {
+            panic!("Query vector dimension mismatch");
+        }
+        if let Some(weights) = &options.weights {
+            if weights.len() != dimensions {
+                panic!("Weight vector dimension mismatch");
+            }
+        }
+
+        let state = self.state.clone();
+        Ok(future_to_promise(async move {
+            let hits = state.search(&query, k, &options).await?;
+            to_js(&hits)
+        }))
+    }
+}
+
+impl RemoteState {
+    async fn search(
+        &self,
+        query: &[f32],
+        k: usize,
+        options: &QueryOptions,
+    ) -> Result<Vec<IndexHit>, JsValue> {
+        let scorer = Scorer::new(
+            query,
+            options.metric,
+            options.weights.as_deref(),
+            options.assume_normalized,
+        )
+        .truncated(options.search_dims)
+        .with_callback(options.custom_metric.as_ref());
+
+        let probes = options.beam_width.unwrap_or(self.options.probes).max(1);
+        let lists = self.coarse.nearest_lists(&scorer, probes);
+        scorer.check()?;
+
+        let mut blocks = Vec::new();
+        for list in lists {
+            blocks.push(self.list(list).await?);
+        }
+
+        let blocks: Vec<&ListBlock> = blocks.iter().map(|block| block.as_ref()).collect();
+        Ok(rank_blocks(&blocks, &scorer, k, options)?)
+    }
+
+    /// A list's block, from the cache or fetched
+    async fn list(&self, list: usize) -> Result<Rc<ListBlock>, JsValue> {
+        if let Some((_, block)) = self.cache.borrow().iter().find(|(l, _)| *l == list) {
+            return Ok(block.clone());
+        }
+
+        let entry = self.coarse.entries[list];
+        let dimensions = self.header.dimensions;
+        let bytes = fetch(
+            &self.fetch_range,
+            entry.offset,
+            entry.byte_len(dimensions)?,
+            &self.bytes_fetched,
+        )
+        .await?;
+        let block = Rc::new(ListBlock::decode(&entry, dimensions, &bytes)?);
+
+        if self.options.cache_lists > 0 {
+            let mut cache = self.cache.borrow_mut();
+            if cache.len() == self.options.cache_lists {
+                cache.pop_front();
+            }
+            cache.push_back((list, block.clone()));
+        }
+        Ok(block)
+    }
+}
+
+/// Call `fetchRange(offset, length)` and check the bytes that come back
+async fn fetch(
+    fetch_range: &js_sys::Function,
+    offset: u64,
+    length: usize,
+    bytes_fetched: &Cell<u64>,
+) -> Result<Vec<u8>, JsValue> {
+    if length == 0 {
+        return Ok(Vec::new());
+    }
+
+    let promise = fetch_range.call2(
+        &JsValue::NULL,
+        &JsValue::from_f64(offset as f64),
+        &JsValue::from_f64(length as f64),
+    )?;
+    let value = JsFuture::from(js_sys::Promise::resolve(&promise)).await?;
+    if !value.is_instance_of::<js_sys::Uint8Array>()
+        && !value.is_instance_of::<js_sys::ArrayBuffer>()
+    {
+        return Err(VectorError::InvalidSnapshot {
+            message: "fetchRange must resolve to a Uint8Array or ArrayBuffer".to_string(),
+        }
+        .into());
+    }
+
+    let bytes = js_sys::Uint8Array::new(&value).to_vec();
+    bytes_fetched.set(bytes_fetched.get() + bytes.len() as u64);
+    if bytes.len() != length {
+        return Err(VectorError::InvalidSnapshot {
+            message: format!(
+                "fetchRange({}, {}) returned {} bytes",
+                offset,
+                length,
+                bytes.len()
+            ),
+        }
+        .into());
+    }
+    Ok(bytes)
+}
@@ -0,0 +1,38 @@
+/// Small deterministic PRNG (SplitMix64) so seeded algorithms produce the
+/// same output on every platform without pulling in a `rand` dependency
+#[derive(Clone, Debug)]
+pub(crate) struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[0, 1)`
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+
+    /// Uniform in `[0, bound)`; `bound` must be non-zero
+    pub(crate) fn next_below(&mut self, bound: usize) -> usize {
+        (self.next_f64() * bound as f64) as usize % bound
+    }
+
+    /// Standard normal sample (Box-Muller)
+    pub(crate) fn next_gaussian(&mut self) -> f64 {
+        // 1 - u keeps the logarithm's argument in (0, 1]
+        let u = 1.0 - self.next_f64();
+        let v = self.next_f64();
+        (-2.0 * u.ln()).sqrt() * (std::f64::consts::TAU * v).cos()
+    }
+}
@@ -0,0 +1,62 @@
+//! Output precision for returned scores. Rounding happens after ranking, so
+//! it never changes which results are returned, only how they are reported.
+
+use serde::{Deserialize, Serialize};
+
+/// How returned scores are rounded, set as `scoreRounding` in query options:
+/// `{ decimals: 4 }` or `{ fixedU16: { min: -1, max: 1 } }`
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ScoreRounding {
+    /// Round to this many decimal places (at most 15)
+    Decimals(u32),
+    /// Map `[min, max]` linearly onto the integers `0..=65535`, clamping
+    /// scores outside the range
+    FixedU16 {
+        #[serde(default = "default_fixed_min")]
+        min: f64,
+        #[serde(default = "default_fixed_max")]
+        max: f64,
+    },
+}
+
+fn default_fixed_min() -> f64 {
+    -1.0
+}
+
+fn default_fixed_max() -> f64 {
+    1.0
+}
+
+impl ScoreRounding {
+    pub(crate) fn apply(self, score: f64) -> f64 {
+        if !score.is_finite() {
+            return score;
+        }
+
+        match self {
+            ScoreRounding::Decimals(decimals) => {
+                let factor = 10f64.powi(decimals.min(15) as i32);
+                (score * factor).round() / factor
+            }
+            ScoreRounding::FixedU16 { min, max } => {
+                if max <= min {
+                    panic!("fixedU16 range must have max > min");
+                }
+                let unit = (score.clamp(min, max) - min) / (max - min);
+                (unit * u16::MAX as f64).round()
+            }
+        }
+    }
+
+    /// Round a value already in `[0, 1]`, such as a normalized score.
+    /// `FixedU16` maps `[0, 1]` itself rather than its configured score range.
+    pub(crate) fn apply_unit(self, value: f64) -> f64 {
+        match self {
+            ScoreRounding::Decimals(_) => self.apply(value),
+            ScoreRounding::FixedU16 { .. } => {
+                ScoreRounding::FixedU16 { min: 0.0, max: 1.0 }.apply(value)
+            }
+        }
+    }
+}
@@ -0,0 +1,50 @@
+//! Row-by-row access to a set of vectors.
+//!
+//! Builds (k-means, codec training, cluster trees, snapshots) read the same
+//! vectors many times over. Taking them through `Rows` lets the index hand
+//! over its segments, or rows decoded on demand, without first copying
+//! everything into one buffer.
+
+use std::borrow::Cow;
+
+pub(crate) trait Rows {
+    fn dimensions(&self) -> usize;
+
+    fn count(&self) -> usize;
+
+    /// The vector in `slot`, borrowed where it is stored at full precision
+    fn row(&self, slot: usize) -> Cow<'_, [f32]>;
+}
+
+/// Rows stored contiguously in one slot-ordered buffer
+pub(crate) struct Flat<'a> {
+    vectors: &'a [f32],
+    dimensions: usize,
+}
+
+impl<'a> Flat<'a> {
+    pub(crate) fn new(vectors: &'a [f32], dimensions: usize) -> Self {
+        Self {
+            vectors,
+            dimensions,
+        }
+    }
+}
+
+impl Rows for Flat<'_> {
+    fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    fn count(&self) -> usize {
+        match self.dimensions {
+            0 => 0,
+            dims => self.vectors.len() / dims,
+        }
+    }
+
+    fn row(&self, slot: usize) -> Cow<'_, [f32]> {
+        let dims = self.dimensions;
+        Cow::Borrowed(&self.vectors[slot * dims..(slot + 1) * dims])
+    }
+}
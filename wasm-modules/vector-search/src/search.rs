@@ -0,0 +1,717 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::calibration::{normalized_scores, ScoreNormalization};
+use crate::cutoff::{apply_auto_k, AutoK};
+use crate::error::VectorError;
+use crate::events;
+use crate::metric::{MetricKind, Scorer};
+use crate::rounding::ScoreRounding;
+use crate::trace::ScanCounts;
+use crate::{options_from_js, to_js, VectorSearch};
+
+/// Options accepted by `search`; every field is optional on the JS side
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct QueryOptions {
+    /// Metric used to score candidates (defaults to cosine)
+    pub metric: MetricKind,
+    /// Per-dimension weights applied to both query and candidates
+    pub weights: Option<Vec<f64>>,
+    /// Vector indices that must never be returned
+    pub exclude: Vec<usize>,
+    /// One group label per vector, used together with `allowed_groups`
+    pub groups: Option<Vec<u32>>,
+    /// Only vectors whose group label is in this list are returned
+    pub allowed_groups: Option<Vec<u32>>,
+    /// One parent ID per vector, e.g. the document a chunk was cut from.
+    /// Only the best-scoring vector of each parent is returned.
+    pub parents: Option<Vec<u32>>,
+    /// Initial number of approximately ranked candidates (from quantized
+    /// codes) considered before filtering (defaults to the number wanted).
+    /// Exact scans filter every candidate and ignore this.
+    pub candidate_pool: Option<usize>,
+    /// How many times an approximate search's candidate pool or cluster-tree
+    /// beam may double when filtering leaves fewer than `k` results
+    pub max_expansions: usize,
+    /// Treat query and candidates as unit vectors, scoring cosine as a plain
+    /// dot product (ignored when `weights` are set)
+    pub assume_normalized: bool,
+    /// Beam width for cluster-tree search, overriding the tree's default
+    pub beam_width: Option<usize>,
+    /// Scan every vector even when the index has an acceleration structure
+    pub exact: bool,
+    /// Skip candidates in exhaustive `VectorIndex` scans once a partial read
+    /// proves they cannot enter the top k. Applies to unweighted built-in
+    /// metrics over full-precision storage and never changes the results.
+    pub prune: bool,
+    /// Candidates taken from the compressed-code scan and re-scored at full
+    /// precision when the index is quantized (defaults to `4 * k`)
+    pub rerank_k: Option<usize>,
+    /// Cut the ranked list at the largest score gap instead of always
+    /// returning `k` results
+    pub auto_k: Option<AutoK>,
+    /// Round returned scores, e.g. for smaller payloads or snapshot tests
+    /// that must match across SIMD and scalar kernels
+    pub score_rounding: Option<ScoreRounding>,
+    /// Add a `normalized` value in `[0, 1]` to each hit, computed over the
+    /// returned result set
+    pub normalize: Option<ScoreNormalization>,
+    /// Attach read-path statistics (bytes scanned, effective bandwidth) to
+    /// `VectorIndex` result pages
+    pub trace: bool,
+    /// Return only the best-ranked of `VectorIndex` hits whose stored vectors
+    /// are bit-identical, reporting how many copies each one stands for
+    pub collapse_duplicates: bool,
+    /// Score with only the first `searchDims` dimensions of query and
+    /// candidates, trading accuracy for speed on Matryoshka (MRL)
+    /// embeddings. `VectorIndex` queries then scan every vector.
+    pub search_dims: Option<usize>,
+    /// Scoring callback for `metric: "custom"`. Functions don't survive serde,
+    /// so this is read separately by `QueryOptions::from_js`
+    #[serde(skip)]
+    pub custom_metric: Option<js_sys::Function>,
+}
+
+impl Default for QueryOptions {
+    fn default() -> Self {
+        Self {
+            metric: MetricKind::default(),
+            weights: None,
+            exclude: Vec::new(),
+            groups: None,
+            allowed_groups: None,
+            parents: None,
+            candidate_pool: None,
+            max_expansions: 3,
+            assume_normalized: false,
+            beam_width: None,
+            exact: false,
+            prune: false,
+            rerank_k: None,
+            auto_k: None,
+            score_rounding: None,
+            normalize: None,
+            trace: false,
+            collapse_duplicates: false,
+            search_dims: None,
+            custom_metric: None,
+        }
+    }
+}
+
+impl QueryOptions {
+    /// Read query options from JS, lifting out the `customMetric` callback
+    /// before the plain-data fields are deserialized
+    pub(crate) fn from_js(value: JsValue) -> Result<Self, JsValue> {
+        let key = JsValue::from_str("customMetric");
+        let callback = if value.is_object() {
+            js_sys::Reflect::get(&value, &key)?
+        } else {
+            JsValue::UNDEFINED
+        };
+
+        if callback.is_undefined() {
+            let options: QueryOptions = options_from_js(value)?;
+            if options.metric == MetricKind::Custom {
+                return Err(VectorError::InvalidOptions {
+                    message: "metric \"custom\" requires a customMetric callback".to_string(),
+                }
+                .into());
+            }
+            options.check()?;
+            return Ok(options);
+        }
+
+        let rest = js_sys::Object::assign(&js_sys::Object::new(), value.unchecked_ref());
+        js_sys::Reflect::delete_property(&rest, &key)?;
+
+        let function =
+            callback
+                .dyn_into::<js_sys::Function>()
+                .map_err(|_| VectorError::InvalidOptions {
+                    message: "customMetric must be a function".to_string(),
+                })?;
+
+        let mut options: QueryOptions = options_from_js(rest.into())?;
+        options.check()?;
+        options.custom_metric = Some(function);
+        Ok(options)
+    }
+
+    /// Fail on option values that would make every score meaningless
+    pub(crate) fn check(&self) -> Result<(), VectorError> {
+        if let Some(normalization) = &self.normalize {
+            normalization.check()?;
+        }
+        self.check_weights()
+    }
+
+    /// Weights scale each dimension's contribution, so a NaN, infinite or
+    /// negative one would make every score meaningless
+    fn check_weights(&self) -> Result<(), VectorError> {
+        let Some(weights) = &self.weights else {
+            return Ok(());
+        };
+        match weights.iter().position(|w| !w.is_finite() || *w < 0.0) {
+            Some(dimension) => Err(VectorError::InvalidOptions {
+                message: format!(
+                    "weights[{}] is {}; weights must be finite and non-negative",
+                    dimension, weights[dimension]
+                ),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Score as reported to the caller, after any `score_rounding`
+    pub(crate) fn reported_score(&self, score: f64) -> f64 {
+        match self.score_rounding {
+            Some(rounding) => rounding.apply(score),
+            None => score,
+        }
+    }
+
+    /// Normalized value as reported to the caller, after any
+    /// `score_rounding` over the `[0, 1]` range it lies in
+    pub(crate) fn reported_normalized(&self, value: f64) -> f64 {
+        match self.score_rounding {
+            Some(rounding) => rounding.apply_unit(value),
+            None => value,
+        }
+    }
+}
+
+/// Post-filter built from `QueryOptions`
+pub(crate) struct CandidateFilter<'a> {
+    excluded: HashSet<usize>,
+    groups: Option<&'a [u32]>,
+    allowed_groups: Option<HashSet<u32>>,
+}
+
+impl<'a> CandidateFilter<'a> {
+    pub(crate) fn new(options: &'a QueryOptions, count: usize) -> Result<Self, VectorError> {
+        let groups = options.groups.as_deref();
+        if let Some(groups) = groups {
+            if groups.len() != count {
+                return Err(VectorError::InvalidOptions {
+                    message: format!("groups has {} labels for {} vectors", groups.len(), count),
+                });
+            }
+        }
+
+        if options.allowed_groups.is_some() && groups.is_none() {
+            return Err(VectorError::InvalidOptions {
+                message: "allowedGroups requires group labels".to_string(),
+            });
+        }
+
+        Ok(Self {
+            excluded: options.exclude.iter().copied().collect(),
+            groups,
+            allowed_groups: options
+                .allowed_groups
+                .as_ref()
+                .map(|allowed| allowed.iter().copied().collect()),
+        })
+    }
+
+    fn is_active(&self) -> bool {
+        !self.excluded.is_empty() || self.allowed_groups.is_some()
+    }
+
+    pub(crate) fn accepts(&self, idx: usize) -> bool {
+        if self.excluded.contains(&idx) {
+            return false;
+        }
+
+        match (&self.allowed_groups, self.groups) {
+            (Some(allowed), Some(groups)) => allowed.contains(&groups[idx]),
+            _ => true,
+        }
+    }
+}
+
+/// A single ranked search result
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    pub index: usize,
+    pub score: f64,
+    /// Display-ready score, present when `normalize` is set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub normalized: Option<f64>,
+}
+
+/// Order two `(index, score)` pairs best-first for the given metric. NaN
+/// scores (from NaN vectors) sort after every other score either way.
+pub(crate) fn compare_scores(metric: MetricKind, a: &(usize, f64), b: &(usize, f64)) -> Ordering {
+    match (a.1.is_nan(), b.1.is_nan()) {
+        (false, false) if metric.higher_is_better() => b.1.total_cmp(&a.1),
+        (false, false) => a.1.total_cmp(&b.1),
+        (a_nan, b_nan) => a_nan.cmp(&b_nan),
+    }
+}
+
+/// Pair every score with its index and sort best-first for the given metric
+pub(crate) fn rank(scores: Vec<f64>, metric: MetricKind) -> Vec<(usize, f64)> {
+    let mut ranked: Vec<(usize, f64)> = scores.into_iter().enumerate().collect();
+    ranked.sort_by(|a, b| compare_scores(metric, a, b));
+    ranked
+}
+
+/// Select the best `pool` scores without sorting the whole array
+pub(crate) fn top_candidates(scores: &[f64], metric: MetricKind, pool: usize) -> Vec<(usize, f64)> {
+    let mut ranked: Vec<(usize, f64)> = scores.iter().copied().enumerate().collect();
+    if pool == 0 {
+        return Vec::new();
+    }
+
+    if pool < ranked.len() {
+        ranked.select_nth_unstable_by(pool - 1, |a, b| compare_scores(metric, a, b));
+        ranked.truncate(pool);
+    }
+
+    ranked.sort_by(|a, b| compare_scores(metric, a, b));
+    ranked
+}
+
+/// Heap entry ordered so the worst retained candidate is at the top
+#[derive(Clone, Copy, Debug)]
+struct TopKEntry {
+    /// Larger means worse, regardless of metric direction
+    badness: f64,
+    slot: usize,
+    score: f64,
+}
+
+impl PartialEq for TopKEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for TopKEntry {}
+
+impl PartialOrd for TopKEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TopKEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.badness.total_cmp(&other.badness)
+    }
+}
+
+impl TopKEntry {
+    fn new(metric: MetricKind, slot: usize, score: f64) -> Self {
+        // A positive NaN orders after every number, so NaN scores are worst
+        let badness = if score.is_nan() {
+            f64::NAN
+        } else if metric.higher_is_better() {
+            -score
+        } else {
+            score
+        };
+        Self {
+            badness,
+            slot,
+            score,
+        }
+    }
+}
+
+/// Bounded collector keeping the best `k` `(slot, score)` pairs seen so far
+#[derive(Clone)]
+pub(crate) struct TopK {
+    k: usize,
+    metric: MetricKind,
+    heap: BinaryHeap<TopKEntry>,
+}
+
+impl TopK {
+    pub(crate) fn new(k: usize, metric: MetricKind) -> Self {
+        Self {
+            k,
+            metric,
+            heap: BinaryHeap::with_capacity(k.saturating_add(1).min(1 << 16)),
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    pub(crate) fn is_full(&self) -> bool {
+        self.heap.len() >= self.k
+    }
+
+    /// Score of the worst retained candidate, once `k` are held
+    pub(crate) fn threshold(&self) -> Option<f64> {
+        if self.is_full() {
+            self.heap.peek().map(|entry| entry.score)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn push(&mut self, slot: usize, score: f64) {
+        if self.k == 0 {
+            return;
+        }
+
+        let entry = TopKEntry::new(self.metric, slot, score);
+
+        if self.heap.len() < self.k {
+            self.heap.push(entry);
+        } else if let Some(worst) = self.heap.peek() {
+            if entry < *worst {
+                self.heap.pop();
+                self.heap.push(entry);
+            }
+        }
+    }
+
+    /// Consume the collector, yielding candidates best-first one at a time
+    /// so the first is ready without ordering the rest
+    pub(crate) fn into_best_first(self) -> impl Iterator<Item = (usize, f64)> {
+        let mut heap: BinaryHeap<Reverse<TopKEntry>> = self.heap.into_iter().map(Reverse).collect();
+        std::iter::from_fn(move || heap.pop().map(|Reverse(entry)| (entry.slot, entry.score)))
+    }
+
+    /// Consume the collector, returning candidates best-first
+    pub(crate) fn into_sorted(self) -> Vec<(usize, f64)> {
+        let metric = self.metric;
+        let mut items: Vec<(usize, f64)> = self
+            .heap
+            .into_iter()
+            .map(|entry| (entry.slot, entry.score))
+            .collect();
+        items.sort_by(|a, b| compare_scores(metric, a, b));
+        items
+    }
+}
+
+/// Unbounded queue of `(slot, score)` pairs, taken best-first
+pub(crate) struct BestFirst {
+    metric: MetricKind,
+    heap: BinaryHeap<Reverse<TopKEntry>>,
+}
+
+impl BestFirst {
+    pub(crate) fn new(metric: MetricKind) -> Self {
+        Self {
+            metric,
+            heap: BinaryHeap::new(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, slot: usize, score: f64) {
+        self.heap
+            .push(Reverse(TopKEntry::new(self.metric, slot, score)));
+    }
+
+    /// Best pair still queued
+    pub(crate) fn peek(&self) -> Option<(usize, f64)> {
+        self.heap
+            .peek()
+            .map(|Reverse(entry)| (entry.slot, entry.score))
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<(usize, f64)> {
+        self.heap
+            .pop()
+            .map(|Reverse(entry)| (entry.slot, entry.score))
+    }
+}
+
+#[wasm_bindgen]
+impl VectorSearch {
+    /// Find top K vectors using the metric and weights given in `options`.
+    ///
+    /// Returns an array of `{ index, score }` ordered best-first. Euclidean
+    /// scores are distances, so they are ordered ascending. `metric:
+    /// "custom"` ranks by a `customMetric` callback, see `batchScore`.
+    #[wasm_bindgen(js_name = "search")]
+    pub fn search(
+        &self,
+        query: &[f64],
+        vectors: &[f64],
+        count: usize,
+        k: usize,
+        options: JsValue,
+    ) -> Result<JsValue, JsValue> {
+        let options = QueryOptions::from_js(options)?;
+        let timer = events::start();
+        events::search_started("VectorSearch", k, self.dimensions);
+
+        let hits = self.search_hits(query, vectors, count, k, &options)?;
+        let scan = ScanCounts {
+            vectors: count,
+            ..Default::default()
+        };
+        events::search_completed("VectorSearch", timer, &scan, hits.len(), false);
+        to_js(&hits)
+    }
+
+    /// Score every vector against the query with the metric and weights
+    /// given in `options`, returning scores in input order.
+    ///
+    /// With `metric: "custom"` this calls `customMetric(query, candidate,
+    /// weights)` once per vector, which makes it a generic batch scorer at
+    /// the cost of a JS round trip per pair.
+    #[wasm_bindgen(js_name = "batchScore")]
+    pub fn batch_score(
+        &self,
+        query: &[f64],
+        vectors: &[f64],
+        count: usize,
+        options: JsValue,
+    ) -> Result<Vec<f64>, JsValue> {
+        let options = QueryOptions::from_js(options)?;
+        let mut scores = self.score_all(query, vectors, count, &options)?;
+        if options.score_rounding.is_some() {
+            for score in &mut scores {
+                *score = options.reported_score(*score);
+            }
+        }
+        Ok(scores)
+    }
+
+    /// Find top K most similar vectors while capping results per group.
+    ///
+    /// `groups` holds one group label per vector (e.g. the source document of
+    /// a chunk). Candidates are taken in similarity order, skipping any whose
+    /// group already contributed `per_group_limit` results, so the returned
+    /// indices stay diverse across groups.
+    #[wasm_bindgen(js_name = "searchGrouped")]
+    pub fn search_grouped(
+        &self,
+        query: &[f64],
+        vectors: &[f64],
+        count: usize,
+        groups: &[u32],
+        k: usize,
+        per_group_limit: usize,
+    ) -> Vec<usize> {
+        if groups.len() != count {
+            panic!("Group labels size mismatch");
+        }
+
+        let ranked = rank(
+            self.batch_cosine_similarity(query, vectors, count),
+            MetricKind::Cosine,
+        );
+
+        let mut taken_per_group: HashMap<u32, usize> = HashMap::new();
+        let mut results = Vec::with_capacity(k);
+
+        for (idx, _) in ranked {
+            if results.len() == k {
+                break;
+            }
+
+            let taken = taken_per_group.entry(groups[idx]).or_insert(0);
+            if *taken < per_group_limit {
+                *taken += 1;
+                results.push(idx);
+            }
+        }
+
+        results
+    }
+}
+
+impl VectorSearch {
+    /// Score every vector against the query with the configured metric,
+    /// stopping at the first exception from a `customMetric` callback
+    pub(crate) fn score_all(
+        &self,
+        query: &[f64],
+        vectors: &[f64],
+        count: usize,
+        options: &QueryOptions,
+    ) -> Result<Vec<f64>, VectorError> {
+        let scorer = self.scorer(query, vectors, count, options);
+        let mut scores = Vec::with_capacity(count);
+        for vec in vectors.chunks_exact(self.dimensions) {
+            scores.push(scorer.score(vec));
+            scorer.check()?;
+        }
+        Ok(scores)
+    }
+
+    /// Scorer for `query` over `count` vectors, after checking the sizes
+    pub(crate) fn scorer<'a>(
+        &self,
+        query: &'a [f64],
+        vectors: &[f64],
+        count: usize,
+        options: &'a QueryOptions,
+    ) -> Scorer<'a, f64> {
+        if query.len() != self.dimensions {
+            panic!("Query vector dimension mismatch");
+        }
+
+        if vectors.len() != count * self.dimensions {
+            panic!("Vectors array size mismatch");
+        }
+
+        let weights = options.weights.as_deref();
+        if let Some(weights) = weights {
+            if weights.len() != self.dimensions {
+                panic!("Weight vector dimension mismatch");
+            }
+        }
+
+        Scorer::new(query, options.metric, weights, options.assume_normalized)
+            .truncated(options.search_dims)
+            .with_callback(options.custom_metric.as_ref())
+    }
+
+    /// Rank the accepted candidates, then apply any `auto_k` cutoff
+    pub(crate) fn search_hits(
+        &self,
+        query: &[f64],
+        vectors: &[f64],
+        count: usize,
+        k: usize,
+        options: &QueryOptions,
+    ) -> Result<Vec<SearchHit>, VectorError> {
+        let scores = self.score_all(query, vectors, count, options)?;
+        rank_hits(&scores, k, options)
+    }
+}
+
+/// Top `k` hits from the score of every vector, applying the filtering and
+/// result-shaping query options as `VectorSearch.search` does
+pub(crate) fn rank_hits(
+    scores: &[f64],
+    k: usize,
+    options: &QueryOptions,
+) -> Result<Vec<SearchHit>, VectorError> {
+    let filter = CandidateFilter::new(options, scores.len())?;
+
+    let mut ranked = select_hits(scores, k, options, |idx| {
+        !filter.is_active() || filter.accepts(idx)
+    })?;
+    if let Some(auto_k) = &options.auto_k {
+        apply_auto_k(&mut ranked, auto_k, k);
+    }
+
+    let normalized = normalized_scores(&ranked, options);
+    Ok(ranked
+        .into_iter()
+        .zip(normalized)
+        .map(|((index, score), normalized)| SearchHit {
+            index,
+            score: options.reported_score(score),
+            normalized,
+        })
+        .collect())
+}
+
+/// Best `want` accepted candidates, best-first for `metric`, filtering the
+/// scores of every candidate in one pass
+pub(crate) fn select_filtered(
+    scores: &[f64],
+    want: usize,
+    metric: MetricKind,
+    accepts: impl Fn(usize) -> bool,
+) -> Vec<(usize, f64)> {
+    let mut top = TopK::new(want, metric);
+    for (idx, &score) in scores.iter().enumerate() {
+        if accepts(idx) {
+            top.push(idx, score);
+        }
+    }
+    top.into_sorted()
+}
+
+/// `select_filtered` for a query's own metric, keeping only the best
+/// candidate of each parent when `options.parents` is set
+pub(crate) fn select_hits(
+    scores: &[f64],
+    want: usize,
+    options: &QueryOptions,
+    accepts: impl Fn(usize) -> bool,
+) -> Result<Vec<(usize, f64)>, VectorError> {
+    let Some(parents) = &options.parents else {
+        return Ok(select_filtered(scores, want, options.metric, accepts));
+    };
+    if parents.len() != scores.len() {
+        return Err(VectorError::InvalidOptions {
+            message: format!(
+                "parents has {} IDs for {} vectors",
+                parents.len(),
+                scores.len()
+            ),
+        });
+    }
+
+    let mut best: HashMap<u32, (usize, f64)> = HashMap::new();
+    for (idx, &score) in scores.iter().enumerate() {
+        if !accepts(idx) {
+            continue;
+        }
+        let candidate = (idx, score);
+        best.entry(parents[idx])
+            .and_modify(|held| {
+                if compare_scores(options.metric, &candidate, held) == Ordering::Less {
+                    *held = candidate;
+                }
+            })
+            .or_insert(candidate);
+    }
+
+    let mut top = TopK::new(want, options.metric);
+    for (idx, score) in best.into_values() {
+        top.push(idx, score);
+    }
+    Ok(top.into_sorted())
+}
+
+/// Select up to `want` accepted candidates from approximate `scores`.
+///
+/// Only the best `candidatePool` are filtered at first; the pool doubles
+/// while filtering leaves fewer than `want`, up to `max_expansions` times or
+/// until the whole corpus is considered. Only then is a short list returned.
+pub(crate) fn select_expanding(
+    scores: &[f64],
+    want: usize,
+    metric: MetricKind,
+    options: &QueryOptions,
+    accepts: impl Fn(usize) -> bool,
+) -> Vec<(usize, f64)> {
+    let count = scores.len();
+    let mut pool = options.candidate_pool.unwrap_or(want).max(want).min(count);
+    let mut expansions = 0;
+
+    loop {
+        let selected: Vec<(usize, f64)> = top_candidates(scores, metric, pool)
+            .into_iter()
+            .filter(|&(idx, _)| accepts(idx))
+            .take(want)
+            .collect();
+
+        if selected.len() == want || pool == count || expansions == options.max_expansions {
+            if expansions > 0 {
+                log!(
+                    "Expanded candidate pool {} times to {} for {} results",
+                    expansions,
+                    pool,
+                    selected.len()
+                );
+            }
+            return selected;
+        }
+
+        pool = pool.saturating_mul(2).min(count);
+        expansions += 1;
+    }
+}
@@ -0,0 +1,375 @@
+//! Corpus storage in a `SharedArrayBuffer`, so several Web Workers can
+//! search one copy of the vectors instead of each holding their own.
+//!
+//! Only the raw vectors are shared. `search` and `batchScore` work as on
+//! `VectorSearch`, with every query option, over the stored vectors instead
+//! of a passed-in array. `VectorIndex` storage (segments, cluster trees,
+//! codes) lives in each module instance's own memory and is not shared;
+//! workers that need those build their own index.
+//!
+//! Buffer layout: a header of four `i32`s (`MAGIC`, sequence, dimensions,
+//! count) followed by `capacity * dimensions` f32 values.
+//!
+//! Synchronization rules:
+//! - Exactly one writer: the `SharedCorpus` that created the buffer. Views
+//!   made with `SharedCorpus.attach` in other workers are read-only.
+//! - The sequence is a seqlock. The writer makes it odd before touching the
+//!   data and even again afterwards, then wakes any waiting readers with
+//!   `Atomics.notify`. A reader notes it before a scan and re-checks it
+//!   after, so a scan that overlapped a write is detected and retried (up to
+//!   `MAX_READ_ATTEMPTS` times, after which the search fails with
+//!   `ConcurrentWrite` and can be retried later).
+//! - A reader that finds a write in progress waits for it before scanning:
+//!   workers block in `Atomics.wait` (for at most `WAIT_TIMEOUT_MS`), while
+//!   the main thread, which may not block, spins for at most `MAX_SPINS`
+//!   sequence reads.
+//! - Readers never block writers, so batch writes (`pushBatch`) to keep the
+//!   write windows few and short.
+
+use wasm_bindgen::prelude::*;
+
+use crate::error::VectorError;
+use crate::metric::Scorer;
+use crate::search::{rank_hits, QueryOptions};
+use crate::to_js;
+
+/// Identifies buffers laid out by this module ("VSC1")
+const MAGIC: i32 = 0x5653_4331;
+
+const HEADER_MAGIC: u32 = 0;
+const HEADER_SEQUENCE: u32 = 1;
+const HEADER_DIMENSIONS: u32 = 2;
+const HEADER_COUNT: u32 = 3;
+const HEADER_LEN: u32 = 4;
+const HEADER_BYTES: u32 = HEADER_LEN * 4;
+
+/// Scans retried before a search gives up on a busy writer
+const MAX_READ_ATTEMPTS: u32 = 8;
+
+/// Longest a worker blocks in `Atomics.wait` for one write to finish
+const WAIT_TIMEOUT_MS: f64 = 50.0;
+
+/// Sequence reads the main thread spins through waiting for one write to
+/// finish
+const MAX_SPINS: u32 = 1 << 16;
+
+/// Rows copied out of shared memory per block while scanning
+const SCAN_BLOCK_ROWS: usize = 1024;
+
+/// A vector corpus stored in a `SharedArrayBuffer`.
+///
+/// Create one with `new SharedCorpus(dimensions, capacity)` in the owning
+/// worker, post its `buffer` to other workers, and call
+/// `SharedCorpus.attach(buffer)` there for a read-only view.
+#[wasm_bindgen]
+pub struct SharedCorpus {
+    buffer: js_sys::SharedArrayBuffer,
+    header: js_sys::Int32Array,
+    data: js_sys::Float32Array,
+    dimensions: usize,
+    capacity: usize,
+    read_only: bool,
+}
+
+#[wasm_bindgen]
+impl SharedCorpus {
+    /// Allocate shared storage for up to `capacity` vectors
+    #[wasm_bindgen(constructor)]
+    pub fn new(dimensions: usize, capacity: usize) -> Result<SharedCorpus, JsValue> {
+        if dimensions == 0 {
+            return Err(VectorError::InvalidOptions {
+                message: "SharedCorpus needs at least one dimension".to_string(),
+            }
+            .into());
+        }
+
+        let bytes = capacity
+            .checked_mul(dimensions)
+            .and_then(|floats| floats.checked_mul(4))
+            .and_then(|bytes| bytes.checked_add(HEADER_BYTES as usize))
+            .and_then(|bytes| u32::try_from(bytes).ok())
+            .ok_or(VectorError::CapacityExceeded { capacity })?;
+
+        let buffer = js_sys::SharedArrayBuffer::new(bytes);
+        let corpus = Self::view(buffer, dimensions, capacity, false);
+        corpus.header.set_index(HEADER_MAGIC, MAGIC);
+        corpus
+            .header
+            .set_index(HEADER_DIMENSIONS, dimensions as i32);
+
+        log!(
+            "SharedCorpus allocated for {} vectors of {} dimensions",
+            capacity,
+            dimensions
+        );
+        Ok(corpus)
+    }
+
+    /// Attach a read-only view to a buffer created by another worker's
+    /// `SharedCorpus`
+    pub fn attach(buffer: js_sys::SharedArrayBuffer) -> Result<SharedCorpus, JsValue> {
+        let invalid = |message: &str| VectorError::InvalidBuffer {
+            message: message.to_string(),
+        };
+
+        if buffer.byte_length() < HEADER_BYTES {
+            return Err(invalid("buffer is smaller than the header").into());
+        }
+
+        let header = js_sys::Int32Array::new_with_byte_offset_and_length(&buffer, 0, HEADER_LEN);
+        if js_sys::Atomics::load(&header, HEADER_MAGIC)? != MAGIC {
+            return Err(invalid("buffer was not created by SharedCorpus").into());
+        }
+
+        let dimensions = js_sys::Atomics::load(&header, HEADER_DIMENSIONS)? as usize;
+        if dimensions == 0 {
+            return Err(invalid("buffer has zero dimensions").into());
+        }
+
+        let capacity = (buffer.byte_length() - HEADER_BYTES) as usize / (dimensions * 4);
+        Ok(Self::view(buffer, dimensions, capacity, true))
+    }
+
+    /// The underlying buffer, to post to other workers
+    #[wasm_bindgen(getter)]
+    pub fn buffer(&self) -> js_sys::SharedArrayBuffer {
+        self.buffer.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    /// Maximum number of vectors the buffer holds
+    #[wasm_bindgen(getter)]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Whether this is an attached view rather than the writer
+    #[wasm_bindgen(getter, js_name = "readOnly")]
+    pub fn read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Number of vectors currently stored
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> Result<usize, JsValue> {
+        Ok(js_sys::Atomics::load(&self.header, HEADER_COUNT)? as usize)
+    }
+
+    /// Append a flattened batch of `count` vectors in one write window,
+    /// returning the index of the first
+    #[wasm_bindgen(js_name = "pushBatch")]
+    pub fn push_batch(&mut self, vectors: &[f32], count: usize) -> Result<usize, JsValue> {
+        if vectors.len() != count * self.dimensions {
+            panic!("Vectors array size mismatch");
+        }
+
+        self.check_writable()?;
+        let start = self.size()?;
+        if start + count > self.capacity {
+            return Err(VectorError::CapacityExceeded {
+                capacity: self.capacity,
+            }
+            .into());
+        }
+
+        self.write(|corpus| {
+            let offset = (start * corpus.dimensions) as u32;
+            corpus
+                .data
+                .subarray(offset, offset + vectors.len() as u32)
+                .copy_from(vectors);
+            js_sys::Atomics::store(&corpus.header, HEADER_COUNT, (start + count) as i32)?;
+            Ok(())
+        })?;
+
+        Ok(start)
+    }
+
+    /// Append one vector, returning its index
+    pub fn push(&mut self, vector: &[f32]) -> Result<usize, JsValue> {
+        self.push_batch(vector, 1)
+    }
+
+    /// Overwrite the vector at `index`
+    pub fn set(&mut self, index: usize, vector: &[f32]) -> Result<(), JsValue> {
+        if vector.len() != self.dimensions {
+            panic!("Vector dimension mismatch");
+        }
+
+        self.check_writable()?;
+        if index >= self.size()? {
+            panic!("Index out of bounds");
+        }
+
+        self.write(|corpus| {
+            let offset = (index * corpus.dimensions) as u32;
+            corpus
+                .data
+                .subarray(offset, offset + vector.len() as u32)
+                .copy_from(vector);
+            Ok(())
+        })
+    }
+
+    /// Remove every vector, keeping the allocation
+    pub fn clear(&mut self) -> Result<(), JsValue> {
+        self.check_writable()?;
+        self.write(|corpus| {
+            js_sys::Atomics::store(&corpus.header, HEADER_COUNT, 0)?;
+            Ok(())
+        })
+    }
+
+    /// Find the top K vectors for `query`, with the same options and result
+    /// shape as `VectorSearch.search`. Group labels and `parents` must hold
+    /// one entry per stored vector. Safe to call while the writer is active,
+    /// following the rules in the module docs.
+    pub fn search(&self, query: &[f32], k: usize, options: JsValue) -> Result<JsValue, JsValue> {
+        let options = QueryOptions::from_js(options)?;
+        let scores = self.score_all(query, &options)?;
+        to_js(&rank_hits(&scores, k, &options)?)
+    }
+
+    /// Score every stored vector against `query`, in storage order, as
+    /// `VectorSearch.batchScore` does
+    #[wasm_bindgen(js_name = "batchScore")]
+    pub fn batch_score(&self, query: &[f32], options: JsValue) -> Result<Vec<f64>, JsValue> {
+        let options = QueryOptions::from_js(options)?;
+        let mut scores = self.score_all(query, &options)?;
+        if options.score_rounding.is_some() {
+            for score in &mut scores {
+                *score = options.reported_score(*score);
+            }
+        }
+        Ok(scores)
+    }
+}
+
+impl SharedCorpus {
+    fn view(
+        buffer: js_sys::SharedArrayBuffer,
+        dimensions: usize,
+        capacity: usize,
+        read_only: bool,
+    ) -> Self {
+        let header = js_sys::Int32Array::new_with_byte_offset_and_length(&buffer, 0, HEADER_LEN);
+        let data = js_sys::Float32Array::new_with_byte_offset_and_length(
+            &buffer,
+            HEADER_BYTES,
+            (capacity * dimensions) as u32,
+        );
+
+        Self {
+            buffer,
+            header,
+            data,
+            dimensions,
+            capacity,
+            read_only,
+        }
+    }
+
+    fn check_writable(&self) -> Result<(), VectorError> {
+        if self.read_only {
+            return Err(VectorError::ReadOnly);
+        }
+        Ok(())
+    }
+
+    /// Run `update` inside a seqlock write window
+    fn write(&mut self, update: impl FnOnce(&Self) -> Result<(), JsValue>) -> Result<(), JsValue> {
+        js_sys::Atomics::add(&self.header, HEADER_SEQUENCE, 1)?;
+        let result = update(self);
+        js_sys::Atomics::add(&self.header, HEADER_SEQUENCE, 1)?;
+        js_sys::Atomics::notify(&self.header, HEADER_SEQUENCE)?;
+        result
+    }
+
+    /// The sequence once no write is in progress, or `None` if the write
+    /// outlasted the wait. `can_wait` is cleared the first time
+    /// `Atomics.wait` throws, as it does on the main thread, and spinning is
+    /// used from then on.
+    fn settled_sequence(&self, can_wait: &mut bool) -> Result<Option<i32>, JsValue> {
+        let mut spins = 0;
+        loop {
+            let sequence = js_sys::Atomics::load(&self.header, HEADER_SEQUENCE)?;
+            if sequence % 2 == 0 {
+                return Ok(Some(sequence));
+            }
+
+            if *can_wait {
+                match js_sys::Atomics::wait_with_timeout(
+                    &self.header,
+                    HEADER_SEQUENCE,
+                    sequence,
+                    WAIT_TIMEOUT_MS,
+                ) {
+                    Ok(outcome) if outcome == "timed-out" => return Ok(None),
+                    Ok(_) => {}
+                    Err(_) => *can_wait = false,
+                }
+            } else {
+                spins += 1;
+                if spins == MAX_SPINS {
+                    return Ok(None);
+                }
+                std::hint::spin_loop();
+            }
+        }
+    }
+
+    /// Score every stored vector, retrying scans that overlapped a write
+    fn score_all(&self, query: &[f32], options: &QueryOptions) -> Result<Vec<f64>, JsValue> {
+        if query.len() != self.dimensions {
+            panic!("Query vector dimension mismatch");
+        }
+
+        let weights = options.weights.as_deref();
+        if let Some(weights) = weights {
+            if weights.len() != self.dimensions {
+                panic!("Weight vector dimension mismatch");
+            }
+        }
+
+        let scorer = Scorer::new(query, options.metric, weights, options.assume_normalized)
+            .truncated(options.search_dims)
+            .with_callback(options.custom_metric.as_ref());
+        let mut block = vec![0.0f32; SCAN_BLOCK_ROWS * self.dimensions];
+
+        let mut can_wait = true;
+        for _ in 0..MAX_READ_ATTEMPTS {
+            let Some(before) = self.settled_sequence(&mut can_wait)? else {
+                continue;
+            };
+
+            let count = js_sys::Atomics::load(&self.header, HEADER_COUNT)? as usize;
+            let mut scores = Vec::with_capacity(count);
+            for start in (0..count).step_by(SCAN_BLOCK_ROWS) {
+                let rows = SCAN_BLOCK_ROWS.min(count - start);
+                let floats = rows * self.dimensions;
+                let offset = (start * self.dimensions) as u32;
+                self.data
+                    .subarray(offset, offset + floats as u32)
+                    .copy_to(&mut block[..floats]);
+
+                for row in block[..floats].chunks_exact(self.dimensions) {
+                    scores.push(scorer.score(row));
+                    scorer.check()?;
+                }
+            }
+
+            if js_sys::Atomics::load(&self.header, HEADER_SEQUENCE)? == before {
+                return Ok(scores);
+            }
+        }
+
+        Err(VectorError::ConcurrentWrite {
+            attempts: MAX_READ_ATTEMPTS,
+        }
+        .into())
+    }
+}
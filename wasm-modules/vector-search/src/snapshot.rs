@@ -0,0 +1,562 @@
+//! Serialized snapshots laid out for partial reads over HTTP range requests.
+//!
+//! Vectors are partitioned into lists by k-means, and each list is stored
+//! contiguously, so a reader needs the small header and coarse index plus
+//! only the lists it probes. All integers and floats are little-endian.
+//!
+//! ```text
+//! offset 0    header, 64 bytes
+//!             magic "VSNP", version u32, dimensions u32, lists u32,
+//!             count u64, data offset u64, defaults length u32,
+//!             zero padding
+//! offset 64   directory, 16 bytes per list:
+//!             byte offset u64, rows u32, zero u32
+//!             centroids, lists * dimensions f32
+//!             query defaults, JSON as from `queryDefaults` (empty when the
+//!             collection has none)
+//! data offset list blocks, each rows * u32 IDs then rows * dimensions f32
+//! ```
+//!
+//! Version 1 snapshots have no query defaults; their padding reads as a
+//! zero defaults length, so they still open.
+
+// The decoding half is only used by `RemoteSnapshot`
+#![cfg_attr(not(feature = "remote"), allow(dead_code))]
+
+use std::collections::HashSet;
+
+use serde::Deserialize;
+
+use crate::calibration::normalized_scores;
+use crate::cluster_tree::kmeans;
+use crate::cutoff::apply_auto_k;
+use crate::error::VectorError;
+use crate::index::IndexHit;
+use crate::metric::Scorer;
+use crate::rng::SplitMix64;
+use crate::rows::Rows;
+use crate::search::{QueryOptions, TopK};
+
+const MAGIC: &[u8; 4] = b"VSNP";
+const VERSION: u32 = 2;
+
+/// Size of the fixed header, the first range a reader fetches
+pub(crate) const HEADER_BYTES: usize = 64;
+
+const ENTRY_BYTES: usize = 16;
+
+/// Largest `dimensions` a header may declare
+const MAX_DIMENSIONS: usize = 1 << 16;
+
+/// Largest `lists` a header may declare
+const MAX_LISTS: usize = 1 << 20;
+
+/// Largest query defaults section a header may declare
+const MAX_DEFAULTS_BYTES: usize = 1 << 16;
+
+/// Options for `VectorIndex.snapshot`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SnapshotOptions {
+    /// Number of lists (defaults to the square root of the vector count)
+    pub lists: Option<usize>,
+    /// k-means iterations used to partition the vectors
+    pub iterations: usize,
+    pub seed: u64,
+}
+
+impl Default for SnapshotOptions {
+    fn default() -> Self {
+        Self {
+            lists: None,
+            iterations: 10,
+            seed: 42,
+        }
+    }
+}
+
+fn invalid(message: &str) -> VectorError {
+    VectorError::InvalidSnapshot {
+        message: message.to_string(),
+    }
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+}
+
+fn read_f32s(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+        .collect()
+}
+
+/// Parsed fixed-size header
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct SnapshotHeader {
+    pub dimensions: usize,
+    pub lists: usize,
+    pub count: u64,
+    pub data_offset: u64,
+    /// Length of the query defaults following the coarse index
+    pub defaults_len: usize,
+}
+
+impl SnapshotHeader {
+    pub(crate) fn decode(bytes: &[u8]) -> Result<Self, VectorError> {
+        if bytes.len() < HEADER_BYTES {
+            return Err(invalid("header is truncated"));
+        }
+        if &bytes[..4] != MAGIC {
+            return Err(invalid("not a vector snapshot"));
+        }
+        if !(1..=VERSION).contains(&read_u32(bytes, 4)) {
+            return Err(invalid("unsupported snapshot version"));
+        }
+
+        let header = Self {
+            dimensions: read_u32(bytes, 8) as usize,
+            lists: read_u32(bytes, 12) as usize,
+            count: read_u64(bytes, 16),
+            data_offset: read_u64(bytes, 24),
+            defaults_len: read_u32(bytes, 32) as usize,
+        };
+        if header.dimensions == 0 {
+            return Err(invalid("snapshot has zero dimensions"));
+        }
+        if header.dimensions > MAX_DIMENSIONS {
+            return Err(invalid("snapshot has too many dimensions"));
+        }
+        if header.lists > MAX_LISTS {
+            return Err(invalid("snapshot has too many lists"));
+        }
+        if header.defaults_len > MAX_DEFAULTS_BYTES {
+            return Err(invalid("query defaults are too large"));
+        }
+        let coarse_end = header
+            .coarse_len()?
+            .checked_add(HEADER_BYTES + header.defaults_len)
+            .ok_or_else(|| invalid("coarse index is too large"))?;
+        if header.data_offset != coarse_end as u64 {
+            return Err(invalid("coarse index size does not match the header"));
+        }
+        header.data_end()?;
+        Ok(header)
+    }
+
+    /// Byte length of the directory and centroids following the header
+    pub(crate) fn coarse_len(&self) -> Result<usize, VectorError> {
+        self.dimensions
+            .checked_mul(4)
+            .and_then(|centroid| centroid.checked_add(ENTRY_BYTES))
+            .and_then(|per_list| per_list.checked_mul(self.lists))
+            .ok_or_else(|| invalid("coarse index is too large"))
+    }
+
+    /// Offset just past the last list block
+    fn data_end(&self) -> Result<u64, VectorError> {
+        (self.dimensions as u64 + 1)
+            .checked_mul(4)
+            .and_then(|row| row.checked_mul(self.count))
+            .and_then(|data| data.checked_add(self.data_offset))
+            .ok_or_else(|| invalid("data region is too large"))
+    }
+}
+
+/// Where one list's block lives
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct ListEntry {
+    pub offset: u64,
+    pub rows: usize,
+}
+
+impl ListEntry {
+    pub(crate) fn byte_len(&self, dimensions: usize) -> Result<usize, VectorError> {
+        dimensions
+            .checked_add(1)
+            .and_then(|words| words.checked_mul(4))
+            .and_then(|row| row.checked_mul(self.rows))
+            .ok_or_else(|| invalid("list block is too large"))
+    }
+}
+
+/// Directory and centroids, enough to decide which lists to fetch
+#[derive(Clone, Debug)]
+pub(crate) struct CoarseIndex {
+    pub entries: Vec<ListEntry>,
+    pub centroids: Vec<f32>,
+}
+
+impl CoarseIndex {
+    /// Decode the directory, checking that every list lies inside the data
+    /// region and that the lists hold `count` rows between them, so nothing
+    /// outside the snapshot is ever fetched
+    pub(crate) fn decode(header: &SnapshotHeader, bytes: &[u8]) -> Result<Self, VectorError> {
+        if bytes.len() != header.coarse_len()? {
+            return Err(invalid("coarse index is truncated"));
+        }
+
+        let directory_len = header.lists * ENTRY_BYTES;
+        let entries: Vec<ListEntry> = (0..header.lists)
+            .map(|list| ListEntry {
+                offset: read_u64(bytes, list * ENTRY_BYTES),
+                rows: read_u32(bytes, list * ENTRY_BYTES + 8) as usize,
+            })
+            .collect();
+
+        let data_end = header.data_end()?;
+        let mut rows = 0u64;
+        for entry in &entries {
+            let end = entry
+                .offset
+                .checked_add(entry.byte_len(header.dimensions)? as u64)
+                .ok_or_else(|| invalid("list block is out of bounds"))?;
+            if entry.offset < header.data_offset || end > data_end {
+                return Err(invalid("list block is out of bounds"));
+            }
+            rows += entry.rows as u64;
+        }
+        if rows != header.count {
+            return Err(invalid("directory rows do not add up to the count"));
+        }
+
+        Ok(Self {
+            entries,
+            centroids: read_f32s(&bytes[directory_len..]),
+        })
+    }
+
+    /// The `probes` lists whose centroids score best against the query
+    pub(crate) fn nearest_lists(&self, scorer: &Scorer<f32>, probes: usize) -> Vec<usize> {
+        let mut top = TopK::new(probes, scorer.metric());
+        let dimensions = scorer.dimensions();
+        for (list, centroid) in self.centroids.chunks_exact(dimensions).enumerate() {
+            if self.entries[list].rows > 0 {
+                top.push(list, scorer.score(centroid));
+            }
+        }
+        top.into_sorted()
+            .into_iter()
+            .map(|(list, _)| list)
+            .collect()
+    }
+}
+
+/// One fetched list
+#[derive(Clone, Debug)]
+pub(crate) struct ListBlock {
+    pub ids: Vec<u32>,
+    pub vectors: Vec<f32>,
+}
+
+impl ListBlock {
+    pub(crate) fn decode(
+        entry: &ListEntry,
+        dimensions: usize,
+        bytes: &[u8],
+    ) -> Result<Self, VectorError> {
+        if bytes.len() != entry.byte_len(dimensions)? {
+            return Err(invalid("list block is truncated"));
+        }
+
+        let ids_len = entry.rows * 4;
+        Ok(Self {
+            ids: (0..entry.rows)
+                .map(|row| read_u32(bytes, row * 4))
+                .collect(),
+            vectors: read_f32s(&bytes[ids_len..]),
+        })
+    }
+}
+
+/// Fail on query options that need the whole collection
+/// (`collapseDuplicates`, `parents` and group labels), which a snapshot
+/// query only sees the probed lists of
+pub(crate) fn check_block_options(options: &QueryOptions) -> Result<(), VectorError> {
+    if options.collapse_duplicates
+        || options.parents.is_some()
+        || options.groups.is_some()
+        || options.allowed_groups.is_some()
+    {
+        return Err(VectorError::InvalidOptions {
+            message: "collapseDuplicates, parents and groups are not supported by snapshots"
+                .to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Rank the vectors of the fetched `blocks` against the query, applying
+/// the result-shaping query options as `VectorIndex.search` does
+pub(crate) fn rank_blocks(
+    blocks: &[&ListBlock],
+    scorer: &Scorer<f32>,
+    k: usize,
+    options: &QueryOptions,
+) -> Result<Vec<IndexHit>, VectorError> {
+    check_block_options(options)?;
+
+    let dimensions = scorer.dimensions();
+    let excluded: HashSet<u32> = options.exclude.iter().map(|&id| id as u32).collect();
+
+    let ids: Vec<u32> = blocks
+        .iter()
+        .flat_map(|block| block.ids.iter().copied())
+        .collect();
+    let rows = blocks
+        .iter()
+        .flat_map(|block| block.vectors.chunks_exact(dimensions));
+
+    let mut top = TopK::new(k, options.metric);
+    for (candidate, row) in rows.enumerate() {
+        if !excluded.contains(&ids[candidate]) {
+            top.push(candidate, scorer.score(row));
+            scorer.check()?;
+        }
+    }
+
+    let mut ranked = top.into_sorted();
+    if let Some(auto_k) = &options.auto_k {
+        apply_auto_k(&mut ranked, auto_k, k);
+    }
+
+    let normalized = normalized_scores(&ranked, options);
+    Ok(ranked
+        .into_iter()
+        .zip(normalized)
+        .map(|((candidate, score), normalized)| IndexHit {
+            id: ids[candidate],
+            score: options.reported_score(score),
+            normalized,
+            collapsed: None,
+        })
+        .collect())
+}
+
+/// Serialize `rows`, where slot `i` holds the vector for `ids[i]`, with the
+/// collection's serialized query `defaults`. Fails rather than writing a
+/// header `SnapshotHeader::decode` would reject.
+pub(crate) fn encode_snapshot(
+    rows: &impl Rows,
+    ids: &[u32],
+    defaults: &[u8],
+    options: &SnapshotOptions,
+) -> Result<Vec<u8>, VectorError> {
+    let dimensions = rows.dimensions();
+    if dimensions == 0 || dimensions > MAX_DIMENSIONS {
+        return Err(invalid(&format!(
+            "snapshots hold 1 to {} dimensions, not {}",
+            MAX_DIMENSIONS, dimensions
+        )));
+    }
+    if defaults.len() > MAX_DEFAULTS_BYTES {
+        return Err(invalid("query defaults are too large to snapshot"));
+    }
+
+    let count = ids.len();
+    let lists = match count {
+        0 => 0,
+        _ => options
+            .lists
+            .unwrap_or_else(|| (count as f64).sqrt().ceil() as usize)
+            .clamp(1, count.min(MAX_LISTS)),
+    };
+
+    let members: Vec<usize> = (0..count).collect();
+    let assignment = if lists > 0 {
+        let mut rng = SplitMix64::new(options.seed);
+        kmeans(rows, &members, lists, options.iterations, &mut rng)
+    } else {
+        Vec::new()
+    };
+
+    let mut partitions = vec![Vec::new(); lists];
+    for (&slot, &list) in members.iter().zip(&assignment) {
+        partitions[list].push(slot);
+    }
+
+    let coarse_len = lists * (ENTRY_BYTES + dimensions * 4);
+    let data_offset = (HEADER_BYTES + coarse_len + defaults.len()) as u64;
+
+    let mut bytes = Vec::with_capacity(data_offset as usize + count * (dimensions + 1) * 4);
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.extend_from_slice(&(dimensions as u32).to_le_bytes());
+    bytes.extend_from_slice(&(lists as u32).to_le_bytes());
+    bytes.extend_from_slice(&(count as u64).to_le_bytes());
+    bytes.extend_from_slice(&data_offset.to_le_bytes());
+    bytes.extend_from_slice(&(defaults.len() as u32).to_le_bytes());
+    bytes.resize(HEADER_BYTES, 0);
+
+    let mut offset = data_offset;
+    for partition in &partitions {
+        bytes.extend_from_slice(&offset.to_le_bytes());
+        bytes.extend_from_slice(&(partition.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        offset += (partition.len() * (4 + dimensions * 4)) as u64;
+    }
+
+    for partition in &partitions {
+        let mut centroid = vec![0.0f64; dimensions];
+        for &slot in partition {
+            for (c, &x) in centroid.iter_mut().zip(rows.row(slot).iter()) {
+                *c += x as f64;
+            }
+        }
+        let scale = 1.0 / partition.len().max(1) as f64;
+        for c in centroid {
+            bytes.extend_from_slice(&((c * scale) as f32).to_le_bytes());
+        }
+    }
+    bytes.extend_from_slice(defaults);
+
+    for partition in &partitions {
+        for &slot in partition {
+            bytes.extend_from_slice(&ids[slot].to_le_bytes());
+        }
+        for &slot in partition {
+            for x in rows.row(slot).iter() {
+                bytes.extend_from_slice(&x.to_le_bytes());
+            }
+        }
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metric::MetricKind;
+    use crate::rows::Flat;
+
+    const DIMENSIONS: usize = 8;
+    const DEFAULTS: &[u8] = br#"{"metric":"euclidean"}"#;
+
+    fn sample() -> (Vec<f32>, Vec<u32>, Vec<u8>) {
+        let mut rng = SplitMix64::new(7);
+        let vectors: Vec<f32> = (0..200 * DIMENSIONS)
+            .map(|_| rng.next_f64() as f32 * 2.0 - 1.0)
+            .collect();
+        let ids: Vec<u32> = (0..200).map(|i| i * 3 + 1).collect();
+        let bytes = encode_snapshot(
+            &Flat::new(&vectors, DIMENSIONS),
+            &ids,
+            DEFAULTS,
+            &SnapshotOptions::default(),
+        )
+        .unwrap();
+        (vectors, ids, bytes)
+    }
+
+    fn decode_all(bytes: &[u8]) -> (SnapshotHeader, CoarseIndex, Vec<ListBlock>) {
+        let header = SnapshotHeader::decode(&bytes[..HEADER_BYTES]).unwrap();
+        let coarse_end = HEADER_BYTES + header.coarse_len().unwrap();
+        let coarse = CoarseIndex::decode(&header, &bytes[HEADER_BYTES..coarse_end]).unwrap();
+        let blocks = coarse
+            .entries
+            .iter()
+            .map(|entry| {
+                let start = entry.offset as usize;
+                let end = start + entry.byte_len(header.dimensions).unwrap();
+                ListBlock::decode(entry, header.dimensions, &bytes[start..end]).unwrap()
+            })
+            .collect();
+        (header, coarse, blocks)
+    }
+
+    #[test]
+    fn round_trip_restores_every_row() {
+        let (vectors, ids, bytes) = sample();
+        let (header, coarse, blocks) = decode_all(&bytes);
+        assert_eq!((header.dimensions, header.count), (DIMENSIONS, 200));
+        assert_eq!(coarse.centroids.len(), header.lists * DIMENSIONS);
+
+        let coarse_end = HEADER_BYTES + header.coarse_len().unwrap();
+        assert_eq!(&bytes[coarse_end..header.data_offset as usize], DEFAULTS);
+
+        let mut restored = 0;
+        for block in &blocks {
+            for (id, row) in block.ids.iter().zip(block.vectors.chunks_exact(DIMENSIONS)) {
+                let slot = ids.iter().position(|x| x == id).unwrap();
+                assert_eq!(row, &vectors[slot * DIMENSIONS..(slot + 1) * DIMENSIONS]);
+                restored += 1;
+            }
+        }
+        assert_eq!(restored, 200);
+
+        // Ranking every list finds the query's own row first
+        let query = &vectors[5 * DIMENSIONS..6 * DIMENSIONS];
+        let options = QueryOptions {
+            metric: MetricKind::Euclidean,
+            ..Default::default()
+        };
+        let scorer = Scorer::new(query, options.metric, None, false);
+        let refs: Vec<&ListBlock> = blocks.iter().collect();
+        let hits = rank_blocks(&refs, &scorer, 3, &options).unwrap();
+        assert_eq!(hits[0].id, ids[5]);
+    }
+
+    #[test]
+    fn truncated_parts_are_rejected() {
+        let (_, _, bytes) = sample();
+        let (header, coarse, _) = decode_all(&bytes);
+
+        assert!(SnapshotHeader::decode(&bytes[..HEADER_BYTES - 1]).is_err());
+        let coarse_end = HEADER_BYTES + header.coarse_len().unwrap();
+        assert!(CoarseIndex::decode(&header, &bytes[HEADER_BYTES..coarse_end - 4]).is_err());
+
+        let entry = coarse.entries[0];
+        let start = entry.offset as usize;
+        let end = start + entry.byte_len(DIMENSIONS).unwrap();
+        assert!(ListBlock::decode(&entry, DIMENSIONS, &bytes[start..end - 1]).is_err());
+
+        let mut short = header;
+        short.count -= 1;
+        assert!(CoarseIndex::decode(&short, &bytes[HEADER_BYTES..coarse_end]).is_err());
+    }
+
+    #[test]
+    fn overflowing_headers_are_rejected() {
+        let (_, _, bytes) = sample();
+        let header = &bytes[..HEADER_BYTES];
+        let patched = |at: usize, value: &[u8]| {
+            let mut bad = header.to_vec();
+            bad[at..at + value.len()].copy_from_slice(value);
+            SnapshotHeader::decode(&bad)
+        };
+
+        assert!(patched(0, b"NOPE").is_err());
+        assert!(patched(4, &3u32.to_le_bytes()).is_err());
+        assert!(patched(8, &0u32.to_le_bytes()).is_err());
+        assert!(patched(8, &u32::MAX.to_le_bytes()).is_err());
+        assert!(patched(12, &u32::MAX.to_le_bytes()).is_err());
+        assert!(patched(16, &u64::MAX.to_le_bytes()).is_err());
+        assert!(patched(24, &u64::MAX.to_le_bytes()).is_err());
+        assert!(patched(32, &(1u32 << 20).to_le_bytes()).is_err());
+
+        // A directory pointing past the data region is refused too
+        let header = SnapshotHeader::decode(header).unwrap();
+        let mut coarse = bytes[HEADER_BYTES..HEADER_BYTES + header.coarse_len().unwrap()].to_vec();
+        coarse[..8].copy_from_slice(&(bytes.len() as u64).to_le_bytes());
+        assert!(CoarseIndex::decode(&header, &coarse).is_err());
+    }
+
+    #[test]
+    fn unreadable_snapshots_are_not_written() {
+        let options = SnapshotOptions::default();
+        assert!(encode_snapshot(&Flat::new(&[], 0), &[], &[], &options).is_err());
+
+        let wide = vec![0.0f32; MAX_DIMENSIONS + 1];
+        assert!(encode_snapshot(&Flat::new(&wide, wide.len()), &[1], &[], &options).is_err());
+
+        let empty = encode_snapshot(&Flat::new(&[], DIMENSIONS), &[], &[], &options).unwrap();
+        let header = SnapshotHeader::decode(&empty).unwrap();
+        assert_eq!((header.lists, header.count), (0, 0));
+    }
+}
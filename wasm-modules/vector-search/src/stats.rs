@@ -0,0 +1,195 @@
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::{to_js, VectorSearch};
+
+/// Number of equal-width buckets in the norm histogram
+const NORM_BUCKETS: usize = 16;
+
+/// One bucket of the norm histogram, covering `[lower, upper)` (the last
+/// bucket also includes `upper`)
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistogramBucket {
+    pub lower: f64,
+    pub upper: f64,
+    pub count: usize,
+}
+
+/// Summary of a batch of vectors, for spotting broken embeddings before
+/// they are indexed
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CorpusStats {
+    pub count: usize,
+    pub dimensions: usize,
+    /// Per-dimension mean over finite values
+    pub mean: Vec<f64>,
+    /// Per-dimension population variance over finite values
+    pub variance: Vec<f64>,
+    /// L2 norm range and mean over vectors whose values are all finite
+    pub norm_min: f64,
+    pub norm_max: f64,
+    pub norm_mean: f64,
+    pub norm_histogram: Vec<HistogramBucket>,
+    /// Vectors whose values are all zero
+    pub zero_vectors: usize,
+    /// Individual values equal to zero, and their share of all values
+    pub zero_values: usize,
+    pub sparsity: f64,
+    pub nan_values: usize,
+    pub infinite_values: usize,
+    /// Vectors containing at least one NaN or infinite value
+    pub non_finite_vectors: usize,
+}
+
+#[wasm_bindgen]
+impl VectorSearch {
+    /// Compute per-dimension mean and variance, a norm histogram and counts
+    /// of zero, NaN and infinite values over a flattened batch of vectors
+    #[wasm_bindgen(js_name = "computeStats")]
+    pub fn compute_stats(&self, vectors: &[f64], count: usize) -> Result<JsValue, JsValue> {
+        to_js(&self.corpus_stats(vectors, count))
+    }
+}
+
+impl VectorSearch {
+    pub(crate) fn corpus_stats(&self, vectors: &[f64], count: usize) -> CorpusStats {
+        if vectors.len() != count * self.dimensions {
+            panic!("Vectors array size mismatch");
+        }
+
+        let dims = self.dimensions;
+        let mut finite = vec![0usize; dims];
+        let mut mean = vec![0.0f64; dims];
+        let mut m2 = vec![0.0f64; dims];
+
+        let mut norms = Vec::with_capacity(count);
+        let mut zero_vectors = 0;
+        let mut zero_values = 0;
+        let mut nan_values = 0;
+        let mut infinite_values = 0;
+        let mut non_finite_vectors = 0;
+
+        for vec in vectors.chunks_exact(dims) {
+            let mut all_finite = true;
+            let mut all_zero = true;
+            let mut norm = 0.0;
+
+            for (d, &x) in vec.iter().enumerate() {
+                if x.is_nan() {
+                    nan_values += 1;
+                    all_finite = false;
+                    all_zero = false;
+                    continue;
+                }
+                if x.is_infinite() {
+                    infinite_values += 1;
+                    all_finite = false;
+                    all_zero = false;
+                    continue;
+                }
+
+                if x == 0.0 {
+                    zero_values += 1;
+                } else {
+                    all_zero = false;
+                }
+                norm += x * x;
+
+                // Welford's update keeps the variance stable for large batches
+                finite[d] += 1;
+                let delta = x - mean[d];
+                mean[d] += delta / finite[d] as f64;
+                m2[d] += delta * (x - mean[d]);
+            }
+
+            if all_zero {
+                zero_vectors += 1;
+            }
+            if all_finite {
+                norms.push(norm.sqrt());
+            } else {
+                non_finite_vectors += 1;
+            }
+        }
+
+        let variance = m2
+            .iter()
+            .zip(&finite)
+            .map(|(&m2, &n)| if n > 0 { m2 / n as f64 } else { 0.0 })
+            .collect();
+
+        let (norm_min, norm_max) = norms
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &n| {
+                (lo.min(n), hi.max(n))
+            });
+        let (norm_min, norm_max) = if norms.is_empty() {
+            (0.0, 0.0)
+        } else {
+            (norm_min, norm_max)
+        };
+        let norm_mean = if norms.is_empty() {
+            0.0
+        } else {
+            norms.iter().sum::<f64>() / norms.len() as f64
+        };
+
+        let total_values = count * dims;
+
+        CorpusStats {
+            count,
+            dimensions: dims,
+            mean,
+            variance,
+            norm_min,
+            norm_max,
+            norm_mean,
+            norm_histogram: norm_histogram(&norms, norm_min, norm_max),
+            zero_vectors,
+            zero_values,
+            sparsity: if total_values > 0 {
+                zero_values as f64 / total_values as f64
+            } else {
+                0.0
+            },
+            nan_values,
+            infinite_values,
+            non_finite_vectors,
+        }
+    }
+}
+
+fn norm_histogram(norms: &[f64], min: f64, max: f64) -> Vec<HistogramBucket> {
+    if norms.is_empty() {
+        return Vec::new();
+    }
+
+    // A single distinct norm gets a single bucket rather than 16 empty ones
+    let buckets = if max > min { NORM_BUCKETS } else { 1 };
+    let width = (max - min) / buckets as f64;
+
+    let mut histogram: Vec<HistogramBucket> = (0..buckets)
+        .map(|b| HistogramBucket {
+            lower: min + width * b as f64,
+            upper: if b + 1 == buckets {
+                max
+            } else {
+                min + width * (b + 1) as f64
+            },
+            count: 0,
+        })
+        .collect();
+
+    for &norm in norms {
+        let bucket = if width > 0.0 {
+            (((norm - min) / width) as usize).min(buckets - 1)
+        } else {
+            0
+        };
+        histogram[bucket].count += 1;
+    }
+
+    histogram
+}
@@ -0,0 +1,105 @@
+//! Top-k delivered one confirmed result at a time.
+//!
+//! `findTopKStreaming` hands each result to a JS callback as soon as its
+//! rank is final, best first, instead of returning the whole list at the
+//! end, and stops once the callback has seen enough.
+//!
+//! Over a flat array nothing bounds the vectors not yet scored, so no rank
+//! is final until every vector has been: `VectorSearch` scores them all and
+//! then delivers the hits in order. `VectorIndex.findTopKStreaming` walks
+//! its cluster tree best-first and delivers each hit while the walk is
+//! still going, once no unvisited subtree can outrank it.
+
+use wasm_bindgen::prelude::*;
+
+use crate::error::VectorError;
+use crate::events;
+use crate::search::{CandidateFilter, QueryOptions, SearchHit, TopK};
+use crate::trace::ScanCounts;
+use crate::{to_js, VectorSearch};
+
+#[wasm_bindgen]
+impl VectorSearch {
+    /// `search` that calls `onResult(hit, rank)` for each of the top `k`
+    /// results in rank order, returning how many were delivered. The hits
+    /// follow a full scan, see the module docs. Returning `false` from the
+    /// callback stops delivery; an exception thrown by it is rethrown.
+    /// `autoK`, `normalize` and `parents` need the whole result set, so they
+    /// can't be combined with streaming, and `collapseDuplicates` is only
+    /// supported by `VectorIndex`.
+    #[wasm_bindgen(js_name = "findTopKStreaming")]
+    pub fn find_top_k_streaming(
+        &self,
+        query: &[f64],
+        vectors: &[f64],
+        count: usize,
+        k: usize,
+        options: JsValue,
+        on_result: &js_sys::Function,
+    ) -> Result<usize, JsValue> {
+        let options = QueryOptions::from_js(options)?;
+        if options.auto_k.is_some() || options.normalize.is_some() || options.parents.is_some() {
+            return Err(VectorError::InvalidOptions {
+                message: "autoK, normalize and parents cannot be combined with streaming"
+                    .to_string(),
+            }
+            .into());
+        }
+        if options.collapse_duplicates {
+            return Err(VectorError::InvalidOptions {
+                message: "collapseDuplicates is only supported by VectorIndex".to_string(),
+            }
+            .into());
+        }
+
+        let timer = events::start();
+        events::search_started("VectorSearch", k, self.dimensions);
+
+        let mut delivered = 0;
+        for (rank, (index, score)) in self
+            .streamed_top_k(query, vectors, count, k, &options)?
+            .enumerate()
+        {
+            let hit = SearchHit {
+                index,
+                score: options.reported_score(score),
+                normalized: None,
+            };
+            delivered += 1;
+            let verdict = on_result.call2(&JsValue::NULL, &to_js(&hit)?, &JsValue::from(rank))?;
+            if verdict == JsValue::FALSE {
+                break;
+            }
+        }
+
+        let scan = ScanCounts {
+            vectors: count,
+            ..Default::default()
+        };
+        events::search_completed("VectorSearch", timer, &scan, delivered, false);
+        Ok(delivered)
+    }
+}
+
+impl VectorSearch {
+    /// Accepted top-`k` candidates of a full scan, yielded best-first
+    pub(crate) fn streamed_top_k(
+        &self,
+        query: &[f64],
+        vectors: &[f64],
+        count: usize,
+        k: usize,
+        options: &QueryOptions,
+    ) -> Result<impl Iterator<Item = (usize, f64)>, VectorError> {
+        let scores = self.score_all(query, vectors, count, options)?;
+        let filter = CandidateFilter::new(options, count)?;
+
+        let mut top = TopK::new(k, options.metric);
+        for (index, &score) in scores.iter().enumerate() {
+            if filter.accepts(index) {
+                top.push(index, score);
+            }
+        }
+        Ok(top.into_best_first())
+    }
+}
@@ -0,0 +1,171 @@
+//! Scoring and top-k restricted to caller-chosen candidate positions.
+//!
+//! Candidates pre-filtered in JS (e.g. by keyword match) are scored in place
+//! in the full corpus buffer instead of being copied into a new one first.
+//! They are given either as a list of positions or as a bitmap with bit
+//! `i % 8` of byte `i / 8` set for position `i`.
+
+use wasm_bindgen::prelude::*;
+
+use crate::calibration::normalized_scores;
+use crate::cutoff::apply_auto_k;
+use crate::error::VectorError;
+use crate::events;
+use crate::search::{CandidateFilter, QueryOptions, SearchHit, TopK};
+use crate::trace::ScanCounts;
+use crate::{to_js, VectorSearch};
+
+/// Candidate positions within a corpus of `count` vectors
+pub(crate) enum Candidates {
+    List(Vec<u32>),
+    Bitmap(Vec<u8>),
+}
+
+impl Candidates {
+    /// Read a `Uint32Array` (or array) of positions, or a `Uint8Array`
+    /// bitmap
+    pub(crate) fn from_js(value: JsValue) -> Result<Self, VectorError> {
+        if let Some(bitmap) = value.dyn_ref::<js_sys::Uint8Array>() {
+            return Ok(Candidates::Bitmap(bitmap.to_vec()));
+        }
+        if let Some(list) = value.dyn_ref::<js_sys::Uint32Array>() {
+            return Ok(Candidates::List(list.to_vec()));
+        }
+
+        serde_wasm_bindgen::from_value(value)
+            .map(Candidates::List)
+            .map_err(|_| VectorError::InvalidOptions {
+                message: "candidates must be a Uint32Array of positions or a Uint8Array bitmap"
+                    .to_string(),
+            })
+    }
+
+    /// Distinct positions in ascending order
+    pub(crate) fn positions(&self, count: usize) -> Vec<usize> {
+        match self {
+            Candidates::List(list) => {
+                let mut positions: Vec<usize> = list.iter().map(|&i| i as usize).collect();
+                positions.sort_unstable();
+                positions.dedup();
+                if positions.last().is_some_and(|&last| last >= count) {
+                    panic!("Candidate index out of range");
+                }
+                positions
+            }
+            Candidates::Bitmap(bitmap) => {
+                if bitmap.len() != count.div_ceil(8) {
+                    panic!("Candidate bitmap size mismatch");
+                }
+                (0..count)
+                    .filter(|&i| bitmap[i / 8] & (1 << (i % 8)) != 0)
+                    .collect()
+            }
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl VectorSearch {
+    /// Score only the vectors at `indices`, returning one score per index in
+    /// the order given. Scores match `batchScore` at the same positions.
+    #[wasm_bindgen(js_name = "scoreSubset")]
+    pub fn score_subset(
+        &self,
+        query: &[f64],
+        vectors: &[f64],
+        count: usize,
+        indices: &[u32],
+        options: JsValue,
+    ) -> Result<Vec<f64>, JsValue> {
+        let options = QueryOptions::from_js(options)?;
+        let scorer = self.scorer(query, vectors, count, &options);
+
+        let mut scores = Vec::with_capacity(indices.len());
+        for &i in indices {
+            let i = i as usize;
+            if i >= count {
+                panic!("Candidate index out of range");
+            }
+            let score = scorer.score(&vectors[i * self.dimensions..(i + 1) * self.dimensions]);
+            scorer.check()?;
+            scores.push(options.reported_score(score));
+        }
+        Ok(scores)
+    }
+
+    /// `search` over only the `candidates` positions, given as a
+    /// `Uint32Array` list or a `Uint8Array` bitmap. `exclude` and group
+    /// filters still apply on top.
+    #[wasm_bindgen(js_name = "searchSubset")]
+    pub fn search_subset(
+        &self,
+        query: &[f64],
+        vectors: &[f64],
+        count: usize,
+        candidates: JsValue,
+        k: usize,
+        options: JsValue,
+    ) -> Result<JsValue, JsValue> {
+        let candidates = Candidates::from_js(candidates)?;
+        let options = QueryOptions::from_js(options)?;
+        let timer = events::start();
+        events::search_started("VectorSearch", k, self.dimensions);
+
+        let positions = candidates.positions(count);
+        let hits = self.subset_hits(query, vectors, count, &positions, k, &options)?;
+        let scan = ScanCounts {
+            vectors: positions.len(),
+            ..Default::default()
+        };
+        events::search_completed("VectorSearch", timer, &scan, hits.len(), false);
+        to_js(&hits)
+    }
+}
+
+impl VectorSearch {
+    pub(crate) fn subset_hits(
+        &self,
+        query: &[f64],
+        vectors: &[f64],
+        count: usize,
+        positions: &[usize],
+        k: usize,
+        options: &QueryOptions,
+    ) -> Result<Vec<SearchHit>, VectorError> {
+        if options.parents.is_some() {
+            return Err(VectorError::InvalidOptions {
+                message: "parents cannot be combined with subset search".to_string(),
+            });
+        }
+
+        let scorer = self.scorer(query, vectors, count, options);
+        let filter = CandidateFilter::new(options, count)?;
+
+        let mut top = TopK::new(k, options.metric);
+        for &i in positions {
+            if filter.accepts(i) {
+                top.push(
+                    i,
+                    scorer.score(&vectors[i * self.dimensions..(i + 1) * self.dimensions]),
+                );
+                scorer.check()?;
+            }
+        }
+
+        let mut ranked = top.into_sorted();
+        if let Some(auto_k) = &options.auto_k {
+            apply_auto_k(&mut ranked, auto_k, k);
+        }
+
+        let normalized = normalized_scores(&ranked, options);
+        Ok(ranked
+            .into_iter()
+            .zip(normalized)
+            .map(|((index, score), normalized)| SearchHit {
+                index,
+                score: options.reported_score(score),
+                normalized,
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,120 @@
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::cluster_tree::kmeans;
+use crate::rng::SplitMix64;
+use crate::rows::Flat;
+use crate::{to_js, VectorSearch};
+
+/// Lloyd iterations run by `summarize`
+const SUMMARY_ITERATIONS: usize = 20;
+
+/// Fixed seed, so the same corpus always summarizes the same way
+const SUMMARY_SEED: u64 = 42;
+
+/// One cluster of a corpus summary
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SummaryCluster {
+    /// Mean of the cluster's members
+    pub centroid: Vec<f64>,
+    /// Number of vectors assigned to the cluster
+    pub members: usize,
+    /// Index of the member closest to the centroid, for labelling the
+    /// cluster with a real item
+    pub representative: usize,
+}
+
+#[wasm_bindgen]
+impl VectorSearch {
+    /// Cluster a flattened batch of vectors into at most `num_centroids`
+    /// groups with k-means, returning one `SummaryCluster` per non-empty
+    /// cluster, largest first
+    pub fn summarize(
+        &self,
+        vectors: &[f64],
+        count: usize,
+        num_centroids: usize,
+    ) -> Result<JsValue, JsValue> {
+        to_js(&self.summary(vectors, count, num_centroids))
+    }
+}
+
+impl VectorSearch {
+    pub(crate) fn summary(
+        &self,
+        vectors: &[f64],
+        count: usize,
+        num_centroids: usize,
+    ) -> Vec<SummaryCluster> {
+        if vectors.len() != count * self.dimensions {
+            panic!("Vectors array size mismatch");
+        }
+
+        let dims = self.dimensions;
+        let k = num_centroids.min(count);
+        if k == 0 {
+            return Vec::new();
+        }
+
+        // Clustering runs at f32 like the index builds; centroids and
+        // representatives are computed from the full-precision input
+        let narrowed: Vec<f32> = vectors.iter().map(|&x| x as f32).collect();
+        let members: Vec<usize> = (0..count).collect();
+        let mut rng = SplitMix64::new(SUMMARY_SEED);
+        let assignment = kmeans(
+            &Flat::new(&narrowed, dims),
+            &members,
+            k,
+            SUMMARY_ITERATIONS,
+            &mut rng,
+        );
+
+        let row = |i: usize| &vectors[i * dims..(i + 1) * dims];
+        let mut sums = vec![0.0f64; k * dims];
+        let mut sizes = vec![0usize; k];
+        for (i, &cluster) in assignment.iter().enumerate() {
+            sizes[cluster] += 1;
+            for (s, &x) in sums[cluster * dims..(cluster + 1) * dims]
+                .iter_mut()
+                .zip(row(i))
+            {
+                *s += x;
+            }
+        }
+
+        for (cluster, sum) in sums.chunks_exact_mut(dims).enumerate() {
+            let scale = 1.0 / sizes[cluster].max(1) as f64;
+            for s in sum {
+                *s *= scale;
+            }
+        }
+        let centroids = sums;
+
+        let mut representatives: Vec<Option<(usize, f64)>> = vec![None; k];
+        for (i, &cluster) in assignment.iter().enumerate() {
+            let distance: f64 = row(i)
+                .iter()
+                .zip(&centroids[cluster * dims..(cluster + 1) * dims])
+                .map(|(x, c)| (x - c) * (x - c))
+                .sum();
+            let best = &mut representatives[cluster];
+            if best.is_none_or(|(_, nearest)| distance < nearest) {
+                *best = Some((i, distance));
+            }
+        }
+
+        let mut clusters: Vec<SummaryCluster> = (0..k)
+            .filter(|&cluster| sizes[cluster] > 0)
+            .map(|cluster| SummaryCluster {
+                centroid: centroids[cluster * dims..(cluster + 1) * dims].to_vec(),
+                members: sizes[cluster],
+                representative: representatives[cluster].map_or(0, |(i, _)| i),
+            })
+            .collect();
+
+        // Stable, so equal-sized clusters keep their k-means order
+        clusters.sort_by_key(|cluster| std::cmp::Reverse(cluster.members));
+        clusters
+    }
+}
@@ -0,0 +1,124 @@
+//! Reproducible synthetic datasets for benchmarks and tests.
+//!
+//! Generating vectors inside WASM avoids building large arrays in JS and
+//! copying them across the boundary, and the output depends only on the
+//! seed and the sequence of calls, on every platform.
+
+use wasm_bindgen::prelude::*;
+
+use crate::rng::SplitMix64;
+
+/// Seeded generator of flattened f32 vector batches.
+///
+/// Each call advances the generator, so a given seed followed by the same
+/// sequence of calls always produces the same data.
+#[wasm_bindgen]
+pub struct VectorTestData {
+    rng: SplitMix64,
+}
+
+/// Vectors drawn around known centroids, with the centroid each came from
+#[wasm_bindgen]
+pub struct ClusteredData {
+    dimensions: usize,
+    vectors: Vec<f32>,
+    centroids: Vec<f32>,
+    labels: Vec<u32>,
+}
+
+#[wasm_bindgen]
+impl VectorTestData {
+    /// Seeds cross the boundary as plain numbers and are truncated to
+    /// integers
+    #[wasm_bindgen(constructor)]
+    pub fn new(seed: f64) -> Self {
+        Self {
+            rng: SplitMix64::new(seed as u64),
+        }
+    }
+
+    /// `count` vectors with components uniform in `[min, max)`
+    pub fn uniform(&mut self, count: usize, dimensions: usize, min: f32, max: f32) -> Vec<f32> {
+        let span = (max - min) as f64;
+        (0..count * dimensions)
+            .map(|_| (min as f64 + self.rng.next_f64() * span) as f32)
+            .collect()
+    }
+
+    /// `count` vectors with independent normally distributed components
+    pub fn gaussian(
+        &mut self,
+        count: usize,
+        dimensions: usize,
+        mean: f32,
+        std_dev: f32,
+    ) -> Vec<f32> {
+        (0..count * dimensions)
+            .map(|_| (mean as f64 + self.rng.next_gaussian() * std_dev as f64) as f32)
+            .collect()
+    }
+
+    /// `count` vectors split round-robin across `clusters` Gaussian blobs.
+    ///
+    /// Centroids are uniform in `[-1, 1)` per component and each vector is
+    /// its centroid plus normal noise with standard deviation `spread`.
+    pub fn blobs(
+        &mut self,
+        count: usize,
+        dimensions: usize,
+        clusters: usize,
+        spread: f32,
+    ) -> ClusteredData {
+        if clusters == 0 {
+            panic!("At least one cluster is required");
+        }
+
+        let centroids = self.uniform(clusters, dimensions, -1.0, 1.0);
+        let mut vectors = Vec::with_capacity(count * dimensions);
+        let mut labels = Vec::with_capacity(count);
+
+        for i in 0..count {
+            let label = i % clusters;
+            let centroid = &centroids[label * dimensions..(label + 1) * dimensions];
+            vectors.extend(
+                centroid
+                    .iter()
+                    .map(|&c| (c as f64 + self.rng.next_gaussian() * spread as f64) as f32),
+            );
+            labels.push(label as u32);
+        }
+
+        ClusteredData {
+            dimensions,
+            vectors,
+            centroids,
+            labels,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl ClusteredData {
+    #[wasm_bindgen(getter)]
+    pub fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    /// Generated vectors, flattened
+    #[wasm_bindgen(getter)]
+    pub fn vectors(&self) -> Vec<f32> {
+        self.vectors.clone()
+    }
+
+    /// Cluster centres, flattened
+    #[wasm_bindgen(getter)]
+    pub fn centroids(&self) -> Vec<f32> {
+        self.centroids.clone()
+    }
+
+    /// Index into `centroids` of the blob each vector was drawn from
+    #[wasm_bindgen(getter)]
+    pub fn labels(&self) -> Vec<u32> {
+        self.labels.clone()
+    }
+}
@@ -0,0 +1,100 @@
+//! Per-query read-path instrumentation, returned when a query sets
+//! `trace: true`.
+//!
+//! Byte counts are estimates from the storage layout (what a scan must read,
+//! not what the cache hierarchy actually moved), which is enough to compare
+//! effective bandwidth against a device's nominal figure: a query running
+//! near it is memory-bound and benefits from a smaller codec, one far below
+//! it is compute-bound.
+
+use serde::Serialize;
+use wasm_bindgen::JsCast;
+
+/// Which read path answered the query
+#[derive(Clone, Copy, Debug, Default, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ScanStrategy {
+    /// Every stored vector scored at full precision
+    #[default]
+    Flat,
+    /// Beam search over the cluster tree
+    ClusterTree,
+    /// Compressed-code scan followed by re-ranking
+    Quantized,
+}
+
+/// Work counted while answering one query
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct ScanCounts {
+    pub strategy: ScanStrategy,
+    /// Stored vectors scored exactly
+    pub vectors: usize,
+    /// Stored vectors skipped after a partial read by `prune`
+    pub pruned: usize,
+    /// Compressed codes scored
+    pub codes: usize,
+    /// Cluster-tree centroids scored
+    pub centroids: usize,
+}
+
+/// Read-path statistics for one query
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryTrace {
+    pub strategy: ScanStrategy,
+    pub vectors_scored: usize,
+    /// Vectors skipped by `prune`; being only partly read, they are left
+    /// out of `bytes_scanned`
+    pub vectors_pruned: usize,
+    pub codes_scanned: usize,
+    pub centroids_scored: usize,
+    /// Estimated bytes read from vector, code and centroid storage
+    pub bytes_scanned: u64,
+    pub elapsed_ms: f64,
+    /// `bytes_scanned` over `elapsed_ms`, or `None` when the query finished
+    /// below the timer's resolution
+    pub gb_per_second: Option<f64>,
+}
+
+/// Per-item sizes used to turn `ScanCounts` into bytes
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ScanCosts {
+    pub vector_bytes: usize,
+    pub code_bytes: usize,
+    pub centroid_bytes: usize,
+}
+
+impl QueryTrace {
+    pub(crate) fn new(counts: ScanCounts, costs: ScanCosts, elapsed_ms: f64) -> Self {
+        let bytes_scanned = (counts.vectors * costs.vector_bytes
+            + counts.codes * costs.code_bytes
+            + counts.centroids * costs.centroid_bytes) as u64;
+
+        Self {
+            strategy: counts.strategy,
+            vectors_scored: counts.vectors,
+            vectors_pruned: counts.pruned,
+            codes_scanned: counts.codes,
+            centroids_scored: counts.centroids,
+            bytes_scanned,
+            elapsed_ms,
+            gb_per_second: (elapsed_ms > 0.0).then(|| bytes_scanned as f64 / (elapsed_ms * 1e6)),
+        }
+    }
+}
+
+/// High-resolution timestamp in milliseconds: `performance.now()` where the
+/// global scope has it (windows and workers), `Date.now()` otherwise
+pub(crate) fn now_ms() -> f64 {
+    let global = js_sys::global();
+    let now = js_sys::Reflect::get(&global, &"performance".into())
+        .ok()
+        .filter(|performance| performance.is_object())
+        .and_then(|performance| {
+            let now = js_sys::Reflect::get(&performance, &"now".into()).ok()?;
+            let now: js_sys::Function = now.dyn_into().ok()?;
+            now.call0(&performance).ok()?.as_f64()
+        });
+
+    now.unwrap_or_else(js_sys::Date::now)
+}
@@ -0,0 +1,183 @@
+//! Strict checks that keep corrupt embeddings out of similarity math.
+//!
+//! A single NaN turns every score it touches into NaN, which then sorts
+//! unpredictably, and an infinite component or an all-zero vector does much
+//! the same to cosine scores. With validation enabled, such vectors are
+//! either rejected with an `InvalidVector` error naming the record and
+//! dimension, or repaired according to `ValidationOptions`.
+
+use std::borrow::Cow;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::error::VectorError;
+use crate::{to_js, VectorSearch};
+
+/// What to do with vectors that fail validation
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ValidationMode {
+    /// No checks; vectors are used as given
+    #[default]
+    Off,
+    /// Fail with an `InvalidVector` error
+    Reject,
+    /// Replace non-finite components with `replacement`
+    Sanitize,
+}
+
+/// Validation settings for ingest and queries
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ValidationOptions {
+    pub mode: ValidationMode,
+    /// Value substituted for NaN and infinite components by `sanitize`
+    pub replacement: f32,
+    /// Whether vectors with zero norm fail validation. Sanitizing cannot
+    /// give them a direction, so they are rejected in both modes.
+    pub reject_zero_norm: bool,
+}
+
+impl Default for ValidationOptions {
+    fn default() -> Self {
+        Self {
+            mode: ValidationMode::Off,
+            replacement: 0.0,
+            reject_zero_norm: true,
+        }
+    }
+}
+
+/// Why a vector failed validation
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum VectorProblem {
+    Nan,
+    Infinite,
+    ZeroNorm,
+}
+
+impl fmt::Display for VectorProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VectorProblem::Nan => write!(f, "NaN"),
+            VectorProblem::Infinite => write!(f, "an infinite value"),
+            VectorProblem::ZeroNorm => write!(f, "zero norm"),
+        }
+    }
+}
+
+/// The first problem found in one vector of a batch
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VectorDefect {
+    /// Position of the vector in the batch
+    pub index: usize,
+    /// Offending component, absent for whole-vector problems
+    pub dimension: Option<usize>,
+    pub problem: VectorProblem,
+    /// Number of NaN or infinite components in the vector
+    pub non_finite: usize,
+}
+
+fn first_non_finite(vector: &[f32]) -> Option<(usize, VectorProblem)> {
+    vector.iter().position(|x| !x.is_finite()).map(|dimension| {
+        let problem = if vector[dimension].is_nan() {
+            VectorProblem::Nan
+        } else {
+            VectorProblem::Infinite
+        };
+        (dimension, problem)
+    })
+}
+
+fn is_zero_norm(vector: &[f32]) -> bool {
+    vector.iter().all(|&x| x == 0.0)
+}
+
+impl ValidationOptions {
+    /// Check `vector` against these settings, returning it unchanged, a
+    /// sanitized copy, or an error naming `index` (`None` for queries)
+    pub(crate) fn check<'a>(
+        &self,
+        vector: &'a [f32],
+        index: Option<usize>,
+    ) -> Result<Cow<'a, [f32]>, VectorError> {
+        let invalid =
+            |dimension: Option<usize>, problem: VectorProblem| VectorError::InvalidVector {
+                index,
+                dimension,
+                problem,
+            };
+
+        let vector = match self.mode {
+            ValidationMode::Off => return Ok(Cow::Borrowed(vector)),
+            ValidationMode::Reject => {
+                if let Some((dimension, problem)) = first_non_finite(vector) {
+                    return Err(invalid(Some(dimension), problem));
+                }
+                Cow::Borrowed(vector)
+            }
+            ValidationMode::Sanitize => {
+                if first_non_finite(vector).is_some() {
+                    let replacement = self.replacement;
+                    Cow::Owned(
+                        vector
+                            .iter()
+                            .map(|&x| if x.is_finite() { x } else { replacement })
+                            .collect(),
+                    )
+                } else {
+                    Cow::Borrowed(vector)
+                }
+            }
+        };
+
+        if self.reject_zero_norm && is_zero_norm(&vector) {
+            return Err(invalid(None, VectorProblem::ZeroNorm));
+        }
+        Ok(vector)
+    }
+}
+
+#[wasm_bindgen]
+impl VectorSearch {
+    /// Report every vector in a flattened batch that contains NaN or
+    /// infinite values or has zero norm, without changing anything
+    #[wasm_bindgen(js_name = "validateVectors")]
+    pub fn validate_vectors(&self, vectors: &[f32], count: usize) -> Result<JsValue, JsValue> {
+        to_js(&self.vector_defects(vectors, count))
+    }
+}
+
+impl VectorSearch {
+    pub(crate) fn vector_defects(&self, vectors: &[f32], count: usize) -> Vec<VectorDefect> {
+        if vectors.len() != count * self.dimensions {
+            panic!("Vectors array size mismatch");
+        }
+        if self.dimensions == 0 {
+            return Vec::new();
+        }
+
+        vectors
+            .chunks_exact(self.dimensions)
+            .enumerate()
+            .filter_map(|(index, vector)| {
+                let (dimension, problem) = match first_non_finite(vector) {
+                    Some((dimension, problem)) => (Some(dimension), problem),
+                    None if is_zero_norm(vector) => (None, VectorProblem::ZeroNorm),
+                    None => return None,
+                };
+
+                Some(VectorDefect {
+                    index,
+                    dimension,
+                    problem,
+                    non_finite: vector.iter().filter(|x| !x.is_finite()).count(),
+                })
+            })
+            .collect()
+    }
+}